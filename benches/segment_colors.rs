@@ -0,0 +1,111 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use afterglow::segment_map::{
+    self, average_indexed_segment_colors, average_segment_colors, build_segment_map, Orientation,
+    SegmentIndex, SegmentMap, DEFAULT_EDGE_FRACTION,
+};
+
+const NUM_LEDS: usize = 36;
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+fn segment_colors(c: &mut Criterion) {
+    let segment_map = build_segment_map(
+        NUM_LEDS,
+        WIDTH,
+        HEIGHT,
+        Orientation::default(),
+        DEFAULT_EDGE_FRACTION,
+        None,
+    );
+    let indexed_map = SegmentMap::new(segment_map.clone(), WIDTH, NUM_LEDS);
+    let rgb: Vec<u8> = (0..WIDTH * HEIGHT * 3)
+        .map(|index| (index % 251) as u8)
+        .collect();
+
+    let mut group = c.benchmark_group("segment_colors_1080p");
+    group.bench_function("pixel_table", |b| {
+        b.iter(|| average_segment_colors(black_box(&rgb), &segment_map, NUM_LEDS))
+    });
+    for factor in [1, 2, 4] {
+        let segment_index = SegmentIndex::new(&indexed_map.subsample(factor));
+        group.bench_with_input(
+            BenchmarkId::new("subsampled_index", factor),
+            &segment_index,
+            |b, segment_index| {
+                b.iter(|| average_indexed_segment_colors(black_box(&rgb), segment_index))
+            },
+        );
+    }
+    let segment_index = SegmentIndex::new(&indexed_map.subsample(1));
+    group.bench_function("serial_index", |b| {
+        b.iter(|| {
+            segment_map::average_indexed_segment_colors_serial(black_box(&rgb), &segment_index)
+        })
+    });
+    #[cfg(feature = "rayon")]
+    group.bench_function("parallel_index", |b| {
+        b.iter(|| {
+            segment_map::average_indexed_segment_colors_parallel(black_box(&rgb), &segment_index)
+        })
+    });
+    group.finish();
+}
+
+fn accumulate_pixels(c: &mut Criterion) {
+    let segment_map = build_segment_map(
+        NUM_LEDS,
+        WIDTH,
+        HEIGHT,
+        Orientation::default(),
+        DEFAULT_EDGE_FRACTION,
+        None,
+    );
+    let rgb: Vec<u8> = (0..WIDTH * HEIGHT * 3)
+        .map(|index| (index % 251) as u8)
+        .collect();
+
+    let (pixel_indices, segment_indices) =
+        SegmentMap::new(segment_map.clone(), WIDTH, NUM_LEDS).to_flat_lookup();
+
+    let mut group = c.benchmark_group("accumulate_pixels_1080p");
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            let mut sums = vec![(0, 0, 0); NUM_LEDS];
+            let mut counts = vec![0; NUM_LEDS];
+            segment_map::accumulate_pixels(black_box(&rgb), &segment_map, &mut sums, &mut counts);
+            sums
+        })
+    });
+    group.bench_function("flat_lookup", |b| {
+        b.iter(|| {
+            let mut sums = vec![(0, 0, 0); NUM_LEDS];
+            let mut counts = vec![0; NUM_LEDS];
+            segment_map::accumulate_flat_lookup(
+                black_box(&rgb),
+                (&pixel_indices, &segment_indices),
+                &mut sums,
+                &mut counts,
+            );
+            sums
+        })
+    });
+    #[cfg(feature = "simd")]
+    group.bench_function("simd", |b| {
+        b.iter(|| {
+            let mut sums = vec![(0, 0, 0); NUM_LEDS];
+            let mut counts = vec![0; NUM_LEDS];
+            segment_map::accumulate_pixels_simd(
+                black_box(&rgb),
+                &segment_map,
+                &mut sums,
+                &mut counts,
+            );
+            sums
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, segment_colors, accumulate_pixels);
+criterion_main!(benches);
@@ -1,31 +1,149 @@
 #![deny(clippy::all)]
 
-mod led;
+mod api;
+mod http;
+mod metrics;
+mod output;
+mod segment_cache;
+mod self_test;
+mod shutdown;
+mod smoothing;
+mod state;
 
+use afterglow::brightness::mean_luminance;
+use afterglow::camera_format::{describe_device, format_mismatch, supported_formats, FormatOption};
+use afterglow::config::{Config, OutputKind};
+use afterglow::effects::{BreathEffect, ChaseEffect, EffectKind, RainbowEffect};
+use afterglow::frame_buffer::ReusableFrameBuffer;
+use afterglow::led::{LEDStrip, LEDStripBuilder};
+use afterglow::logging::init_logging;
+use afterglow::power::{estimate_milliamps, limit_power};
+use afterglow::segment_map::{average_indexed_segment_colors, zone_ranges};
+#[cfg(feature = "rpi")]
+use afterglow::spi_settings::ClockFallback;
+use afterglow::spi_settings::SpiSettings;
+use afterglow::test_pattern::TestPattern;
+use api::{serve_api, ApiSink, ApiState};
+use clap::{Parser, Subcommand};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Select;
-use led::LEDStrip;
+use metrics::{serve_metrics, MeteredSink, Metrics};
 use nokhwa::pixel_format::RgbFormat;
 use nokhwa::utils::{
-    CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType, Resolution,
+    CameraFormat, CameraIndex, CameraInfo, RequestedFormat, RequestedFormatType, Resolution,
 };
-use nokhwa::Camera;
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+use nokhwa::{Buffer, Camera};
+use output::{
+    replay_frames, AdaptiveSink, DmxUsbSink, DryRunSink, FanOutSink, FrameLogReader, FrameLogSink,
+    GifSink, HyperionSink, KeepAliveSink, MockSpiSink, MqttSink, OscSink, OutputSink, SplitSink,
+    StdoutSink, TcpFrameSource, TcpSink, TerminalSink, UdpBroadcastSink, UdpFrameSource, WsServer,
+};
+#[cfg(feature = "rpi")]
+use output::{I2cOutputSink, RetryPolicy, RetryingSink, SpiSink};
+use segment_cache::{default_cache_dir, load_or_build};
+use self_test::{run_boot_sequence, run_led_walk};
+use shutdown::{fade_out, idle_fade_factor, install_signal_handlers, FADE_STEP};
+use smoothing::{
+    blur_leds, DeadBandFilter, FlickerDetector, FrameHistory, HysteresisFilter, SceneChangeDetector,
+};
+use state::StatePersister;
 use std::{
-    cmp::Ordering,
-    f64::consts::{PI, TAU},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{self, AtomicBool},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
+use tracing::{debug_span, error, info, instrument, warn};
 
-fn prompt_camera_device() -> CameraIndex {
+const NUM_LEDS: usize = 36;
+const SIGNAL_LOSS_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    config: Config,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Play a frame log recorded with --record-frames back to the outputs
+    Replay {
+        /// Frame log to play back
+        path: PathBuf,
+
+        /// Playback speed relative to the recording (e.g. 2.0 for double
+        /// speed)
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
+    /// Print the available cameras and their capture formats, then exit
+    ListDevices,
+    /// Write the configured segment map as a color-coded PNG without opening a
+    /// camera
+    Map {
+        /// PNG file to write
+        #[arg(long, value_name = "PATH")]
+        png: PathBuf,
+
+        /// Capture width to build the segment map for
+        #[arg(long, default_value_t = 1280)]
+        width: u32,
+
+        /// Capture height to build the segment map for
+        #[arg(long, default_value_t = 720)]
+        height: u32,
+
+        /// Number of LEDs to split the segment map into
+        #[arg(long, default_value_t = NUM_LEDS)]
+        leds: usize,
+
+        /// Stamp each segment with its LED index
+        #[arg(long)]
+        labels: bool,
+    },
+}
+
+fn query_devices() -> Vec<CameraInfo> {
     let mut devices =
         nokhwa::query(nokhwa::utils::ApiBackend::Auto).expect("Unable to query video devices");
+    devices.sort_by_key(|device| device.index().clone());
+
+    devices
+}
+
+fn list_devices() {
+    for device in query_devices() {
+        let formats = match Camera::new(
+            device.index().clone(),
+            RequestedFormat::new::<RgbFormat>(RequestedFormatType::None),
+        ) {
+            Ok(mut camera) => supported_formats(&mut camera),
+            Err(err) => {
+                warn!("Unable to open {}: {}", device.human_name(), err);
+                Vec::new()
+            }
+        };
+        print!("{}", describe_device(&device, &formats));
+    }
+}
+
+fn prompt_camera_device() -> CameraIndex {
+    let devices = query_devices();
     if devices.is_empty() {
         panic!("No devices found");
     }
 
-    devices.sort_by_key(|device| device.index().clone());
     let device_options: Vec<String> = devices
         .iter()
         .map(|device| format!("{} ({})", device.human_name(), device.description()))
@@ -41,41 +159,26 @@ fn prompt_camera_device() -> CameraIndex {
     devices[selection].index().clone()
 }
 
-fn prompt_camera(camera_index: CameraIndex) -> Camera {
+fn prompt_camera(camera_index: CameraIndex, strict_format: bool) -> Camera {
     let mut camera = Camera::new(
         camera_index,
         RequestedFormat::new::<RgbFormat>(RequestedFormatType::None),
     )
     .expect("Unable to build camera");
-    let camera_resolutions = camera
-        .compatible_list_by_resolution(FrameFormat::YUYV)
-        .expect("Unable to get available camera resolutions");
-
-    let mut resolutions: Vec<&Resolution> = camera_resolutions.keys().collect();
-    resolutions.sort_by(|a, b| match a.width().cmp(&b.width()) {
-        Ordering::Equal => a.height().cmp(&b.height()),
-        ord => ord,
-    });
-    let resolution_options: Vec<String> = resolutions
-        .iter()
-        .map(|resolution| {
-            format!(
-                "{}\t(fps options: {:?})",
-                resolution,
-                camera_resolutions.get(resolution).unwrap()
-            )
-        })
-        .collect();
-    let selected_resolution_index = Select::with_theme(&ColorfulTheme::default())
+    let format_options = supported_formats(&mut camera);
+    if format_options.is_empty() {
+        panic!("No YUYV or MJPEG capture formats available");
+    }
+
+    let format_labels: Vec<String> = format_options.iter().map(FormatOption::label).collect();
+    let selected_format = &format_options[Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Select capture resolution")
-        .items(&resolution_options)
+        .items(&format_labels)
         .default(0)
         .interact()
-        .expect("Must choose a resolution");
+        .expect("Must choose a resolution")];
 
-    let fps_options = camera_resolutions
-        .get(resolutions[selected_resolution_index])
-        .unwrap();
+    let fps_options = &selected_format.frame_rates;
     let selected_fps_index = Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Select capture fps")
         .items(fps_options)
@@ -83,101 +186,842 @@ fn prompt_camera(camera_index: CameraIndex) -> Camera {
         .interact()
         .expect("Must choose an fps option");
 
+    let requested_format = CameraFormat::new(
+        selected_format.resolution,
+        selected_format.format,
+        fps_options[selected_fps_index],
+    );
     camera
         .set_camera_requset(RequestedFormat::new::<RgbFormat>(
-            RequestedFormatType::Closest(CameraFormat::new(
-                *resolutions[selected_resolution_index],
-                FrameFormat::YUYV,
-                fps_options[selected_fps_index],
-            )),
+            RequestedFormatType::Closest(requested_format),
         ))
         .expect("Failed to set camera format");
 
+    if let Some(mismatch) =
+        format_mismatch(&requested_format, camera.resolution(), camera.frame_rate())
+    {
+        if strict_format {
+            panic!("{}", mismatch);
+        }
+        warn!("{}", mismatch);
+    }
+
     camera
 }
 
-fn build_segment_map(num_leds: usize, width: u32, height: u32) -> Vec<Option<usize>> {
-    let mut segment_table: Vec<Option<usize>> =
-        Vec::with_capacity((width * height).try_into().unwrap());
+// The camera is opened on the capture thread since it cannot be moved between
+// threads, which lets the render loop keep running while frames stall
+fn spawn_capture(
+    strict_format: bool,
+) -> io::Result<(Resolution, u32, Receiver<io::Result<Buffer>>)> {
+    let (opened_tx, opened_rx) = mpsc::channel();
+    let (frame_tx, frame_rx) = mpsc::sync_channel(1);
+    thread::spawn(move || {
+        let mut camera = prompt_camera(prompt_camera_device(), strict_format);
+        let opened = camera.open_stream().map_err(io::Error::other);
+        let failed = opened.is_err();
+        if opened_tx
+            .send(opened.map(|_| (camera.resolution(), camera.frame_rate())))
+            .is_err()
+            || failed
+        {
+            return;
+        }
+
+        loop {
+            let frame = camera.frame().map_err(io::Error::other);
+            let failed = frame.is_err();
+            if frame_tx.send(frame).is_err() || failed {
+                return;
+            }
+        }
+    });
 
-    let width = width as i32;
-    let height = height as i32;
-    let half_width = width / 2;
-    let half_height = height / 2;
-    let edge = half_width.min(half_height) / 2;
+    let (resolution, frame_rate) = opened_rx
+        .recv()
+        .map_err(|_| io::Error::other("Camera capture stopped"))??;
+    Ok((resolution, frame_rate, frame_rx))
+}
 
-    let theta_scalar = (num_leds as f64) / TAU;
+fn build_mock_sink(config: &Config) -> MockSpiSink<Box<dyn Write + Send>> {
+    let writer: Box<dyn Write + Send> = match &config.mock_log {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).expect("Unable to create mock output log"),
+        )),
+        None => Box::new(io::sink()),
+    };
 
-    for y in 0..height {
-        let dy = (y - half_height) as f64;
-        for x in 0..width {
-            let dx = (half_width - x) as f64;
-            segment_table.push(if dx.hypot(dy) >= edge.into() {
-                let theta = dy.atan2(dx) + PI;
-                let segment = ((theta * theta_scalar).floor() as usize).min(num_leds - 1);
-                Some(segment)
-            } else {
-                None
-            });
+    let sink = MockSpiSink::new(writer);
+    match (config.mock_min_fps, config.mock_max_fps) {
+        (None, None) => sink,
+        (min_fps, max_fps) => {
+            sink.with_frame_rate_bounds(min_fps.unwrap_or(0.0)..=max_fps.unwrap_or(f32::INFINITY))
         }
     }
+}
+
+#[cfg(not(feature = "rpi"))]
+fn build_spi_sink(settings: SpiSettings, config: &Config) -> impl OutputSink + Send {
+    warn!(
+        "Built without the rpi feature, writing LED data for {} to a mock output",
+        settings
+    );
 
-    segment_table
+    build_mock_sink(config)
 }
 
-fn main() {
-    let camera_index = prompt_camera_device();
-    let mut camera = prompt_camera(camera_index);
+#[cfg(not(feature = "rpi"))]
+fn build_i2c_sink(config: &Config) -> impl OutputSink + Send {
+    warn!(
+        "Built without the rpi feature, writing LED data for I2C bus {} to a mock output",
+        config.i2c_bus
+    );
 
-    let resolution = camera.resolution();
-    let width = resolution.width();
-    let height = resolution.height();
+    build_mock_sink(config)
+}
+
+#[cfg(feature = "rpi")]
+fn retry_policy(config: &Config) -> RetryPolicy {
+    RetryPolicy {
+        max_retries: config.spi_retries,
+        retry_delay: Duration::from_millis(config.spi_retry_delay_ms),
+        reopen_after: config.spi_reopen_after,
+    }
+}
+
+#[cfg(feature = "rpi")]
+fn build_spi_sink(settings: SpiSettings, config: &Config) -> impl OutputSink + Send {
+    info!("Writing LED data to {}", settings);
+
+    let label = settings.to_string();
+    let mut settings = settings;
+    let mut clock_speeds = ClockFallback::new(
+        settings.clock_speed,
+        config.spi_min_clock_speed.unwrap_or(settings.clock_speed),
+    );
+    RetryingSink::new(
+        &label,
+        move || {
+            if let Some(clock_speed) = clock_speeds.next() {
+                if clock_speed != settings.clock_speed {
+                    warn!("{}: falling back to {} Hz", settings, clock_speed);
+                }
+                settings.clock_speed = clock_speed;
+            }
+            SpiSink::open(settings)
+        },
+        retry_policy(config),
+    )
+    .expect("Unable to initialize SPI")
+}
+
+#[cfg(feature = "rpi")]
+fn build_i2c_sink(config: &Config) -> impl OutputSink + Send {
+    let label = format!("i2c{} at {:#04x}", config.i2c_bus, config.i2c_address);
+    info!("Writing LED data to {}", label);
+
+    let (bus, address, leds_per_device) = (
+        config.i2c_bus,
+        config.i2c_address,
+        config.i2c_leds_per_device as usize,
+    );
+    RetryingSink::new(
+        &label,
+        move || I2cOutputSink::open(bus, address, leds_per_device),
+        retry_policy(config),
+    )
+    .expect("Unable to initialize I2C")
+}
+
+fn build_split_spi_sink(config: &Config, num_leds: usize) -> SplitSink {
+    SplitSink::new(
+        config
+            .spi_strips
+            .iter()
+            .map(|strip| -> (_, Box<dyn OutputSink + Send>) {
+                assert!(
+                    strip.leds.end <= num_leds,
+                    "SPI strip LEDs {:?} exceed the {} LEDs being driven",
+                    strip.leds,
+                    num_leds
+                );
+                // A strip can chain whole ring zones or drive part of one, but
+                // can't start or end partway through a zone it leaves
+                for zone in zone_ranges(&config.ring_zones) {
+                    let overlaps = strip.leds.start < zone.end && zone.start < strip.leds.end;
+                    let contains_zone =
+                        strip.leds.start <= zone.start && zone.end <= strip.leds.end;
+                    let inside_zone = zone.start <= strip.leds.start && strip.leds.end <= zone.end;
+                    assert!(
+                        !overlaps || contains_zone || inside_zone,
+                        "SPI strip LEDs {:?} split ring zone LEDs {:?}",
+                        strip.leds,
+                        zone
+                    );
+                }
+                (
+                    strip.leds.clone(),
+                    Box::new(build_spi_sink(
+                        SpiSettings {
+                            bus: strip.bus,
+                            slave_select: strip.slave_select,
+                            clock_speed: strip.clock_speed,
+                            mode: config.spi_mode,
+                        },
+                        config,
+                    )),
+                )
+            })
+            .collect(),
+    )
+}
+
+fn build_adaptive_sink(
+    sink: impl OutputSink + Send + 'static,
+    config: &Config,
+) -> Box<dyn OutputSink + Send> {
+    if config.adaptive_spi {
+        Box::new(AdaptiveSink::new(
+            sink,
+            config.change_threshold,
+            config.force_write_interval,
+        ))
+    } else {
+        Box::new(sink)
+    }
+}
+
+fn build_output_sink(config: &Config, num_leds: usize) -> Box<dyn OutputSink> {
+    let mut sinks: Vec<Box<dyn OutputSink>> = config
+        .outputs
+        .iter()
+        .map(|output| -> Box<dyn OutputSink + Send> {
+            match output {
+                OutputKind::Spi if config.dry_run.is_some() => {
+                    Box::new(DryRunSink::stdout(config.dry_run_dump_every()))
+                }
+                OutputKind::Spi if config.spi_strips.is_empty() => {
+                    build_adaptive_sink(build_spi_sink(config.spi_settings(), config), config)
+                }
+                OutputKind::Spi => {
+                    build_adaptive_sink(build_split_spi_sink(config, num_leds), config)
+                }
+                OutputKind::I2c => Box::new(build_i2c_sink(config)),
+                OutputKind::Stdout => Box::new(StdoutSink::stdout()),
+                OutputKind::Terminal => Box::new(TerminalSink::stdout()),
+                OutputKind::Tcp => Box::new(TcpSink::new(config.tcp_target.as_deref().unwrap())),
+                OutputKind::Udp => Box::new(
+                    UdpBroadcastSink::new(config.udp_target.unwrap())
+                        .expect("Unable to open UDP socket"),
+                ),
+                OutputKind::Osc => Box::new(
+                    OscSink::new(config.osc_target.unwrap(), config.osc_per_led)
+                        .expect("Unable to open OSC socket"),
+                ),
+                OutputKind::Hyperion => Box::new(HyperionSink::new(
+                    config.hyperion_target.as_deref().unwrap(),
+                    &config.hyperion_origin,
+                    config.hyperion_priority,
+                )),
+                OutputKind::Dmx => Box::new(
+                    DmxUsbSink::open(&config.dmx_port, &config.dmx_channels)
+                        .expect("Unable to open DMX widget"),
+                ),
+                OutputKind::Mqtt => Box::new(
+                    MqttSink::new(&config.mqtt_settings().expect("Invalid MQTT configuration"))
+                        .expect("Unable to read the MQTT certificates"),
+                ),
+                OutputKind::Mock => Box::new(build_mock_sink(config)),
+            }
+        })
+        .map(|sink| -> Box<dyn OutputSink> {
+            match config.keep_alive {
+                Some(interval) => Box::new(KeepAliveSink::new(sink, interval)),
+                None => sink,
+            }
+        })
+        .collect();
+
+    if let Some(path) = &config.record_gif {
+        sinks.push(Box::new(
+            GifSink::create(path, config.record_pixel_size, config.record_duration)
+                .expect("Unable to create GIF recording"),
+        ));
+    }
+
+    if let Some(port) = config.websocket_port {
+        let server =
+            WsServer::bind(([0, 0, 0, 0], port).into()).expect("Unable to serve WebSockets");
+        info!("Streaming LED colors on ws://{}", server.local_addr());
+        sinks.push(Box::new(server));
+    }
+
+    if let Some(path) = &config.record_frames {
+        sinks.push(Box::new(
+            FrameLogSink::create(path).expect("Unable to create frame log"),
+        ));
+    }
+
+    if sinks.len() == 1 {
+        sinks.pop().unwrap()
+    } else {
+        Box::new(FanOutSink::new(sinks))
+    }
+}
+
+fn restore_led_strip<const N: usize>(path: &Path) -> LEDStrip<N> {
+    let state = match fs::read(path) {
+        Ok(state) => state,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return LEDStrip::new(),
+        Err(err) => {
+            warn!("Unable to read LED state from {}: {}", path.display(), err);
+            return LEDStrip::new();
+        }
+    };
+
+    LEDStrip::from_bytes(&state).unwrap_or_else(|err| {
+        warn!("Ignoring LED state in {}: {}", path.display(), err);
+        LEDStrip::new()
+    })
+}
+
+#[instrument(level = "trace", skip_all)]
+fn write_frame<const N: usize>(
+    led_strip: &LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    state: Option<&StatePersister>,
+) -> io::Result<()> {
+    if let Some(state) = state {
+        state.save(led_strip.to_bytes());
+    }
+    sink.write_strip(led_strip, led_strip.get_spi_data())
+}
+
+fn run_test_pattern<const N: usize>(
+    pattern: TestPattern,
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    state: Option<&StatePersister>,
+    max_milliamps: Option<u32>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let frame_delay = pattern.frame_delay();
+
+    let mut tick = 0;
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        for index in 0..N {
+            led_strip.set_led(index, pattern.color(index, N, tick));
+        }
+        if let Some(max_milliamps) = max_milliamps {
+            limit_power(led_strip, max_milliamps);
+        }
+
+        write_frame(led_strip, sink, state)?;
+        thread::sleep(frame_delay);
+        tick += 1;
+    }
+
+    Ok(())
+}
+
+fn run_effect<const N: usize>(
+    effect: EffectKind,
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    state: Option<&StatePersister>,
+    config: &Config,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let frame_delay = Duration::from_secs(1) / config.effect_fps;
+
+    let mut rainbow = RainbowEffect::new(config.effect_speed);
+    let mut breath = BreathEffect::new(
+        config.effect_color.unwrap_or(0xffffff),
+        config.effect_period,
+    );
+    let mut chase = ChaseEffect {
+        highlight_width: config.chase_width,
+        blend_width: config.chase_blend,
+        background_color: config.effect_background,
+        highlight_color: config.effect_color.unwrap_or(0xffffff),
+        step_ms: config.chase_step_ms,
+    };
+
+    let start = Instant::now();
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        match effect {
+            EffectKind::Rainbow => rainbow.tick(led_strip),
+            EffectKind::Breath => breath.tick(elapsed_ms, led_strip),
+            EffectKind::Chase => chase.tick(elapsed_ms, led_strip),
+        }
+        if let Some(max_milliamps) = config.max_milliamps {
+            limit_power(led_strip, max_milliamps);
+        }
+
+        write_frame(led_strip, sink, state)?;
+        thread::sleep(frame_delay);
+    }
+
+    Ok(())
+}
+
+fn run_tcp_receiver<const N: usize>(
+    addr: &str,
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    state: Option<&StatePersister>,
+    max_milliamps: Option<u32>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let mut source = TcpFrameSource::bind(addr).expect("Unable to listen for TCP frames");
+    info!(
+        "Receiving frames on {}",
+        source.local_addr().expect("Unable to get listen address")
+    );
+
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        let frame = match source.next_frame() {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!("Failed to receive TCP frame: {}", err);
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+        };
+
+        for (index, &(r, g, b)) in frame.colors.iter().take(N).enumerate() {
+            led_strip.set_led_rgb(index, r, g, b);
+        }
+        if let Some(max_milliamps) = max_milliamps {
+            limit_power(led_strip, max_milliamps);
+        }
+
+        write_frame(led_strip, sink, state)?;
+    }
 
-    let segment_map = build_segment_map(NUM_LEDS, width, height);
+    Ok(())
+}
+
+fn run_udp_receiver(port: u16, sink: &mut dyn OutputSink, shutdown: &AtomicBool) -> io::Result<()> {
+    let mut source = UdpFrameSource::bind(port).expect("Unable to listen for UDP frames");
+    info!(
+        "Receiving UDP frames on {}",
+        source.local_addr().expect("Unable to get listen address")
+    );
+
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        match source.next_frame() {
+            Ok(spi_data) => sink.write(&spi_data)?,
+            Err(err) => warn!("Dropping UDP frame: {}", err),
+        }
+    }
+
+    Ok(())
+}
+
+fn run_replay<const N: usize>(
+    path: &Path,
+    speed: f64,
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    state: Option<&StatePersister>,
+    max_milliamps: Option<u32>,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let mut log = FrameLogReader::open(path).expect("Unable to open frame log");
+    info!(
+        "Replaying {} LEDs from {} at {}x speed",
+        log.led_count(),
+        path.display(),
+        speed
+    );
+
+    let result = replay_frames(
+        &mut log,
+        speed,
+        |colors| {
+            if shutdown.load(atomic::Ordering::Relaxed) {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+
+            for (index, &(r, g, b)) in colors.iter().take(N).enumerate() {
+                led_strip.set_led_rgb(index, r, g, b);
+            }
+            if let Some(max_milliamps) = max_milliamps {
+                limit_power(led_strip, max_milliamps);
+            }
+
+            write_frame(led_strip, sink, state)
+        },
+        thread::sleep,
+    );
+    match result {
+        Ok(frames) => info!("Replayed {} frames", frames),
+        Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+        Err(err) => return Err(err),
+    }
 
-    camera.open_stream().expect("Unable to open stream");
+    Ok(())
+}
 
-    let mut spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 16_000_000, Mode::Mode0)
-        .expect("Unable to initialize SPI");
+fn run_camera<const N: usize>(
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    state: Option<&StatePersister>,
+    metrics: Option<&Metrics>,
+    api_state: Option<&ApiState>,
+    config: &Config,
+    shutdown: &AtomicBool,
+) -> io::Result<()> {
+    let (resolution, frame_rate, frames) = spawn_capture(config.strict_format)?;
+    let width = resolution.width();
+    let height = resolution.height();
 
-    const NUM_LEDS: usize = 36;
-    let mut led_strip: LEDStrip<NUM_LEDS> = LEDStrip::new();
+    let build = || config.segment_map(N, width, height);
+    let segment_map = match default_cache_dir().filter(|_| !config.no_segment_cache) {
+        Some(cache_dir) => load_or_build(
+            &cache_dir,
+            &config.segment_map_params(N, width, height),
+            build,
+        ),
+        None => build(),
+    }
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let segment_index = config.segment_index(&segment_map);
 
-    let frame_delay = Duration::from_millis((1000 / camera.frame_rate()).into());
+    let frame_delay = Duration::from_millis((1000 / frame_rate).into());
+    let idle_fade = Duration::from_millis(config.idle_fade_ms);
 
-    loop {
-        let frame = camera.frame().expect("Unable to get frame from camera");
-        let decoded_image = frame.decode_image::<RgbFormat>().unwrap();
+    let breath = (config.effect == Some(EffectKind::Breath))
+        .then(|| BreathEffect::new(0xffffff, config.effect_period));
+    let hue_enhancement = config.hue_enhancement();
+    let mut frame_history = config
+        .frame_average
+        .map(|frames| FrameHistory::new(frames.into()));
+    let mut scene_change = config.scene_change_threshold.map(SceneChangeDetector::new);
+    let mut flicker = config.flicker_suppression.then(FlickerDetector::new);
+    let mut hysteresis = config.hysteresis.map(HysteresisFilter::new);
+    let mut dead_band =
+        (config.dead_band_threshold > 0.0).then(|| DeadBandFilter::new(config.dead_band_threshold));
+    let mut power_limiter = config.power_limiter();
+    let start = Instant::now();
 
-        let mut led_values: [(u64, u64, u64); NUM_LEDS] = [(0, 0, 0); NUM_LEDS];
-        let mut counts: [u64; NUM_LEDS] = [0; NUM_LEDS];
-        for (index, pixel) in decoded_image.chunks_exact(3).enumerate() {
-            if let Some(segment) = segment_map[index] {
-                if counts[segment] == 0 {
-                    led_values[segment].0 = u64::from(pixel[0]).pow(2);
-                    led_values[segment].1 = u64::from(pixel[1]).pow(2);
-                    led_values[segment].2 = u64::from(pixel[2]).pow(2);
-                } else {
-                    led_values[segment].0 += u64::from(pixel[0]).pow(2);
-                    led_values[segment].1 += u64::from(pixel[1]).pow(2);
-                    led_values[segment].2 += u64::from(pixel[2]).pow(2);
+    let mut frame_buffer = ReusableFrameBuffer::new();
+    let mut last_frame: Option<Instant> = None;
+    let mut signal_lost: Option<Instant> = None;
+    let mut idle_faded = false;
+    while !shutdown.load(atomic::Ordering::Relaxed) {
+        let timeout = match signal_lost {
+            Some(lost) if lost.elapsed() < idle_fade => FADE_STEP,
+            _ => SIGNAL_LOSS_TIMEOUT,
+        };
+        let frame = match frames.recv_timeout(timeout) {
+            Ok(frame) => frame?,
+            // Fades the output only, so the persisted state keeps the last
+            // camera colors
+            Err(RecvTimeoutError::Timeout) if !idle_fade.is_zero() => {
+                let lost = *signal_lost.get_or_insert_with(Instant::now);
+                // Once the strip is dark there is nothing left to write
+                if !idle_faded {
+                    let factor = idle_fade_factor(lost.elapsed(), idle_fade);
+                    sink.write(&led_strip.get_spi_data_with_brightness(factor))?;
+                    idle_faded = factor == 0.0;
                 }
-                counts[segment] += 1;
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("Camera capture stopped"))
+            }
+        };
+        signal_lost = None;
+        idle_faded = false;
+
+        let _frame_span = debug_span!("frame").entered();
+        let now = Instant::now();
+        if let Some(metrics) = metrics {
+            metrics.frame_captured();
+            if let Some(last_frame) = last_frame {
+                metrics.set_fps(1.0 / now.duration_since(last_frame).as_secs_f64());
             }
         }
+        if let Some(api_state) = api_state {
+            api_state.frame_captured(now);
+        }
+        last_frame = Some(now);
 
-        for (index, led_value) in led_values.iter().enumerate() {
-            let (r, g, b) = led_value;
-            let count = counts[index];
-            let r = ((r / count) as f64).sqrt() as u32;
-            let g = ((g / count) as f64).sqrt() as u32;
-            let b = ((b / count) as f64).sqrt() as u32;
-            let color = r << 16 | g << 8 | b;
+        if let Err(err) = frame_buffer.decode_into(&frame) {
+            warn!("Dropping camera frame: {}", err);
+            if let Some(metrics) = metrics {
+                metrics.frame_dropped();
+            }
+            continue;
+        }
+
+        let segment_start = Instant::now();
+        let segment_colors =
+            match average_indexed_segment_colors(frame_buffer.data(), &segment_index) {
+                Ok(segment_colors) => segment_colors,
+                Err(err) => {
+                    warn!("Dropping camera frame: {}", err);
+                    if let Some(metrics) = metrics {
+                        metrics.frame_dropped();
+                    }
+                    continue;
+                }
+            };
+        let segment_colors: [u32; N] = segment_colors.try_into().unwrap();
+        if let Some(metrics) = metrics {
+            metrics.segment_computed(segment_start.elapsed());
+        }
+        let is_cut = scene_change
+            .as_mut()
+            .is_some_and(|scene_change| scene_change.is_cut(segment_colors));
+        let segment_colors = match &mut frame_history {
+            Some(frame_history) => {
+                if is_cut {
+                    frame_history.reset();
+                }
+                frame_history.update(segment_colors)
+            }
+            None => segment_colors,
+        };
+        let segment_colors = match &mut flicker {
+            Some(flicker) => flicker.apply(segment_colors),
+            None => segment_colors,
+        };
+        let segment_colors = blur_leds(segment_colors, config.blur);
+        for (index, color) in segment_colors.into_iter().enumerate() {
             led_strip.set_led(index, color);
         }
+        if let Some(hysteresis) = &mut hysteresis {
+            hysteresis.apply(led_strip);
+        }
+        if let Some(dead_band) = &mut dead_band {
+            dead_band.apply(led_strip);
+        }
+        if let Some(color_matrix) = &config.color_matrix {
+            led_strip.apply_color_matrix_all(color_matrix);
+        }
+        if config.hue_rotation_degrees != 0.0 {
+            led_strip.apply_hue_rotation_all(config.hue_rotation_degrees);
+        }
+        if let Some(hue_enhancement) = &hue_enhancement {
+            led_strip.enhance_hue_all(hue_enhancement);
+        }
+        if config.grayscale {
+            led_strip.grayscale_all();
+        } else if config.desaturate > 0.0 {
+            led_strip.desaturate_all(config.desaturate);
+        }
+        if config.invert {
+            led_strip.invert_all();
+        }
+        if let Some(breath) = &breath {
+            breath.modulate(start.elapsed().as_millis() as u64, led_strip);
+        }
+        if config.auto_brightness {
+            let luminance = mean_luminance(frame_buffer.data());
+            led_strip.scale_brightness(
+                config
+                    .auto_brightness_curve
+                    .brightness(luminance, config.auto_brightness_min),
+            );
+        }
+        power_limiter.apply(led_strip);
+        if let Some(metrics) = metrics {
+            metrics.set_milliamps(estimate_milliamps(led_strip));
+        }
 
-        spi.write(led_strip.get_spi_data())
-            .expect("Failed to write SPI data");
+        write_frame(led_strip, sink, state)?;
         thread::sleep(frame_delay);
     }
+
+    // Continue the shutdown fade from wherever the idle fade had reached
+    if let Some(lost) = signal_lost {
+        led_strip.scale_brightness(idle_fade_factor(lost.elapsed(), idle_fade));
+    }
+
+    Ok(())
+}
+
+fn led_strip_builder<const N: usize>(config: &Config) -> LEDStripBuilder<N> {
+    let [r, g, b] = config.white_balance;
+    LEDStripBuilder::new()
+        .offset(config.led_offset)
+        .reversed(config.reverse_leds)
+        .chip(config.chip)
+        .brightness(config.brightness)
+        .gamma(config.gamma)
+        .white_balance(r, g, b)
+        .dithering(config.dither)
+        .white_extraction(config.white_extraction)
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_logging(cli.config.log_level, cli.config.log_format);
+    if let Some(Command::ListDevices) = cli.command {
+        list_devices();
+        return;
+    }
+    let config = cli
+        .config
+        .with_config_file()
+        .expect("Unable to load the config file");
+    if let Some(Command::Map {
+        png,
+        width,
+        height,
+        leds,
+        labels,
+    }) = &cli.command
+    {
+        let segment_map = config
+            .segment_map(*leds, *width, *height)
+            .expect("Invalid segment map configuration");
+        segment_map
+            .render_png(png, *labels)
+            .expect("Unable to write segment map PNG");
+        info!("Wrote segment map to {}", png.display());
+        return;
+    }
+
+    let mut sink = build_output_sink(&config, NUM_LEDS);
+
+    let metrics = config.metrics_addr.as_deref().map(|addr| {
+        let metrics = Arc::new(Metrics::default());
+        let addr = serve_metrics(addr, Arc::clone(&metrics)).expect("Unable to serve metrics");
+        info!("Serving metrics on http://{}/metrics", addr);
+        metrics
+    });
+    if let Some(metrics) = &metrics {
+        sink = Box::new(MeteredSink::new(sink, Arc::clone(metrics)));
+    }
+
+    let api_state = config.http_api_port.map(|port| {
+        let api_state = Arc::new(ApiState::new(&config, NUM_LEDS));
+        let addr = serve_api(([0, 0, 0, 0], port).into(), Arc::clone(&api_state))
+            .expect("Unable to serve the HTTP API");
+        info!("Serving the HTTP API on http://{}", addr);
+        api_state
+    });
+    if let Some(api_state) = &api_state {
+        sink = Box::new(ApiSink::new(
+            sink,
+            Arc::clone(api_state),
+            led_strip_builder::<NUM_LEDS>(&config),
+            config.power_limiter(),
+        ));
+    }
+
+    let builder = led_strip_builder(&config);
+    let mut led_strip: LEDStrip<NUM_LEDS> = match &config.persist_state {
+        Some(path) => {
+            let mut led_strip = restore_led_strip(path);
+            builder
+                .configure(&mut led_strip)
+                .expect("Invalid LED strip configuration");
+            led_strip
+        }
+        None => builder.build().expect("Invalid LED strip configuration"),
+    };
+
+    if !config.no_selftest {
+        run_boot_sequence(
+            &mut led_strip,
+            sink.as_mut(),
+            config.max_milliamps,
+            thread::sleep,
+        )
+        .expect("Failed to write LED data");
+    }
+
+    let state = config.persist_state.clone().map(StatePersister::spawn);
+    if state.is_some() {
+        sink.write(led_strip.get_spi_data())
+            .expect("Failed to write LED data");
+    }
+    let state = state.as_ref();
+
+    if config.self_test {
+        run_led_walk(&mut led_strip, sink.as_mut(), Duration::from_millis(250))
+            .expect("Failed to write LED data");
+    }
+
+    let shutdown = install_signal_handlers().expect("Unable to install signal handlers");
+    let metrics = metrics.as_deref();
+    let api_state = api_state.as_deref();
+    // Breathing without a color modulates the camera colors instead
+    let standalone_effect = config
+        .effect
+        .filter(|&effect| effect != EffectKind::Breath || config.effect_color.is_some());
+
+    let result = if let Some(Command::Replay { path, speed }) = &cli.command {
+        assert!(*speed > 0.0, "Replay speed must be positive");
+        run_replay(
+            path,
+            *speed,
+            &mut led_strip,
+            sink.as_mut(),
+            state,
+            config.max_milliamps,
+            &shutdown,
+        )
+    } else if let Some(addr) = &config.receive {
+        run_tcp_receiver(
+            addr,
+            &mut led_strip,
+            sink.as_mut(),
+            state,
+            config.max_milliamps,
+            &shutdown,
+        )
+    } else if let Some(port) = config.receive_udp {
+        run_udp_receiver(port, sink.as_mut(), &shutdown)
+    } else if let Some(test_pattern) = config.test_pattern {
+        run_test_pattern(
+            test_pattern,
+            &mut led_strip,
+            sink.as_mut(),
+            state,
+            config.max_milliamps,
+            &shutdown,
+        )
+    } else if let Some(effect) = standalone_effect {
+        run_effect(
+            effect,
+            &mut led_strip,
+            sink.as_mut(),
+            state,
+            &config,
+            &shutdown,
+        )
+    } else {
+        run_camera(
+            &mut led_strip,
+            sink.as_mut(),
+            state,
+            metrics,
+            api_state,
+            &config,
+            &shutdown,
+        )
+    };
+
+    // Frames received over UDP bypass the strip, so there is nothing to fade
+    let fade_duration = if config.receive_udp.is_some() {
+        Duration::ZERO
+    } else {
+        Duration::from_millis(config.fade_out_ms)
+    };
+    if let Err(err) = fade_out(&mut led_strip, sink.as_mut(), fade_duration, thread::sleep) {
+        error!("Failed to turn off the LEDs: {}", err);
+    }
+
+    if let Err(err) = result {
+        error!("Stopped after an unrecoverable error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,533 @@
+use crate::http::{serve, Request};
+use crate::output::OutputSink;
+use afterglow::config::Config;
+use afterglow::led::{decode_spi_data, AnyLedStrip, LEDStrip, LEDStripBuilder};
+use afterglow::power::PowerLimiter;
+use afterglow::spi_settings::value_name;
+use std::{
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const STALE_FRAME_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Default)]
+struct FrameStats {
+    frame_count: u64,
+    fps: f64,
+    last_frame: Option<Instant>,
+    last_error: Option<String>,
+    colors: Vec<u32>,
+}
+
+pub struct ApiState {
+    started: Instant,
+    led_count: usize,
+    spi_device: String,
+    config: String,
+    frames: Mutex<FrameStats>,
+    overrides: Mutex<Option<Vec<u32>>>,
+}
+
+impl ApiState {
+    pub fn new(config: &Config, led_count: usize) -> Self {
+        let spi_device = config.spi_settings().device_path();
+
+        Self {
+            started: Instant::now(),
+            led_count,
+            config: render_config(config, led_count, &spi_device),
+            spi_device,
+            frames: Mutex::default(),
+            overrides: Mutex::default(),
+        }
+    }
+
+    // Health follows the camera rather than the sink, which keeps being
+    // written to while the output fades after a signal loss
+    pub fn frame_captured(&self, timestamp: Instant) {
+        let mut frames = self.frames.lock().unwrap();
+        frames.frame_count += 1;
+        if let Some(last_frame) = frames.last_frame {
+            frames.fps = 1.0 / timestamp.duration_since(last_frame).as_secs_f64();
+        }
+        frames.last_frame = Some(timestamp);
+    }
+
+    fn frame_written(&self, colors: Vec<u32>) {
+        self.frames.lock().unwrap().colors = colors;
+    }
+
+    fn write_failed(&self, err: &io::Error) {
+        self.frames.lock().unwrap().last_error = Some(err.to_string());
+    }
+
+    fn render_health(&self, now: Instant) -> String {
+        let frames = self.frames.lock().unwrap();
+        let capturing = frames
+            .last_frame
+            .is_some_and(|last_frame| now.duration_since(last_frame) < STALE_FRAME_TIMEOUT);
+
+        format!(
+            "{{\"capturing\":{},\"fps\":{},\"frame_count\":{},\"last_error\":{},\"leds\":{}}}",
+            capturing,
+            json_number(frames.fps),
+            frames.frame_count,
+            frames
+                .last_error
+                .as_deref()
+                .map_or("null".to_string(), json_string),
+            render_colors(&frames.colors)
+        )
+    }
+
+    fn render_status(&self) -> String {
+        let frames = self.frames.lock().unwrap();
+        format!(
+            "{{\"fps\":{},\"led_count\":{},\"spi_device\":{},\"uptime\":{},\"frame_count\":{}}}",
+            json_number(frames.fps),
+            self.led_count,
+            json_string(&self.spi_device),
+            self.started.elapsed().as_secs_f64(),
+            frames.frame_count
+        )
+    }
+
+    fn render_leds(&self) -> String {
+        let colors = match &*self.overrides.lock().unwrap() {
+            Some(overrides) => overrides.clone(),
+            None => self.frames.lock().unwrap().colors.clone(),
+        };
+        render_colors(&colors)
+    }
+
+    fn set_overrides(&self, body: &str) -> Result<(), String> {
+        let colors = parse_colors(body)?;
+        if colors.len() != self.led_count {
+            return Err(format!(
+                "expected {} LED colors, got {}",
+                self.led_count,
+                colors.len()
+            ));
+        }
+
+        *self.overrides.lock().unwrap() = Some(colors);
+        Ok(())
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+
+    out
+}
+
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn render_colors(colors: &[u32]) -> String {
+    let colors: Vec<String> = colors
+        .iter()
+        .map(|color| format!("\"#{:06x}\"", color))
+        .collect();
+    format!("[{}]", colors.join(","))
+}
+
+fn parse_colors(body: &str) -> Result<Vec<u32>, String> {
+    let items = body
+        .trim()
+        .strip_prefix('[')
+        .and_then(|body| body.strip_suffix(']'))
+        .ok_or_else(|| "expected a JSON array of colors".to_string())?
+        .trim();
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    items
+        .split(',')
+        .map(|item| {
+            let item = item.trim();
+            let color = item
+                .strip_prefix("\"#")
+                .and_then(|item| item.strip_suffix('"'))
+                .filter(|hex| hex.len() == 6)
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok());
+            match color {
+                Some(color) => Ok(color),
+                None => item
+                    .parse()
+                    .ok()
+                    .filter(|&color| color <= 0xffffff)
+                    .ok_or_else(|| format!("invalid color: {}", item)),
+            }
+        })
+        .collect()
+}
+
+fn render_config(config: &Config, led_count: usize, spi_device: &str) -> String {
+    let outputs: Vec<String> = config
+        .outputs
+        .iter()
+        .map(|&output| json_string(&value_name(output)))
+        .collect();
+
+    format!(
+        concat!(
+            "{{\"led_count\":{},\"outputs\":[{}],\"spi_device\":{},\"spi_clock_speed\":{},",
+            "\"chip\":{},\"brightness\":{},\"gamma\":{},\"led_offset\":{},\"reverse_leds\":{},",
+            "\"layout\":{},\"max_milliamps\":{}}}"
+        ),
+        led_count,
+        outputs.join(","),
+        json_string(spi_device),
+        config.spi_clock_speed,
+        json_string(&value_name(config.chip)),
+        json_number(config.brightness.into()),
+        json_number(config.gamma.into()),
+        config.led_offset,
+        config.reverse_leds,
+        json_string(&value_name(config.layout)),
+        config
+            .max_milliamps
+            .map_or("null".to_string(), |max_milliamps| max_milliamps
+                .to_string()),
+    )
+}
+
+fn handle(request: &Request, state: &ApiState) -> (&'static str, String) {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/health") => ("200 OK", state.render_health(Instant::now())),
+        ("GET", "/status") => ("200 OK", state.render_status()),
+        ("GET", "/leds") => ("200 OK", state.render_leds()),
+        ("POST", "/leds") => match state.set_overrides(&request.body) {
+            Ok(()) => ("200 OK", state.render_leds()),
+            Err(err) => (
+                "400 Bad Request",
+                format!("{{\"error\":{}}}", json_string(&err)),
+            ),
+        },
+        ("DELETE", "/leds") => {
+            *state.overrides.lock().unwrap() = None;
+            ("200 OK", state.render_leds())
+        }
+        ("GET", "/config") => ("200 OK", state.config.clone()),
+        (_, "/health" | "/status" | "/leds" | "/config") => {
+            ("405 Method Not Allowed", String::new())
+        }
+        _ => ("404 Not Found", String::new()),
+    }
+}
+
+pub fn serve_api(addr: SocketAddr, state: Arc<ApiState>) -> io::Result<SocketAddr> {
+    serve(addr, "API", "application/json", move |request| {
+        handle(request, &state)
+    })
+}
+
+pub struct ApiSink<S, const N: usize> {
+    sink: S,
+    state: Arc<ApiState>,
+    builder: LEDStripBuilder<N>,
+    power_limiter: PowerLimiter,
+}
+
+impl<S: OutputSink, const N: usize> ApiSink<S, N> {
+    // Overrides are encoded with a strip configured like the one being
+    // overridden, so chip, brightness, offset and power limits still apply
+    pub fn new(
+        sink: S,
+        state: Arc<ApiState>,
+        builder: LEDStripBuilder<N>,
+        power_limiter: PowerLimiter,
+    ) -> Self {
+        Self {
+            sink,
+            state,
+            builder,
+            power_limiter,
+        }
+    }
+
+    fn write_overrides(&mut self, overrides: Vec<u32>) -> io::Result<()> {
+        let mut led_strip = LEDStrip::try_from_slice(&overrides).map_err(io::Error::other)?;
+        self.builder
+            .configure(&mut led_strip)
+            .map_err(io::Error::other)?;
+        self.power_limiter.apply(&mut led_strip);
+
+        self.state.frame_written(overrides);
+        self.sink
+            .write_strip(&led_strip, led_strip.get_spi_data())
+            .inspect_err(|err| self.state.write_failed(err))
+    }
+}
+
+impl<S: OutputSink, const N: usize> OutputSink for ApiSink<S, N> {
+    // Frames that arrive already encoded only have their corrected colors
+    // to report
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let overrides = self.state.overrides.lock().unwrap().clone();
+        if let Some(overrides) = overrides {
+            return self.write_overrides(overrides);
+        }
+
+        let colors = decode_spi_data(spi_data)
+            .into_iter()
+            .map(|(r, g, b)| u32::from_be_bytes([0, r, g, b]))
+            .collect();
+        self.state.frame_written(colors);
+        self.sink
+            .write(spi_data)
+            .inspect_err(|err| self.state.write_failed(err))
+    }
+
+    fn write_strip(&mut self, led_strip: &dyn AnyLedStrip, spi_data: &[u8]) -> io::Result<()> {
+        let overrides = self.state.overrides.lock().unwrap().clone();
+        if let Some(overrides) = overrides {
+            return self.write_overrides(overrides);
+        }
+
+        let colors = (0..led_strip.num_leds())
+            .map(|index| {
+                let (r, g, b) = led_strip.get_led(index);
+                u32::from_be_bytes([0, r, g, b])
+            })
+            .collect();
+        self.state.frame_written(colors);
+        self.sink
+            .write_strip(led_strip, spi_data)
+            .inspect_err(|err| self.state.write_failed(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::{parse_colors, serve_api, ApiSink, ApiState};
+    use crate::output::{OutputSink, VecSink};
+    use afterglow::config::Config;
+    use afterglow::led::{decode_spi_data, LEDStrip, LEDStripBuilder};
+    use afterglow::power::PowerLimiter;
+    use clap::Parser;
+    use std::{
+        io::{self, Read, Write},
+        net::{SocketAddr, TcpStream},
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    struct FailingSink;
+
+    impl OutputSink for FailingSink {
+        fn write(&mut self, _spi_data: &[u8]) -> io::Result<()> {
+            Err(io::Error::other("SPI write failed"))
+        }
+    }
+
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn response_body(response: &str) -> &str {
+        response.split_once("\r\n\r\n").unwrap().1
+    }
+
+    #[test]
+    fn it_parses_led_colors() {
+        assert_eq!(
+            parse_colors(" [\"#ff0000\", 255, \"#00FF00\"] "),
+            Ok(vec![0xff0000, 0x0000ff, 0x00ff00])
+        );
+        assert_eq!(parse_colors("[]"), Ok(Vec::new()));
+        assert!(parse_colors("\"#ff0000\"").is_err());
+        assert!(parse_colors("[\"#ff00\"]").is_err());
+        assert!(parse_colors("[16777216]").is_err());
+    }
+
+    #[test]
+    fn it_serves_status_and_config() {
+        let config = Config::try_parse_from(["afterglow", "--spi-bus", "spi1"]).unwrap();
+        let state = Arc::new(ApiState::new(&config, 2));
+        let addr = serve_api("127.0.0.1:0".parse().unwrap(), Arc::clone(&state)).unwrap();
+        state.frame_captured(Instant::now());
+
+        let response = request(addr, "GET", "/status", "");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        let status = response_body(&response);
+        assert!(status.contains("\"led_count\":2"));
+        assert!(status.contains("\"spi_device\":\"/dev/spidev1.0\""));
+        assert!(status.contains("\"frame_count\":1"));
+
+        let response = request(addr, "GET", "/config", "");
+        assert!(response_body(&response).contains("\"chip\":\"apa102\""));
+
+        assert!(request(addr, "GET", "/", "").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(
+            request(addr, "PUT", "/leds", "").starts_with("HTTP/1.1 405 Method Not Allowed\r\n")
+        );
+    }
+
+    #[test]
+    fn it_reports_health() {
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        let state = Arc::new(ApiState::new(&config, 2));
+        assert_eq!(
+            state.render_health(Instant::now()),
+            "{\"capturing\":false,\"fps\":0,\"frame_count\":0,\"last_error\":null,\"leds\":[]}"
+        );
+
+        let mut sink = ApiSink::new(
+            FailingSink,
+            Arc::clone(&state),
+            LEDStripBuilder::<2>::new(),
+            PowerLimiter::new(None, None),
+        );
+        assert!(sink
+            .write(LEDStrip::new_with_data([0xff0000, 0x0000ff]).get_spi_data())
+            .is_err());
+        assert!(state
+            .render_health(Instant::now())
+            .starts_with("{\"capturing\":false,\"fps\":0,\"frame_count\":0,"));
+
+        let now = Instant::now();
+        state.frame_captured(now);
+        assert_eq!(
+            state.render_health(now),
+            concat!(
+                "{\"capturing\":true,\"fps\":0,\"frame_count\":1,",
+                "\"last_error\":\"SPI write failed\",\"leds\":[\"#ff0000\",\"#0000ff\"]}"
+            )
+        );
+        assert!(state
+            .render_health(now + Duration::from_secs(5))
+            .starts_with("{\"capturing\":false,"));
+
+        let addr = serve_api("127.0.0.1:0".parse().unwrap(), Arc::clone(&state)).unwrap();
+        let response = request(addr, "GET", "/health", "");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response_body(&response).contains("\"frame_count\":1"));
+    }
+
+    #[test]
+    fn it_overrides_led_colors() {
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        let state = Arc::new(ApiState::new(&config, 2));
+        let addr = serve_api("127.0.0.1:0".parse().unwrap(), Arc::clone(&state)).unwrap();
+        let mut sink = ApiSink::new(
+            VecSink::default(),
+            Arc::clone(&state),
+            LEDStripBuilder::<2>::new(),
+            PowerLimiter::new(None, None),
+        );
+        let camera_frame = LEDStrip::new_with_data([0x102030, 0x405060]);
+
+        sink.write(camera_frame.get_spi_data()).unwrap();
+        assert_eq!(
+            response_body(&request(addr, "GET", "/leds", "")),
+            "[\"#102030\",\"#405060\"]"
+        );
+
+        assert!(request(addr, "POST", "/leds", "[\"#ff0000\"]")
+            .starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        let response = request(addr, "POST", "/leds", "[\"#ff0000\", \"#00ff00\"]");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        sink.write(camera_frame.get_spi_data()).unwrap();
+        assert_eq!(
+            response_body(&request(addr, "GET", "/leds", "")),
+            "[\"#ff0000\",\"#00ff00\"]"
+        );
+
+        request(addr, "DELETE", "/leds", "");
+        sink.write(camera_frame.get_spi_data()).unwrap();
+
+        let frames: Vec<Vec<(u8, u8, u8)>> = sink
+            .sink
+            .frames
+            .iter()
+            .map(|spi_data| decode_spi_data(spi_data))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                vec![(0x10, 0x20, 0x30), (0x40, 0x50, 0x60)],
+                vec![(0xff, 0x00, 0x00), (0x00, 0xff, 0x00)],
+                vec![(0x10, 0x20, 0x30), (0x40, 0x50, 0x60)],
+            ]
+        );
+    }
+
+    #[test]
+    fn it_limits_the_power_of_overrides() {
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        let state = Arc::new(ApiState::new(&config, 2));
+        let addr = serve_api("127.0.0.1:0".parse().unwrap(), Arc::clone(&state)).unwrap();
+        let mut sink = ApiSink::new(
+            VecSink::default(),
+            Arc::clone(&state),
+            LEDStripBuilder::<2>::new(),
+            PowerLimiter::new(None, Some(20)),
+        );
+
+        request(addr, "POST", "/leds", "[\"#ffffff\", \"#ffffff\"]");
+        sink.write(LEDStrip::<2>::new().get_spi_data()).unwrap();
+
+        assert_eq!(decode_spi_data(&sink.sink.frames[0]), vec![(42, 42, 42); 2]);
+    }
+
+    #[test]
+    fn it_round_trips_uncorrected_colors() {
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        let state = Arc::new(ApiState::new(&config, 2));
+        let addr = serve_api("127.0.0.1:0".parse().unwrap(), Arc::clone(&state)).unwrap();
+        let builder = || LEDStripBuilder::<2>::new().gamma(2.2).brightness(15);
+        let mut sink = ApiSink::new(
+            VecSink::default(),
+            Arc::clone(&state),
+            builder(),
+            PowerLimiter::new(None, None),
+        );
+        let mut camera_frame = builder().build().unwrap();
+        camera_frame.set_led(0, 0x804020);
+
+        sink.write_strip(&camera_frame, camera_frame.get_spi_data())
+            .unwrap();
+        let leds = response_body(&request(addr, "GET", "/leds", "")).to_string();
+        assert_eq!(leds, "[\"#804020\",\"#000000\"]");
+
+        request(addr, "POST", "/leds", &leds);
+        sink.write_strip(&camera_frame, camera_frame.get_spi_data())
+            .unwrap();
+        assert_eq!(sink.sink.frames[1], sink.sink.frames[0]);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::color::luminance;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BrightnessCurve {
+    #[default]
+    Linear,
+    Sqrt,
+    Square,
+}
+
+impl BrightnessCurve {
+    pub fn brightness(&self, luminance: f32, min_brightness: f32) -> f32 {
+        let luminance = luminance.clamp(0.0, 1.0);
+        let level = match self {
+            BrightnessCurve::Linear => luminance,
+            BrightnessCurve::Sqrt => luminance.sqrt(),
+            BrightnessCurve::Square => luminance.powi(2),
+        };
+
+        let min_brightness = min_brightness.clamp(0.0, 1.0);
+        min_brightness + (1.0 - min_brightness) * level
+    }
+}
+
+pub fn mean_luminance(rgb: &[u8]) -> f32 {
+    let pixels = rgb.chunks_exact(3);
+    let num_pixels = pixels.len();
+    if num_pixels == 0 {
+        return 0.0;
+    }
+
+    let total: f64 = pixels
+        .map(|pixel| f64::from(luminance(pixel[0], pixel[1], pixel[2])))
+        .sum();
+    (total / num_pixels as f64 / 255.0) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::brightness::{mean_luminance, BrightnessCurve};
+
+    #[test]
+    fn it_computes_the_mean_luminance_of_a_solid_gray_frame() {
+        let frame = [0x80; 4 * 3 * 3];
+        assert!((mean_luminance(&frame) - 128.0 / 255.0).abs() < 1e-4);
+
+        assert_eq!(mean_luminance(&[0x00; 12]), 0.0);
+        assert!((mean_luminance(&[0xff; 12]) - 1.0).abs() < 1e-4);
+        assert_eq!(mean_luminance(&[]), 0.0);
+    }
+
+    #[test]
+    fn it_averages_luminance_across_pixels() {
+        let frame = [0xff, 0xff, 0xff, 0x00, 0x00, 0x00];
+        assert!((mean_luminance(&frame) - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn it_maps_luminance_through_the_curve() {
+        assert_eq!(BrightnessCurve::Linear.brightness(0.25, 0.0), 0.25);
+        assert_eq!(BrightnessCurve::Sqrt.brightness(0.25, 0.0), 0.5);
+        assert_eq!(BrightnessCurve::Square.brightness(0.5, 0.0), 0.25);
+        assert_eq!(BrightnessCurve::Linear.brightness(0.0, 0.2), 0.2);
+        assert_eq!(BrightnessCurve::Linear.brightness(1.0, 0.2), 1.0);
+        assert_eq!(BrightnessCurve::Linear.brightness(2.0, 0.0), 1.0);
+    }
+}
@@ -0,0 +1,219 @@
+use nokhwa::utils::{CameraFormat, CameraInfo, FrameFormat, Resolution};
+use nokhwa::Camera;
+use std::{collections::HashMap, fmt::Write};
+
+const FRAME_RATE_TOLERANCE: f64 = 0.1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatOption {
+    pub format: FrameFormat,
+    pub resolution: Resolution,
+    pub frame_rates: Vec<u32>,
+}
+
+impl FormatOption {
+    pub fn label(&self) -> String {
+        format!(
+            "{} {}\t(fps options: {:?})",
+            self.resolution, self.format, self.frame_rates
+        )
+    }
+}
+
+pub fn merge_format_options(
+    formats: Vec<(FrameFormat, HashMap<Resolution, Vec<u32>>)>,
+) -> Vec<FormatOption> {
+    let mut options: Vec<FormatOption> = formats
+        .into_iter()
+        .flat_map(|(format, resolutions)| {
+            resolutions
+                .into_iter()
+                .map(move |(resolution, frame_rates)| FormatOption {
+                    format,
+                    resolution,
+                    frame_rates,
+                })
+        })
+        .collect();
+    options.sort_by_key(|option| {
+        (
+            option.resolution.width(),
+            option.resolution.height(),
+            option.format != FrameFormat::YUYV,
+        )
+    });
+
+    options
+}
+
+pub fn supported_formats(camera: &mut Camera) -> Vec<FormatOption> {
+    merge_format_options(
+        [FrameFormat::YUYV, FrameFormat::MJPEG]
+            .into_iter()
+            .filter_map(|format| {
+                camera
+                    .compatible_list_by_resolution(format)
+                    .ok()
+                    .map(|resolutions| (format, resolutions))
+            })
+            .collect(),
+    )
+}
+
+pub fn describe_device(device: &CameraInfo, formats: &[FormatOption]) -> String {
+    let mut description = format!(
+        "{}: {} ({})\n",
+        device.index(),
+        device.human_name(),
+        device.description()
+    );
+    for option in formats {
+        let frame_rates: Vec<String> = option
+            .frame_rates
+            .iter()
+            .map(|frame_rate| frame_rate.to_string())
+            .collect();
+        let _ = writeln!(
+            description,
+            "  {} {} @ {} fps",
+            option.resolution,
+            option.format,
+            frame_rates.join(",")
+        );
+    }
+
+    description
+}
+
+pub fn format_mismatch(
+    requested: &CameraFormat,
+    resolution: Resolution,
+    frame_rate: u32,
+) -> Option<String> {
+    let mut mismatches = Vec::new();
+
+    if resolution != requested.resolution() {
+        mismatches.push(format!(
+            "{} instead of {}",
+            resolution,
+            requested.resolution()
+        ));
+    }
+
+    let frame_rate_delta = frame_rate.abs_diff(requested.frame_rate());
+    if f64::from(frame_rate_delta) > f64::from(requested.frame_rate()) * FRAME_RATE_TOLERANCE {
+        mismatches.push(format!(
+            "{} fps instead of {} fps",
+            frame_rate,
+            requested.frame_rate()
+        ));
+    }
+
+    (!mismatches.is_empty()).then(|| format!("Camera is capturing at {}", mismatches.join(" and ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::camera_format::{
+        describe_device, format_mismatch, merge_format_options, FormatOption,
+    };
+    use nokhwa::utils::{CameraFormat, CameraIndex, CameraInfo, FrameFormat, Resolution};
+    use std::collections::HashMap;
+
+    #[test]
+    fn it_merges_and_labels_formats_by_resolution() {
+        let yuyv = HashMap::from([
+            (Resolution::new(1280, 720), vec![10]),
+            (Resolution::new(640, 480), vec![30, 15]),
+        ]);
+        let mjpeg = HashMap::from([
+            (Resolution::new(1920, 1080), vec![30]),
+            (Resolution::new(1280, 720), vec![60, 30]),
+        ]);
+
+        let labels: Vec<String> =
+            merge_format_options(vec![(FrameFormat::YUYV, yuyv), (FrameFormat::MJPEG, mjpeg)])
+                .iter()
+                .map(|option| option.label())
+                .collect();
+        assert_eq!(
+            labels,
+            vec![
+                "640x480 YUYV\t(fps options: [30, 15])",
+                "1280x720 YUYV\t(fps options: [10])",
+                "1280x720 MJPEG\t(fps options: [60, 30])",
+                "1920x1080 MJPEG\t(fps options: [30])",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_describes_a_device_and_its_formats() {
+        let device = CameraInfo::new(
+            "USB Camera",
+            "Video4Linux Device",
+            "",
+            CameraIndex::Index(2),
+        );
+        let formats = [
+            FormatOption {
+                format: FrameFormat::YUYV,
+                resolution: Resolution::new(640, 480),
+                frame_rates: vec![30, 15],
+            },
+            FormatOption {
+                format: FrameFormat::MJPEG,
+                resolution: Resolution::new(1920, 1080),
+                frame_rates: vec![30],
+            },
+        ];
+
+        assert_eq!(
+            describe_device(&device, &formats),
+            concat!(
+                "2: USB Camera (Video4Linux Device)\n",
+                "  640x480 YUYV @ 30,15 fps\n",
+                "  1920x1080 MJPEG @ 30 fps\n",
+            )
+        );
+        assert_eq!(
+            describe_device(&device, &[]),
+            "2: USB Camera (Video4Linux Device)\n"
+        );
+    }
+
+    #[test]
+    fn it_accepts_formats_close_to_the_request() {
+        let requested = CameraFormat::new(Resolution::new(640, 480), FrameFormat::YUYV, 30);
+
+        assert_eq!(
+            format_mismatch(&requested, Resolution::new(640, 480), 30),
+            None
+        );
+        assert_eq!(
+            format_mismatch(&requested, Resolution::new(640, 480), 27),
+            None
+        );
+    }
+
+    #[test]
+    fn it_describes_resolution_and_frame_rate_mismatches() {
+        let requested = CameraFormat::new(Resolution::new(640, 480), FrameFormat::YUYV, 30);
+
+        assert_eq!(
+            format_mismatch(&requested, Resolution::new(1280, 720), 30),
+            Some("Camera is capturing at 1280x720 instead of 640x480".to_string())
+        );
+        assert_eq!(
+            format_mismatch(&requested, Resolution::new(640, 480), 15),
+            Some("Camera is capturing at 15 fps instead of 30 fps".to_string())
+        );
+        assert_eq!(
+            format_mismatch(&requested, Resolution::new(320, 240), 60),
+            Some(
+                "Camera is capturing at 320x240 instead of 640x480 and 60 fps instead of 30 fps"
+                    .to_string()
+            )
+        );
+    }
+}
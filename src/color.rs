@@ -0,0 +1,489 @@
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::str::FromStr;
+
+pub fn clamp_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+pub fn rgb_to_hsv(color: u32) -> (f32, f32, f32) {
+    let [_, r, g, b] = color.to_be_bytes();
+    let (r, g, b) = (
+        f32::from(r) / 255.0,
+        f32::from(g) / 255.0,
+        f32::from(b) / 255.0,
+    );
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+
+    (hue, saturation, max)
+}
+
+pub fn parse_hex_color(hex: &str) -> Result<u32, String> {
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("expected a RRGGBB color, got: {}", hex));
+    }
+    Ok(u32::from_str_radix(hex, 16).unwrap())
+}
+
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> u32 {
+    let hue = hue.rem_euclid(360.0);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let m = value - chroma;
+
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    let to_channel = |c: f32| u32::from(clamp_u8((c + m) * 255.0));
+    (to_channel(r) << 16) | (to_channel(g) << 8) | to_channel(b)
+}
+
+pub fn apply_hue_rotation(color: u32, degrees: f32) -> u32 {
+    let (hue, saturation, value) = rgb_to_hsv(color);
+    hsv_to_rgb(hue + degrees, saturation, value)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HueEnhancement {
+    pub saturation_boost: f32,
+    pub protected_hue_start: f32,
+    pub protected_hue_end: f32,
+    pub protection: f32,
+}
+
+impl HueEnhancement {
+    fn is_protected(&self, hue: f32) -> bool {
+        let start = self.protected_hue_start.rem_euclid(360.0);
+        let end = self.protected_hue_end.rem_euclid(360.0);
+        if start <= end {
+            (start..=end).contains(&hue)
+        } else {
+            hue >= start || hue <= end
+        }
+    }
+}
+
+pub fn enhance_hue(color: u32, params: &HueEnhancement) -> u32 {
+    let (hue, saturation, value) = rgb_to_hsv(color);
+    let boost = if params.is_protected(hue) {
+        params.saturation_boost * (1.0 - params.protection.clamp(0.0, 1.0))
+    } else {
+        params.saturation_boost
+    };
+
+    hsv_to_rgb(hue, (saturation * (1.0 + boost)).clamp(0.0, 1.0), value)
+}
+
+pub fn apply_inversion(color: u32) -> u32 {
+    0xffffff ^ (color & 0xffffff)
+}
+
+pub fn luminance(r: u8, g: u8, b: u8) -> f32 {
+    0.2126 * f32::from(r) + 0.7152 * f32::from(g) + 0.0722 * f32::from(b)
+}
+
+pub fn apply_brightness(color: u32, factor: f32) -> u32 {
+    let factor = factor.clamp(0.0, 1.0);
+    let [_, r, g, b] = color.to_be_bytes();
+    let scale = |channel: u8| (f32::from(channel) * factor).round() as u32;
+    (scale(r) << 16) | (scale(g) << 8) | scale(b)
+}
+
+pub fn mix(from: u32, to: u32, amount: f32) -> u32 {
+    let amount = amount.clamp(0.0, 1.0);
+    let [_, r1, g1, b1] = from.to_be_bytes();
+    let [_, r2, g2, b2] = to.to_be_bytes();
+
+    let lerp = |a: u8, b: u8| {
+        let a = f32::from(a);
+        (a + (f32::from(b) - a) * amount).round() as u32
+    };
+    (lerp(r1, r2) << 16) | (lerp(g1, g2) << 8) | lerp(b1, b2)
+}
+
+pub fn apply_grayscale(color: u32) -> u32 {
+    let [_, r, g, b] = color.to_be_bytes();
+    let y = u32::from(clamp_u8(luminance(r, g, b)));
+    (y << 16) | (y << 8) | y
+}
+
+pub fn apply_desaturate(color: u32, amount: f32) -> u32 {
+    let amount = amount.clamp(0.0, 1.0);
+    let [_, r, g, b] = color.to_be_bytes();
+    let [_, y, _, _] = apply_grayscale(color).to_be_bytes();
+
+    let lerp = |channel: u8| {
+        let channel = f32::from(channel);
+        (channel + (f32::from(y) - channel) * amount).round() as u32
+    };
+    (lerp(r) << 16) | (lerp(g) << 8) | lerp(b)
+}
+
+// Gamma corrects each channel, then scales it by that channel's white
+// balance gain
+pub fn apply_gamma(color: u32, gamma: f32, white_balance: [f32; 3]) -> u32 {
+    let [_, r, g, b] = color.to_be_bytes();
+    let [r_gain, g_gain, b_gain] = white_balance;
+    let correct = |channel: u8, gain: f32| {
+        u32::from(clamp_u8(
+            (f32::from(channel) / 255.0).powf(gamma) * gain * 255.0,
+        ))
+    };
+    (correct(r, r_gain) << 16) | (correct(g, g_gain) << 8) | correct(b, b_gain)
+}
+
+// Moves the gray shared by all three channels onto a dedicated white LED,
+// extraction 1.0 moving all of it and 0.0 leaving the white LED off
+pub fn rgb_to_rgbw((r, g, b): (u8, u8, u8), extraction: f32) -> (u8, u8, u8, u8) {
+    let w = clamp_u8(f32::from(r.min(g).min(b)) * extraction.clamp(0.0, 1.0));
+    (r - w, g - w, b - w, w)
+}
+
+pub fn srgb_to_linear(channel: u8) -> f32 {
+    let value = f32::from(channel) / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    clamp_u8(encoded * 255.0)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct ColorMatrix {
+    pub rr: f32,
+    pub rg: f32,
+    pub rb: f32,
+    pub gr: f32,
+    pub gg: f32,
+    pub gb: f32,
+    pub br: f32,
+    pub bg: f32,
+    pub bb: f32,
+}
+
+impl Default for ColorMatrix {
+    fn default() -> Self {
+        Self {
+            rr: 1.0,
+            rg: 0.0,
+            rb: 0.0,
+            gr: 0.0,
+            gg: 1.0,
+            gb: 0.0,
+            br: 0.0,
+            bg: 0.0,
+            bb: 1.0,
+        }
+    }
+}
+
+impl FromStr for ColorMatrix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected 9 comma separated matrix entries, got: {}", s);
+
+        let entries: Vec<f32> = s
+            .split(',')
+            .map(|entry| entry.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let [rr, rg, rb, gr, gg, gb, br, bg, bb] = entries[..] else {
+            return Err(invalid());
+        };
+
+        Ok(ColorMatrix {
+            rr,
+            rg,
+            rb,
+            gr,
+            gg,
+            gb,
+            br,
+            bg,
+            bb,
+        })
+    }
+}
+
+pub fn apply_color_matrix(color: u32, m: &ColorMatrix) -> u32 {
+    let [_, r, g, b] = color.to_be_bytes();
+    let (r, g, b) = (f32::from(r), f32::from(g), f32::from(b));
+
+    let r_out = clamp_u8(m.rr * r + m.rg * g + m.rb * b);
+    let g_out = clamp_u8(m.gr * r + m.gg * g + m.gb * b);
+    let b_out = clamp_u8(m.br * r + m.bg * g + m.bb * b);
+    (u32::from(r_out) << 16) | (u32::from(g_out) << 8) | u32::from(b_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::{
+        apply_brightness, apply_color_matrix, apply_desaturate, apply_gamma, apply_grayscale,
+        apply_hue_rotation, apply_inversion, clamp_u8, enhance_hue, hsv_to_rgb, linear_to_srgb,
+        luminance, mix, parse_hex_color, rgb_to_hsv, rgb_to_rgbw, srgb_to_linear, ColorMatrix,
+        HueEnhancement,
+    };
+
+    #[test]
+    fn it_parses_hex_colors() {
+        assert_eq!(parse_hex_color("4b8040"), Ok(0x4b8040));
+        assert_eq!(parse_hex_color("FFFFFF"), Ok(0xffffff));
+        assert!(parse_hex_color("4b80").is_err());
+        assert!(parse_hex_color("zzzzzz").is_err());
+        assert!(parse_hex_color("+4b804").is_err());
+    }
+
+    #[test]
+    fn it_clamps_and_rounds_channel_values() {
+        assert_eq!(clamp_u8(127.4), 127);
+        assert_eq!(clamp_u8(127.5), 128);
+        assert_eq!(clamp_u8(-3.0), 0);
+        assert_eq!(clamp_u8(300.0), 255);
+        assert_eq!(clamp_u8(f32::NAN), 0);
+    }
+
+    #[test]
+    fn it_converts_known_colors_to_hsv() {
+        assert_eq!(rgb_to_hsv(0xff0000), (0.0, 1.0, 1.0));
+        assert_eq!(rgb_to_hsv(0x00ff00), (120.0, 1.0, 1.0));
+        assert_eq!(rgb_to_hsv(0x0000ff), (240.0, 1.0, 1.0));
+        assert_eq!(rgb_to_hsv(0x000000), (0.0, 0.0, 0.0));
+        assert_eq!(rgb_to_hsv(0xffffff), (0.0, 0.0, 1.0));
+
+        assert_eq!(hsv_to_rgb(60.0, 1.0, 1.0), 0xffff00);
+        assert_eq!(hsv_to_rgb(180.0, 0.5, 1.0), 0x80ffff);
+        assert_eq!(hsv_to_rgb(300.0, 1.0, 0.5), 0x800080);
+    }
+
+    #[test]
+    fn it_round_trips_colors_through_hsv() {
+        for color in [
+            0x4b8040, 0xc08060, 0x6080c0, 0x123456, 0xfedcba, 0x808080, 0x010203,
+        ] {
+            let (hue, saturation, value) = rgb_to_hsv(color);
+            assert_eq!(hsv_to_rgb(hue, saturation, value), color);
+        }
+    }
+
+    #[test]
+    fn it_weights_luminance_by_channel() {
+        assert_eq!(luminance(0, 0, 0), 0.0);
+        assert!((luminance(255, 255, 255) - 255.0).abs() < 1e-3);
+        assert!(luminance(0, 255, 0) > luminance(255, 0, 0));
+        assert!(luminance(255, 0, 0) > luminance(0, 0, 255));
+    }
+
+    #[test]
+    fn it_rotates_red_to_green_and_blue() {
+        assert_eq!(apply_hue_rotation(0xff0000, 120.0), 0x00ff00);
+        assert_eq!(apply_hue_rotation(0xff0000, 240.0), 0x0000ff);
+    }
+
+    #[test]
+    fn it_wraps_hue_rotations() {
+        assert_eq!(apply_hue_rotation(0xff0000, 360.0), 0xff0000);
+        assert_eq!(apply_hue_rotation(0xff0000, -120.0), 0x0000ff);
+        assert_eq!(apply_hue_rotation(0x4b8040, 0.0), 0x4b8040);
+    }
+
+    #[test]
+    fn it_leaves_grayscale_colors_unchanged() {
+        assert_eq!(apply_hue_rotation(0x000000, 90.0), 0x000000);
+        assert_eq!(apply_hue_rotation(0x808080, 90.0), 0x808080);
+        assert_eq!(apply_hue_rotation(0xffffff, 90.0), 0xffffff);
+    }
+
+    const SKIN_TONES: HueEnhancement = HueEnhancement {
+        saturation_boost: 0.5,
+        protected_hue_start: 15.0,
+        protected_hue_end: 50.0,
+        protection: 1.0,
+    };
+
+    #[test]
+    fn it_boosts_orange_less_than_blue() {
+        let orange = 0xc08060;
+        let blue = 0x6080c0;
+        let (_, orange_saturation, _) = rgb_to_hsv(orange);
+        let (_, blue_saturation, _) = rgb_to_hsv(blue);
+
+        let params = HueEnhancement {
+            protection: 0.75,
+            ..SKIN_TONES
+        };
+        let (_, boosted_orange, _) = rgb_to_hsv(enhance_hue(orange, &params));
+        let (_, boosted_blue, _) = rgb_to_hsv(enhance_hue(blue, &params));
+
+        assert!(boosted_orange - orange_saturation < boosted_blue - blue_saturation);
+        assert!(boosted_orange > orange_saturation);
+        assert!((boosted_blue - blue_saturation * 1.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn it_leaves_fully_protected_hues_unchanged() {
+        assert_eq!(enhance_hue(0xc08060, &SKIN_TONES), 0xc08060);
+        assert_ne!(enhance_hue(0x6080c0, &SKIN_TONES), 0x6080c0);
+        assert_eq!(enhance_hue(0x808080, &SKIN_TONES), 0x808080);
+    }
+
+    #[test]
+    fn it_protects_hue_ranges_that_wrap_around_red() {
+        let params = HueEnhancement {
+            protected_hue_start: 340.0,
+            protected_hue_end: 20.0,
+            ..SKIN_TONES
+        };
+        assert_eq!(enhance_hue(0xc06060, &params), 0xc06060);
+        assert_ne!(enhance_hue(0x60c060, &params), 0x60c060);
+    }
+
+    #[test]
+    fn it_inverts_colors() {
+        assert_eq!(apply_inversion(0xff0000), 0x00ffff);
+        assert_eq!(apply_inversion(0x000000), 0xffffff);
+        assert_eq!(apply_inversion(0x4b8040), 0xb47fbf);
+        assert_eq!(apply_inversion(0xff4b8040), 0xb47fbf);
+    }
+
+    #[test]
+    fn it_mixes_two_colors() {
+        assert_eq!(mix(0xff0000, 0x0000ff, 0.0), 0xff0000);
+        assert_eq!(mix(0xff0000, 0x0000ff, 1.0), 0x0000ff);
+        assert_eq!(mix(0xff0000, 0x0000ff, 0.5), 0x800080);
+        assert_eq!(mix(0x000000, 0x4b8040, 2.0), 0x4b8040);
+    }
+
+    #[test]
+    fn it_converts_colors_to_luminance_weighted_gray() {
+        assert_eq!(apply_grayscale(0xff0000), 0x363636);
+        assert_eq!(apply_grayscale(0x00ff00), 0xb6b6b6);
+        assert_eq!(apply_grayscale(0x0000ff), 0x121212);
+        assert_eq!(apply_grayscale(0xffffff), 0xffffff);
+        assert_eq!(apply_grayscale(0x000000), 0x000000);
+    }
+
+    #[test]
+    fn it_desaturates_colors_partially() {
+        assert_eq!(apply_desaturate(0xff0000, 0.0), 0xff0000);
+        assert_eq!(apply_desaturate(0xff0000, 1.0), 0x363636);
+        assert_eq!(apply_desaturate(0xff0000, 0.5), 0x9b1b1b);
+        assert_eq!(apply_desaturate(0xff0000, 2.0), 0x363636);
+    }
+
+    #[test]
+    fn it_scales_color_brightness() {
+        assert_eq!(apply_brightness(0x4b8040, 1.0), 0x4b8040);
+        assert_eq!(apply_brightness(0x4b8040, 0.5), 0x264020);
+        assert_eq!(apply_brightness(0x4b8040, 0.0), 0x000000);
+        assert_eq!(apply_brightness(0x4b8040, 1.5), 0x4b8040);
+    }
+
+    #[test]
+    fn it_applies_gamma_correction() {
+        assert_eq!(apply_gamma(0xff8000, 1.0, [1.0; 3]), 0xff8000);
+        assert_eq!(apply_gamma(0xff8000, 2.2, [1.0; 3]), 0xff3800);
+        assert_eq!(apply_gamma(0x4b8040, 0.5, [1.0; 3]), 0x8ab580);
+        assert_eq!(apply_gamma(0xffffff, 2.0, [1.0, 0.5, 0.0]), 0xff8000);
+        assert_eq!(apply_gamma(0x808080, 1.0, [2.0, 1.0, 1.0]), 0xff8080);
+    }
+
+    #[test]
+    fn it_extracts_the_white_channel() {
+        assert_eq!(rgb_to_rgbw((128, 128, 128), 1.0), (0, 0, 0, 128));
+        assert_eq!(rgb_to_rgbw((255, 255, 255), 1.0), (0, 0, 0, 255));
+        assert_eq!(rgb_to_rgbw((255, 0, 0), 1.0), (255, 0, 0, 0));
+        assert_eq!(rgb_to_rgbw((0, 200, 255), 1.0), (0, 200, 255, 0));
+        assert_eq!(rgb_to_rgbw((200, 150, 100), 1.0), (100, 50, 0, 100));
+        assert_eq!(rgb_to_rgbw((200, 150, 100), 0.5), (150, 100, 50, 50));
+        assert_eq!(rgb_to_rgbw((200, 150, 100), 0.0), (200, 150, 100, 0));
+    }
+
+    #[test]
+    fn it_converts_between_srgb_and_linear_light() {
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+        assert!((srgb_to_linear(10) - 0.003035).abs() < 1e-6);
+        assert!((srgb_to_linear(128) - 0.215861).abs() < 1e-6);
+
+        assert_eq!(linear_to_srgb(0.5), 188);
+        assert_eq!(linear_to_srgb(-1.0), 0);
+        assert_eq!(linear_to_srgb(2.0), 255);
+        for channel in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(channel)), channel);
+        }
+    }
+
+    const SWAP_RED_GREEN: ColorMatrix = ColorMatrix {
+        rr: 0.0,
+        rg: 1.0,
+        rb: 0.0,
+        gr: 1.0,
+        gg: 0.0,
+        gb: 0.0,
+        br: 0.0,
+        bg: 0.0,
+        bb: 1.0,
+    };
+
+    #[test]
+    fn it_multiplies_colors_by_a_color_matrix() {
+        assert_eq!(apply_color_matrix(0xff0000, &SWAP_RED_GREEN), 0x00ff00);
+        assert_eq!(apply_color_matrix(0x4b8040, &SWAP_RED_GREEN), 0x804b40);
+        assert_eq!(
+            apply_color_matrix(0x4b8040, &ColorMatrix::default()),
+            0x4b8040
+        );
+
+        let warm = ColorMatrix {
+            rr: 1.2,
+            bb: 0.5,
+            br: -0.1,
+            ..ColorMatrix::default()
+        };
+        assert_eq!(apply_color_matrix(0xff8040, &warm), 0xff8007);
+    }
+
+    #[test]
+    fn it_parses_color_matrices() {
+        assert_eq!("0,1,0, 1,0,0, 0,0,1".parse(), Ok(SWAP_RED_GREEN));
+        assert_eq!("1,0,0,0,1,0,0,0,1".parse(), Ok(ColorMatrix::default()));
+        assert!("1,0,0,0,1,0,0,0".parse::<ColorMatrix>().is_err());
+        assert!("1,0,0,0,1,0,0,0,x".parse::<ColorMatrix>().is_err());
+    }
+}
@@ -0,0 +1,1283 @@
+use crate::brightness::BrightnessCurve;
+use crate::color::{parse_hex_color, ColorMatrix, HueEnhancement};
+use crate::effects::EffectKind;
+use crate::led::ChipProfile;
+use crate::logging::LogFormat;
+use crate::power::PowerLimiter;
+use crate::segment_map::{
+    build_border_segment_map, build_bottom_segment_map, build_matrix_segment_map,
+    build_segment_map_from_layout, mirror_segment_map, Corner, Crop, CropRect, EdgeCounts,
+    LedLayout, MatrixSize, Orientation, RingZone, Rotation, SegmentIndex, SegmentLayout,
+    SegmentMap, SegmentMapBuilder, SegmentMapError, DEFAULT_BAND_FRACTION,
+    DEFAULT_BORDER_THICKNESS, DEFAULT_EDGE_FRACTION,
+};
+use crate::spi_settings::{
+    parse_bus, parse_clock_speed, parse_slave_select, SpiBus, SpiMode, SpiSettings, SpiSlaveSelect,
+};
+use crate::test_pattern::TestPattern;
+use clap::{Parser, ValueEnum};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "serde")]
+use std::fs;
+use std::{
+    error::Error,
+    fmt,
+    net::SocketAddr,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use tracing::{instrument, level_filters::LevelFilter};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputKind {
+    Spi,
+    I2c,
+    Stdout,
+    Terminal,
+    Tcp,
+    Udp,
+    Osc,
+    Hyperion,
+    Dmx,
+    Mqtt,
+    Mock,
+}
+
+fn parse_i2c_address(s: &str) -> Result<u16, String> {
+    let address = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .map_err(|_| format!("invalid I2C address: {}", s))?;
+
+    if !(0x08..=0x77).contains(&address) {
+        return Err(format!(
+            "I2C address must be between 0x08 and 0x77, got: {}",
+            s
+        ));
+    }
+    Ok(address)
+}
+
+fn parse_white_balance(s: &str) -> Result<[f32; 3], String> {
+    let invalid = || format!("expected R,G,B gains, got: {}", s);
+
+    let gains: Vec<f32> = s
+        .split(',')
+        .map(|gain| gain.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    gains.try_into().map_err(|_| invalid())
+}
+
+fn parse_dmx_channel(s: &str) -> Result<(usize, u16), String> {
+    let invalid = || format!("expected LED:CHANNEL, got: {}", s);
+
+    let (led, channel) = s.split_once(':').ok_or_else(invalid)?;
+    let led: usize = led.parse().map_err(|_| invalid())?;
+    let channel: u16 = channel.parse().map_err(|_| invalid())?;
+    if !(1..=510).contains(&channel) {
+        return Err(format!(
+            "DMX start channel must be between 1 and 510, got: {}",
+            channel
+        ));
+    }
+    Ok((led, channel))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DryRunMode {
+    Summary,
+    Full,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpiStripConfig {
+    pub bus: SpiBus,
+    pub slave_select: SpiSlaveSelect,
+    pub clock_speed: u32,
+    pub leds: Range<usize>,
+}
+
+impl FromStr for SpiStripConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected BUS:SS:HZ:FIRST-LAST, got: {}", s);
+
+        let parts: Vec<&str> = s.split(':').collect();
+        let [bus, slave_select, clock_speed, leds] = parts[..] else {
+            return Err(invalid());
+        };
+        let (first, last) = leds.split_once('-').ok_or_else(invalid)?;
+        let first: usize = first.parse().map_err(|_| invalid())?;
+        let last: usize = last.parse().map_err(|_| invalid())?;
+        if last < first {
+            return Err(format!("LED range {} ends before it starts", leds));
+        }
+
+        Ok(SpiStripConfig {
+            bus: parse_bus(bus)?,
+            slave_select: parse_slave_select(slave_select)?,
+            clock_speed: parse_clock_speed(clock_speed)?,
+            leds: first..last + 1,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigFileError {
+    Read(String),
+    Parse(String),
+}
+
+impl fmt::Display for ConfigFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigFileError::Read(err) => write!(f, "unable to read config file: {}", err),
+            ConfigFileError::Parse(err) => write!(f, "invalid config file: {}", err),
+        }
+    }
+}
+
+impl Error for ConfigFileError {}
+
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(deny_unknown_fields))]
+pub struct MqttSection {
+    pub broker_url: Option<String>,
+    pub port: Option<u16>,
+    pub topic_prefix: Option<String>,
+    pub qos: Option<u8>,
+    pub ca_cert: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+// Settings that are awkward to pass as flags, read from the TOML file given
+// with --config
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Deserialize), serde(deny_unknown_fields))]
+pub struct ConfigFile {
+    pub color_matrix: Option<ColorMatrix>,
+    pub mqtt: Option<MqttSection>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttTls {
+    pub ca_cert: PathBuf,
+    pub client_auth: Option<(PathBuf, PathBuf)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MqttSettings {
+    pub broker_url: String,
+    pub port: u16,
+    pub topic_prefix: String,
+    pub qos: u8,
+    pub tls: Option<MqttTls>,
+}
+
+impl ConfigFile {
+    #[cfg(feature = "serde")]
+    pub fn from_toml(s: &str) -> Result<Self, ConfigFileError> {
+        toml::from_str(s).map_err(|err| ConfigFileError::Parse(err.to_string()))
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: &Path) -> Result<Self, ConfigFileError> {
+        let s = fs::read_to_string(path)
+            .map_err(|err| ConfigFileError::Read(format!("{}: {}", path.display(), err)))?;
+        Self::from_toml(&s)
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn load(path: &Path) -> Result<Self, ConfigFileError> {
+        Err(ConfigFileError::Parse(format!(
+            "{}: reading config files needs the serde feature",
+            path.display()
+        )))
+    }
+}
+
+#[derive(Parser, Clone, Debug)]
+#[command(version, about)]
+pub struct Config {
+    /// TOML file with [color_matrix] and [mqtt] sections; flags given on the
+    /// command line take precedence over the file
+    #[arg(long = "config", value_name = "PATH")]
+    pub config_file: Option<PathBuf>,
+
+    /// Map the camera frame to LEDs around a circle, an ellipse stretched to
+    /// the frame's aspect ratio, along the border of the frame (e.g. behind a
+    /// TV), from left to right across the bottom of the frame (e.g. under a
+    /// desk), or to a grid of LEDs showing a downscaled frame
+    #[arg(long, value_enum, default_value_t = SegmentLayout::Circle)]
+    pub layout: SegmentLayout,
+
+    /// Number of LEDs on each edge of the border layout
+    #[arg(
+        long,
+        value_name = "TOP,RIGHT,BOTTOM,LEFT",
+        required_if_eq("layout", "border")
+    )]
+    pub border_leds: Option<EdgeCounts>,
+
+    /// Corner the first LED of the border layout starts at, continuing
+    /// clockwise
+    #[arg(long, value_enum, default_value_t = Corner::TopLeft)]
+    pub border_start: Corner,
+
+    /// Fraction of the smaller frame dimension to map to the border LEDs
+    /// (0.0 - 1.0)
+    #[arg(long, default_value_t = DEFAULT_BORDER_THICKNESS)]
+    pub border_thickness: f64,
+
+    /// Only map the part of the frame inside these insets, given as one
+    /// fraction for every side or TOP,RIGHT,BOTTOM,LEFT fractions of the
+    /// frame (e.g. to ignore the wall around a screen)
+    #[arg(long, value_name = "INSETS")]
+    pub crop: Option<Crop>,
+
+    /// Only map the part of the frame inside this rectangle, given in
+    /// pixels
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT", conflicts_with = "crop")]
+    pub crop_rect: Option<CropRect>,
+
+    /// Columns and rows of LEDs in the matrix layout
+    #[arg(long, value_name = "COLSxROWS", required_if_eq("layout", "matrix"))]
+    pub matrix_size: Option<MatrixSize>,
+
+    /// Matrix rows are wired in a zig-zag, with every other row running
+    /// from right to left
+    #[arg(long)]
+    pub serpentine: bool,
+
+    /// Fraction of the frame height at the bottom to map to the LEDs of the
+    /// bottom layout (0.0 - 1.0)
+    #[arg(long, default_value_t = DEFAULT_BAND_FRACTION)]
+    pub band_fraction: f64,
+
+    /// Mirror the segment mapping left to right
+    #[arg(long)]
+    pub flip_horizontal: bool,
+
+    /// Mirror the segment mapping top to bottom
+    #[arg(long)]
+    pub flip_vertical: bool,
+
+    /// Rotate the segment mapping by the given number of degrees
+    #[arg(long, value_enum, default_value_t = Rotation::Rotate0)]
+    pub rotate: Rotation,
+
+    /// Rotate the segment mapping by an arbitrary number of degrees on top
+    /// of --rotate, for cameras mounted at an angle or rings whose first LED
+    /// isn't at the default start angle
+    #[arg(
+        long,
+        visible_alias = "start-angle",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    pub rotate_degrees: f64,
+
+    /// Number the segments counter-clockwise for rings wired in that
+    /// direction
+    #[arg(long)]
+    pub counter_clockwise: bool,
+
+    /// Angles in degrees where each LED's segment of the circle layout
+    /// starts, for LEDs that aren't evenly spaced (e.g. 0,90,180)
+    #[arg(long, value_name = "DEGREES", value_delimiter = ',')]
+    pub segment_boundaries: Vec<f64>,
+
+    /// TOML file listing each LED's angular range in degrees, as
+    /// `start`/`end` or `center`/`width`, for rings with gaps between LEDs
+    #[arg(long, value_name = "PATH", conflicts_with = "segment_boundaries")]
+    pub led_layout: Option<PathBuf>,
+
+    /// Split the circle layout into concentric rings, each sampling its own
+    /// band of the frame given as fractions of the distance from the center
+    /// to the nearest edge, optionally with a start angle in degrees and a
+    /// direction (e.g. --ring-zone 24,0.3,0.6 --ring-zone 36,0.6,1.0,90,ccw).
+    /// LEDs are numbered one ring after another along a single strip
+    #[arg(
+        long = "ring-zone",
+        value_name = "LEDS,INNER,OUTER[,START[,cw|ccw]]",
+        conflicts_with_all = ["segment_boundaries", "led_layout"]
+    )]
+    pub ring_zones: Vec<RingZone>,
+
+    /// Fraction of the distance from the frame center to its nearest edge
+    /// to leave unmapped (0.0 - 1.0)
+    #[arg(long, default_value_t = DEFAULT_EDGE_FRACTION)]
+    pub edge_fraction: f64,
+
+    /// Fraction of the distance from the frame center to its nearest edge
+    /// beyond which pixels are ignored (0.0 - 1.0), e.g. to skip distorted
+    /// fisheye corners
+    #[arg(long)]
+    pub outer_fraction: Option<f64>,
+
+    /// Average LED colors over the last K camera frames
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u16).range(1..))]
+    pub frame_average: Option<u16>,
+
+    /// Drop the frame average when the mean per-channel change between
+    /// frames exceeds this threshold so hard cuts show up immediately
+    #[arg(long, value_name = "THRESHOLD")]
+    pub scene_change_threshold: Option<f32>,
+
+    /// Smooth LEDs more heavily while their brightness rapidly flickers,
+    /// e.g. from fluorescent lights beating against the camera frame rate
+    #[arg(long)]
+    pub flicker_suppression: bool,
+
+    /// Blend each LED with this many neighbors on either side to soften
+    /// the boundaries between segments, 0 to disable
+    #[arg(long, value_name = "RADIUS", default_value_t = 0)]
+    pub blur: usize,
+
+    /// Only average every Nth pixel along each axis of the camera frame
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub sample_stride: u32,
+
+    /// Weigh pixels by their distance from the center of the frame raised
+    /// to this power, so the outer edge counts more toward each LED
+    #[arg(long, value_name = "POWER")]
+    pub radial_weight: Option<f64>,
+
+    /// Average pixels in linear light instead of gamma-encoded sRGB for
+    /// more accurate color mixing
+    #[arg(long)]
+    pub linear_light: bool,
+
+    /// Ignore color changes smaller than this on every channel to reduce
+    /// flicker
+    #[arg(long, value_name = "THRESHOLD")]
+    pub hysteresis: Option<u8>,
+
+    /// Only update an LED when its color moves further than this RGB
+    /// distance, 0 to update on every change
+    #[arg(long, default_value_t = 0.0)]
+    pub dead_band_threshold: f32,
+
+    /// Shift the hue of every LED by the given number of degrees
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub hue_rotation_degrees: f32,
+
+    /// Boost the saturation of every LED color by the given fraction
+    /// (e.g. 0.5 for +50%)
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub saturation_boost: f32,
+
+    /// Start of the hue range, in degrees, shielded from the saturation boost
+    #[arg(long, default_value_t = 15.0)]
+    pub protected_hue_start: f32,
+
+    /// End of the hue range, in degrees, shielded from the saturation boost
+    #[arg(long, default_value_t = 50.0)]
+    pub protected_hue_end: f32,
+
+    /// How much of the saturation boost is withheld from protected hues
+    /// (0.0 - 1.0)
+    #[arg(long, default_value_t = 0.8)]
+    pub hue_protection: f32,
+
+    /// Convert every LED color to luminance-weighted gray
+    #[arg(long)]
+    pub grayscale: bool,
+
+    /// Correct the camera's color cast with a 3x3 matrix applied to every
+    /// LED color, given row by row (e.g. 1.1,0,0,0,1,0,0,0,0.9). Overrides
+    /// the [color_matrix] section of the config file
+    #[arg(long, value_name = "RR,RG,RB,GR,GG,GB,BR,BG,BB")]
+    pub color_matrix: Option<ColorMatrix>,
+
+    /// Blend every LED color toward gray by the given amount (0.0 - 1.0)
+    #[arg(long, default_value_t = 0.0)]
+    pub desaturate: f32,
+
+    /// Replace every LED color with its complement
+    #[arg(long)]
+    pub invert: bool,
+
+    /// Gamma correction applied to every LED color, 1.0 to leave colors
+    /// unchanged
+    #[arg(long, default_value_t = 1.0)]
+    pub gamma: f32,
+
+    /// Dim the strip to follow the overall brightness of the captured scene
+    #[arg(long)]
+    pub auto_brightness: bool,
+
+    /// How scene luminance maps to strip brightness
+    #[arg(long, value_enum, default_value_t = BrightnessCurve::Linear)]
+    pub auto_brightness_curve: BrightnessCurve,
+
+    /// Lowest strip brightness auto-brightness may dim to (0.0 - 1.0)
+    #[arg(long, default_value_t = 0.1)]
+    pub auto_brightness_min: f32,
+
+    /// Scale the strip down to stay within this estimated current draw
+    #[arg(long, value_name = "MA")]
+    pub max_milliamps: Option<u32>,
+
+    /// Continuously adjust brightness to keep the average current draw near
+    /// --abl-target-milliamps
+    #[arg(long = "abl")]
+    pub abl_enabled: bool,
+
+    /// Average current draw automatic brightness limiting aims for
+    #[arg(long, value_name = "MA", default_value_t = 2000, value_parser = clap::value_parser!(u32).range(1..))]
+    pub abl_target_milliamps: u32,
+
+    /// LED driver chip on the strip, sk6812-rgbw needs a 2.4 MHz SPI clock
+    #[arg(long, value_enum, default_value_t = ChipProfile::Apa102)]
+    pub chip: ChipProfile,
+
+    /// Share of the gray in each color moved onto the white channel of RGBW
+    /// strips (0.0 - 1.0)
+    #[arg(long, value_name = "AMOUNT", default_value_t = 1.0)]
+    pub white_extraction: f32,
+
+    /// Overall strip brightness in APA102 global brightness steps (0 - 31)
+    #[arg(long, default_value_t = 31)]
+    pub brightness: u8,
+
+    /// Per-channel gains applied on output to correct the strip's white
+    /// point
+    #[arg(long, value_name = "R,G,B", default_value = "1,1,1", value_parser = parse_white_balance)]
+    pub white_balance: [f32; 3],
+
+    /// Spread rounding error across frames to smooth gradients when dimmed
+    #[arg(long)]
+    pub dither: bool,
+
+    /// Physical position of the first LED along the strip
+    #[arg(long, default_value_t = 0)]
+    pub led_offset: usize,
+
+    /// Treat the strip as wired in the opposite direction
+    #[arg(long)]
+    pub reverse_leds: bool,
+
+    /// Where to send LED data, may be repeated to write to several outputs
+    #[arg(long = "output", value_enum, default_values_t = [OutputKind::Spi])]
+    pub outputs: Vec<OutputKind>,
+
+    /// Log a summary of each second of LED frames instead of writing them to
+    /// SPI, or with full also dump every LED of every Kth frame
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "summary"
+    )]
+    pub dry_run: Option<DryRunMode>,
+
+    /// Dump every Kth frame in the full dry run
+    #[arg(long, value_name = "K", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub dry_run_every: u64,
+
+    /// SPI bus the strip is wired to (spi0 - spi6)
+    #[arg(long, default_value = "spi0", value_parser = parse_bus)]
+    pub spi_bus: SpiBus,
+
+    /// SPI slave select (chip enable) line the strip is wired to (ss0 - ss2)
+    #[arg(
+        long,
+        visible_alias = "spi-ss",
+        default_value = "ss0",
+        value_parser = parse_slave_select
+    )]
+    pub spi_slave_select: SpiSlaveSelect,
+
+    /// SPI clock speed in Hz (100000 - 32000000)
+    #[arg(
+        long,
+        visible_alias = "spi-hz",
+        default_value = "16000000",
+        value_parser = parse_clock_speed
+    )]
+    pub spi_clock_speed: u32,
+
+    /// Halve the SPI clock speed each time the device is reopened after
+    /// repeated write errors, down to this many Hz
+    #[arg(long, value_name = "HZ", value_parser = parse_clock_speed)]
+    pub spi_min_clock_speed: Option<u32>,
+
+    /// SPI mode
+    #[arg(long, value_enum, default_value_t = SpiMode::Mode0)]
+    pub spi_mode: SpiMode,
+
+    /// Drive part of the strip from its own SPI bus, may be repeated
+    /// (e.g. spi0:ss0:16000000:0-35 for LEDs 0 to 35 on SPI0, slave select 0)
+    #[arg(long = "spi-strip", value_name = "BUS:SS:HZ:FIRST-LAST")]
+    pub spi_strips: Vec<SpiStripConfig>,
+
+    /// Number of times to retry a failed SPI or I2C write within a frame
+    #[arg(long, default_value_t = 3)]
+    pub spi_retries: u32,
+
+    /// Milliseconds to wait between SPI or I2C write retries
+    #[arg(long, default_value_t = 5)]
+    pub spi_retry_delay_ms: u64,
+
+    /// Reopen the SPI or I2C device after this many consecutive failed frames
+    #[arg(long, default_value_t = 5)]
+    pub spi_reopen_after: u32,
+
+    /// Skip SPI writes when no LED has changed noticeably since the last
+    /// written frame
+    #[arg(long)]
+    pub adaptive_spi: bool,
+
+    /// Largest RGB distance an LED can move before adaptive SPI writes the
+    /// frame
+    #[arg(long, default_value_t = 0.0)]
+    pub change_threshold: f32,
+
+    /// Write every this many frames with adaptive SPI even when nothing
+    /// changed
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    pub force_write_interval: u32,
+
+    /// Re-send the last frame to the LED outputs after this long without a
+    /// new one (e.g. 2s)
+    #[arg(long, value_parser = humantime::parse_duration)]
+    pub keep_alive: Option<Duration>,
+
+    /// Log every frame written to the mock output to this file
+    #[arg(long, value_name = "PATH")]
+    pub mock_log: Option<PathBuf>,
+
+    /// Fail if frames reach the mock output slower than this rate
+    #[arg(long, value_name = "FPS")]
+    pub mock_min_fps: Option<f32>,
+
+    /// Fail if frames reach the mock output faster than this rate
+    #[arg(long, value_name = "FPS")]
+    pub mock_max_fps: Option<f32>,
+
+    /// I2C bus the LED controllers are wired to
+    #[arg(long, default_value_t = 1)]
+    pub i2c_bus: u8,
+
+    /// Address of the first I2C LED controller, further controllers are
+    /// addressed sequentially after it
+    #[arg(long, default_value = "0x40", value_parser = parse_i2c_address)]
+    pub i2c_address: u16,
+
+    /// Number of LEDs each I2C controller drives
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+    pub i2c_leds_per_device: u64,
+
+    /// Address of the afterglow receiver to stream frames to over TCP
+    #[arg(long, value_name = "HOST:PORT", required_if_eq("outputs", "tcp"))]
+    pub tcp_target: Option<String>,
+
+    /// Broadcast address to send LED data to over UDP (e.g.
+    /// 192.168.1.255:7891)
+    #[arg(long, value_name = "ADDR:PORT", required_if_eq("outputs", "udp"))]
+    pub udp_target: Option<SocketAddr>,
+
+    /// Address to send OSC bundles to
+    #[arg(long, value_name = "ADDR:PORT", required_if_eq("outputs", "osc"))]
+    pub osc_target: Option<SocketAddr>,
+
+    /// Send one OSC message per LED (/afterglow/led/<i>) instead of a single
+    /// blob message (/afterglow/frame) for the whole strip
+    #[arg(long)]
+    pub osc_per_led: bool,
+
+    /// Serial port of the Enttec DMX USB Pro widget
+    #[arg(long, default_value = "/dev/ttyUSB0")]
+    pub dmx_port: PathBuf,
+
+    /// Send an LED to the DMX channel triplet starting at the given channel,
+    /// may be repeated (e.g. 0:1 for LED 0 on channels 1 to 3)
+    #[arg(
+        long = "dmx-channel",
+        value_name = "LED:CHANNEL",
+        value_parser = parse_dmx_channel,
+        required_if_eq("outputs", "dmx")
+    )]
+    pub dmx_channels: Vec<(usize, u16)>,
+
+    /// Address of the Hyperion/HyperHDR flatbuffers server
+    #[arg(long, value_name = "HOST:PORT", required_if_eq("outputs", "hyperion"))]
+    pub hyperion_target: Option<String>,
+
+    /// Priority to register with Hyperion
+    #[arg(long, default_value_t = 150)]
+    pub hyperion_priority: i32,
+
+    /// Origin name to register with Hyperion
+    #[arg(long, default_value = "afterglow")]
+    pub hyperion_origin: String,
+
+    /// Host of the MQTT broker to publish LED colors to
+    #[arg(long, value_name = "HOST")]
+    pub mqtt_broker_url: Option<String>,
+
+    /// Port of the MQTT broker (defaults to 1883, or 8883 over TLS)
+    #[arg(long)]
+    pub mqtt_port: Option<u16>,
+
+    /// Prefix of the leds and status topics (defaults to afterglow)
+    #[arg(long, value_name = "PREFIX")]
+    pub mqtt_topic_prefix: Option<String>,
+
+    /// MQTT quality of service to publish with (0, 1 or 2)
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=2))]
+    pub mqtt_qos: Option<u8>,
+
+    /// PEM CA certificate to verify the MQTT broker with, connecting over
+    /// TLS
+    #[arg(long, value_name = "PATH")]
+    pub mqtt_ca_cert: Option<PathBuf>,
+
+    /// PEM client certificate to authenticate to the MQTT broker with over
+    /// TLS
+    #[arg(long, value_name = "PATH")]
+    pub mqtt_client_cert: Option<PathBuf>,
+
+    /// PEM private key of --mqtt-client-cert
+    #[arg(long, value_name = "PATH")]
+    pub mqtt_client_key: Option<PathBuf>,
+
+    /// Receive frames over TCP on the given address (e.g. :7890) instead of
+    /// capturing from the camera
+    #[arg(long, value_name = "ADDR")]
+    pub receive: Option<String>,
+
+    /// Receive LED data broadcast over UDP on the given port and pass it
+    /// straight through to the outputs instead of capturing from the camera
+    #[arg(long, value_name = "PORT", conflicts_with = "receive")]
+    pub receive_udp: Option<u16>,
+
+    /// Record the LED output to an animated GIF
+    #[arg(long, value_name = "PATH")]
+    pub record_gif: Option<PathBuf>,
+
+    /// Stop recording after the given duration (e.g. 30s)
+    #[arg(long, value_parser = humantime::parse_duration, requires = "record_gif")]
+    pub record_duration: Option<Duration>,
+
+    /// Size in pixels of each LED in the recording
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u16).range(1..))]
+    pub record_pixel_size: u16,
+
+    /// Log every frame sent to the outputs to a file for later replay
+    #[arg(long, value_name = "PATH")]
+    pub record_frames: Option<PathBuf>,
+
+    /// Save the LED colors to this file after every frame and restore them on
+    /// startup
+    #[arg(long, value_name = "PATH")]
+    pub persist_state: Option<PathBuf>,
+
+    /// Exit instead of warning when the camera can't capture at the selected
+    /// resolution and frame rate
+    #[arg(long)]
+    pub strict_format: bool,
+
+    /// Log spans and their timings at or above this level to stderr (off,
+    /// error, warn, info, debug or trace)
+    #[arg(long, default_value = "info")]
+    pub log_level: LevelFilter,
+
+    /// Format of the log output
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty)]
+    pub log_format: LogFormat,
+
+    /// Serve Prometheus metrics at /metrics on the given address (e.g.
+    /// :9100)
+    #[arg(long, value_name = "ADDR")]
+    pub metrics_addr: Option<String>,
+
+    /// Serve a JSON API on the given port with /health, /status, /config and
+    /// /leds, where POST /leds overrides the LED colors until DELETE /leds
+    #[arg(long, value_name = "PORT")]
+    pub http_api_port: Option<u16>,
+
+    /// Stream the LED colors of every frame to WebSocket clients on the
+    /// given port, e.g. for assets/dashboard.html
+    #[arg(long, value_name = "PORT")]
+    pub websocket_port: Option<u16>,
+
+    /// Fade the LEDs out over this many milliseconds on exit, 0 turns them
+    /// off immediately
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    pub fade_out_ms: u64,
+
+    /// Fade the LEDs to black over this many milliseconds once the camera
+    /// stops delivering frames, 0 holds the last frame instead
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub idle_fade_ms: u64,
+
+    /// Always build the segment map instead of reusing the one cached under
+    /// ~/.cache/afterglow for the same settings and resolution
+    #[arg(long = "no-segment-cache")]
+    pub no_segment_cache: bool,
+
+    /// Skip the chase and color flash played on startup
+    #[arg(long = "no-selftest")]
+    pub no_selftest: bool,
+
+    /// Light each LED in turn on startup to check the wiring
+    #[arg(long)]
+    pub self_test: bool,
+
+    /// Drive the strip with an animated effect instead of the camera
+    /// (breath modulates the camera colors unless --effect-color is set)
+    #[arg(long, value_enum)]
+    pub effect: Option<EffectKind>,
+
+    /// How far the effect advances each frame, in degrees of hue
+    #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+    pub effect_speed: f32,
+
+    /// Length of one breath cycle in milliseconds
+    #[arg(long, default_value_t = 2000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub effect_period: u64,
+
+    /// Base color (RRGGBB) for the breath effect to run without the camera,
+    /// or the highlight color of the chase effect
+    #[arg(long, value_parser = parse_hex_color)]
+    pub effect_color: Option<u32>,
+
+    /// Background color (RRGGBB) behind the chase highlight
+    #[arg(long, default_value = "000000", value_parser = parse_hex_color)]
+    pub effect_background: u32,
+
+    /// Number of fully lit LEDs in the chase highlight
+    #[arg(long, default_value_t = 3)]
+    pub chase_width: usize,
+
+    /// Number of LEDs on each side of the chase highlight to fade over
+    #[arg(long, default_value_t = 2)]
+    pub chase_blend: usize,
+
+    /// Milliseconds for the chase highlight to move one LED
+    #[arg(long, default_value_t = 50, value_parser = clap::value_parser!(u64).range(1..))]
+    pub chase_step_ms: u64,
+
+    /// Frames per second to render the effect at
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=1000))]
+    pub effect_fps: u32,
+
+    /// Drive the strip with a test pattern instead of the camera
+    /// (off, white, rainbow, indexed, chase[:MS] or solid:RRGGBB)
+    #[arg(long)]
+    pub test_pattern: Option<TestPattern>,
+}
+
+impl Config {
+    pub fn with_config_file(mut self) -> Result<Self, ConfigFileError> {
+        if let Some(path) = &self.config_file {
+            self.merge(ConfigFile::load(path)?);
+        }
+        Ok(self)
+    }
+
+    pub fn merge(&mut self, file: ConfigFile) {
+        self.color_matrix = self.color_matrix.or(file.color_matrix);
+        if let Some(mqtt) = file.mqtt {
+            self.mqtt_broker_url = self.mqtt_broker_url.take().or(mqtt.broker_url);
+            self.mqtt_port = self.mqtt_port.or(mqtt.port);
+            self.mqtt_topic_prefix = self.mqtt_topic_prefix.take().or(mqtt.topic_prefix);
+            self.mqtt_qos = self.mqtt_qos.or(mqtt.qos);
+            self.mqtt_ca_cert = self.mqtt_ca_cert.take().or(mqtt.ca_cert);
+            self.mqtt_client_cert = self.mqtt_client_cert.take().or(mqtt.client_cert);
+            self.mqtt_client_key = self.mqtt_client_key.take().or(mqtt.client_key);
+        }
+    }
+
+    pub fn mqtt_settings(&self) -> Result<MqttSettings, String> {
+        let broker_url = self
+            .mqtt_broker_url
+            .clone()
+            .ok_or("the mqtt output needs --mqtt-broker-url or broker_url under [mqtt]")?;
+        let qos = self.mqtt_qos.unwrap_or(0);
+        if qos > 2 {
+            return Err(format!("MQTT QoS {} out of range (0 - 2)", qos));
+        }
+
+        let client_auth = match (&self.mqtt_client_cert, &self.mqtt_client_key) {
+            (Some(cert), Some(key)) => Some((cert.clone(), key.clone())),
+            (None, None) => None,
+            _ => return Err("MQTT client certificates need both a certificate and a key".into()),
+        };
+        let tls = match (&self.mqtt_ca_cert, client_auth) {
+            (Some(ca_cert), client_auth) => Some(MqttTls {
+                ca_cert: ca_cert.clone(),
+                client_auth,
+            }),
+            (None, None) => None,
+            (None, Some(_)) => {
+                return Err("MQTT client certificates need a CA certificate for TLS".into())
+            }
+        };
+
+        Ok(MqttSettings {
+            broker_url,
+            port: self
+                .mqtt_port
+                .unwrap_or(if tls.is_some() { 8883 } else { 1883 }),
+            topic_prefix: self
+                .mqtt_topic_prefix
+                .clone()
+                .unwrap_or_else(|| "afterglow".to_string()),
+            qos,
+            tls,
+        })
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        Orientation {
+            flip_horizontal: self.flip_horizontal,
+            flip_vertical: self.flip_vertical,
+            rotation: self.rotate,
+            rotation_degrees: self.rotate_degrees,
+            clockwise: !self.counter_clockwise,
+            elliptical: self.layout == SegmentLayout::Ellipse,
+        }
+    }
+
+    pub fn crop_region(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        match (self.crop, self.crop_rect) {
+            (Some(crop), _) => Some(crop.region(width, height)),
+            (None, Some(crop_rect)) => Some(crop_rect.region(width, height)),
+            (None, None) => None,
+        }
+    }
+
+    #[instrument(level = "debug", skip(self))]
+    pub fn segment_map(
+        &self,
+        num_leds: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<SegmentMap, SegmentMapError> {
+        let builder = SegmentMapBuilder::new()
+            .num_leds(num_leds)
+            .resolution(width, height)
+            .orientation(self.orientation())
+            .inner_radius(self.edge_fraction)
+            .outer_radius(self.outer_fraction)
+            .crop(self.crop_region(width, height));
+        // Circle layouts already mirror through their orientation
+        let mirrored = |segment_map, width| {
+            mirror_segment_map(segment_map, width, self.flip_horizontal, self.flip_vertical)
+        };
+
+        match self.layout {
+            SegmentLayout::Circle | SegmentLayout::Ellipse if !self.ring_zones.is_empty() => {
+                builder.zones(self.ring_zones.clone()).build()
+            }
+            SegmentLayout::Circle | SegmentLayout::Ellipse if self.led_layout.is_some() => {
+                let layout = LedLayout::load(self.led_layout.as_ref().unwrap())
+                    .map_err(SegmentMapError::Layout)?;
+                if layout.num_leds() != num_leds {
+                    return Err(SegmentMapError::LedCount(
+                        "LED",
+                        num_leds,
+                        layout.num_leds(),
+                    ));
+                }
+
+                builder.build_with(|width, height| {
+                    build_segment_map_from_layout(
+                        &layout,
+                        width,
+                        height,
+                        self.orientation(),
+                        self.edge_fraction,
+                        self.outer_fraction,
+                    )
+                })
+            }
+            SegmentLayout::Circle | SegmentLayout::Ellipse
+                if self.segment_boundaries.is_empty() =>
+            {
+                builder.build()
+            }
+            SegmentLayout::Circle | SegmentLayout::Ellipse => builder
+                .boundaries(
+                    self.segment_boundaries
+                        .iter()
+                        .map(|degrees| degrees.to_radians())
+                        .collect(),
+                )
+                .build(),
+            SegmentLayout::Border => {
+                let counts = self.border_leds.unwrap();
+                if counts.total() != num_leds {
+                    return Err(SegmentMapError::LedCount(
+                        "border",
+                        num_leds,
+                        counts.total(),
+                    ));
+                }
+
+                builder.build_with(|width, height| {
+                    mirrored(
+                        build_border_segment_map(
+                            counts,
+                            self.border_start,
+                            width,
+                            height,
+                            self.border_thickness,
+                        ),
+                        width,
+                    )
+                })
+            }
+            SegmentLayout::Bottom => builder.build_with(|width, height| {
+                mirrored(
+                    build_bottom_segment_map(num_leds, width, height, self.band_fraction),
+                    width,
+                )
+            }),
+            SegmentLayout::Matrix => {
+                let MatrixSize { cols, rows } = self.matrix_size.unwrap();
+                if cols * rows != num_leds {
+                    return Err(SegmentMapError::LedCount("matrix", num_leds, cols * rows));
+                }
+
+                builder.build_with(|width, height| {
+                    mirrored(
+                        build_matrix_segment_map(cols, rows, self.serpentine, width, height),
+                        width,
+                    )
+                })
+            }
+        }
+    }
+
+    // Covers everything segment_map reads so that changing any of it misses
+    // the segment map cache
+    pub fn segment_map_params(&self, num_leds: usize, width: u32, height: u32) -> String {
+        format!(
+            "{} leds at {}x{}, {:?} {:?} crop {:?}, radii {:?} {:?}, boundaries {:?}, layout {:?}, \
+             zones {:?}, border {:?} {:?} {:?}, band {:?}, matrix {:?} {:?}",
+            num_leds,
+            width,
+            height,
+            self.layout,
+            self.orientation(),
+            self.crop_region(width, height),
+            self.edge_fraction,
+            self.outer_fraction,
+            self.segment_boundaries,
+            self.led_layout.as_deref().map(LedLayout::load),
+            self.ring_zones,
+            self.border_leds,
+            self.border_start,
+            self.border_thickness,
+            self.band_fraction,
+            self.matrix_size,
+            self.serpentine,
+        )
+    }
+
+    pub fn segment_index(&self, segment_map: &SegmentMap) -> SegmentIndex {
+        let mut segment_index =
+            SegmentIndex::new(&segment_map.subsample(self.sample_stride as usize));
+        if let Some(power) = self.radial_weight {
+            segment_index = segment_index.with_radial_weights(segment_map, power);
+        }
+        if self.linear_light {
+            segment_index = segment_index.with_linear_light();
+        }
+        segment_index
+    }
+
+    pub fn power_limiter(&self) -> PowerLimiter {
+        PowerLimiter::new(
+            self.abl_enabled.then_some(self.abl_target_milliamps),
+            self.max_milliamps,
+        )
+    }
+
+    pub fn spi_settings(&self) -> SpiSettings {
+        SpiSettings {
+            bus: self.spi_bus,
+            slave_select: self.spi_slave_select,
+            clock_speed: self.spi_clock_speed,
+            mode: self.spi_mode,
+        }
+    }
+
+    pub fn dry_run_dump_every(&self) -> Option<u64> {
+        (self.dry_run == Some(DryRunMode::Full)).then_some(self.dry_run_every)
+    }
+
+    pub fn hue_enhancement(&self) -> Option<HueEnhancement> {
+        (self.saturation_boost != 0.0).then_some(HueEnhancement {
+            saturation_boost: self.saturation_boost,
+            protected_hue_start: self.protected_hue_start,
+            protected_hue_end: self.protected_hue_end,
+            protection: self.hue_protection,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "serde")]
+    use crate::color::ColorMatrix;
+    use crate::config::{
+        parse_dmx_channel, parse_i2c_address, parse_white_balance, Config, SpiStripConfig,
+    };
+    #[cfg(feature = "serde")]
+    use crate::config::{ConfigFile, MqttSettings, MqttTls};
+    use crate::logging::LogFormat;
+    use crate::segment_map::{LayoutError, SegmentMapError};
+    use crate::spi_settings::{SpiBus, SpiMode, SpiSettings, SpiSlaveSelect};
+    use clap::Parser;
+    use tracing::level_filters::LevelFilter;
+
+    #[test]
+    fn it_parses_spi_strips() {
+        assert_eq!(
+            "0:0:16000000:0-35".parse(),
+            Ok(SpiStripConfig {
+                bus: SpiBus::Spi0,
+                slave_select: SpiSlaveSelect::Ss0,
+                clock_speed: 16_000_000,
+                leds: 0..36,
+            })
+        );
+        assert_eq!(
+            "spi1:ss2:8000000:36-83".parse(),
+            Ok(SpiStripConfig {
+                bus: SpiBus::Spi1,
+                slave_select: SpiSlaveSelect::Ss2,
+                clock_speed: 8_000_000,
+                leds: 36..84,
+            })
+        );
+        assert!("0:0:16000000".parse::<SpiStripConfig>().is_err());
+        assert!("0:0:16000000:35".parse::<SpiStripConfig>().is_err());
+        assert!("0:0:16000000:35-0".parse::<SpiStripConfig>().is_err());
+        assert!("spi9:0:16000000:0-35".parse::<SpiStripConfig>().is_err());
+        assert!("0:0:64000000:0-35".parse::<SpiStripConfig>().is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_reads_the_color_matrix_from_the_config_file() {
+        let file =
+            ConfigFile::from_toml("[color_matrix]\nrr = 0.0\nrg = 1.0\ngr = 1.0\ngg = 0.0\n")
+                .unwrap();
+        let swap_red_green = ColorMatrix {
+            rr: 0.0,
+            rg: 1.0,
+            gr: 1.0,
+            gg: 0.0,
+            ..ColorMatrix::default()
+        };
+        assert_eq!(file.color_matrix, Some(swap_red_green));
+
+        let mut config = Config::try_parse_from(["afterglow"]).unwrap();
+        config.merge(file);
+        assert_eq!(config.color_matrix, Some(swap_red_green));
+
+        let mut config =
+            Config::try_parse_from(["afterglow", "--color-matrix", "1,0,0,0,1,0,0,0,0.5"]).unwrap();
+        config.merge(ConfigFile::from_toml("[color_matrix]\nrr = 0.0\n").unwrap());
+        assert_eq!(config.color_matrix.unwrap().bb, 0.5);
+        assert_eq!(config.color_matrix.unwrap().rr, 1.0);
+
+        assert_eq!(ConfigFile::from_toml("").unwrap(), ConfigFile::default());
+        assert!(ConfigFile::from_toml("[color_matrix]\nrx = 1.0\n").is_err());
+        assert!(ConfigFile::from_toml("[colour_matrix]\n").is_err());
+    }
+
+    #[test]
+    fn it_rejects_layouts_that_do_not_cover_every_led() {
+        let config =
+            Config::try_parse_from(["afterglow", "--layout", "matrix", "--matrix-size", "8x8"])
+                .unwrap();
+        assert_eq!(
+            config.segment_map(36, 64, 48),
+            Err(SegmentMapError::LedCount("matrix", 36, 64))
+        );
+        assert!(config.segment_map(64, 64, 48).is_ok());
+
+        let config = Config::try_parse_from([
+            "afterglow",
+            "--layout",
+            "border",
+            "--border-leds",
+            "10,8,10,8",
+        ])
+        .unwrap();
+        assert_eq!(
+            config.segment_map(50, 64, 48),
+            Err(SegmentMapError::LedCount("border", 50, 36))
+        );
+
+        let config =
+            Config::try_parse_from(["afterglow", "--led-layout", "/nonexistent/layout.toml"])
+                .unwrap();
+        assert!(matches!(
+            config.segment_map(36, 64, 48),
+            Err(SegmentMapError::Layout(LayoutError::Parse(_)))
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_parses_mqtt_settings() {
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        assert!(config.mqtt_settings().is_err());
+
+        let config =
+            Config::try_parse_from(["afterglow", "--mqtt-broker-url", "broker.local"]).unwrap();
+        assert_eq!(
+            config.mqtt_settings(),
+            Ok(MqttSettings {
+                broker_url: "broker.local".to_string(),
+                port: 1883,
+                topic_prefix: "afterglow".to_string(),
+                qos: 0,
+                tls: None,
+            })
+        );
+
+        let mut config = Config::try_parse_from(["afterglow", "--mqtt-qos", "2"]).unwrap();
+        config.merge(
+            ConfigFile::from_toml(
+                "[mqtt]\nbroker_url = \"broker.local\"\ntopic_prefix = \"home/leds\"\nqos = 1\n\
+                 ca_cert = \"ca.pem\"\nclient_cert = \"client.pem\"\nclient_key = \"client.key\"\n",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            config.mqtt_settings(),
+            Ok(MqttSettings {
+                broker_url: "broker.local".to_string(),
+                port: 8883,
+                topic_prefix: "home/leds".to_string(),
+                qos: 2,
+                tls: Some(MqttTls {
+                    ca_cert: "ca.pem".into(),
+                    client_auth: Some(("client.pem".into(), "client.key".into())),
+                }),
+            })
+        );
+
+        assert!(Config::try_parse_from(["afterglow", "--mqtt-qos", "3"]).is_err());
+        let mut config = Config::try_parse_from(["afterglow"]).unwrap();
+        config.merge(ConfigFile::from_toml("[mqtt]\nbroker_url = \"b\"\nqos = 3\n").unwrap());
+        assert!(config.mqtt_settings().is_err());
+
+        let config = Config::try_parse_from([
+            "afterglow",
+            "--mqtt-broker-url",
+            "broker.local",
+            "--mqtt-client-cert",
+            "client.pem",
+            "--mqtt-client-key",
+            "client.key",
+        ])
+        .unwrap();
+        assert!(config.mqtt_settings().is_err());
+    }
+
+    #[test]
+    fn it_parses_log_settings() {
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        assert_eq!(config.log_level, LevelFilter::INFO);
+        assert_eq!(config.log_format, LogFormat::Pretty);
+
+        let config =
+            Config::try_parse_from(["afterglow", "--log-level", "debug", "--log-format", "json"])
+                .unwrap();
+        assert_eq!(config.log_level, LevelFilter::DEBUG);
+        assert_eq!(config.log_format, LogFormat::Json);
+
+        assert!(Config::try_parse_from(["afterglow", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn it_parses_spi_settings() {
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        assert_eq!(
+            config.spi_settings(),
+            SpiSettings {
+                bus: SpiBus::Spi0,
+                slave_select: SpiSlaveSelect::Ss0,
+                clock_speed: 16_000_000,
+                mode: SpiMode::Mode0,
+            }
+        );
+
+        let config = Config::try_parse_from([
+            "afterglow",
+            "--spi-bus",
+            "spi1",
+            "--spi-ss",
+            "2",
+            "--spi-hz",
+            "8000000",
+        ])
+        .unwrap();
+        assert_eq!(
+            config.spi_settings(),
+            SpiSettings {
+                bus: SpiBus::Spi1,
+                slave_select: SpiSlaveSelect::Ss2,
+                clock_speed: 8_000_000,
+                mode: SpiMode::Mode0,
+            }
+        );
+
+        assert!(Config::try_parse_from(["afterglow", "--spi-bus", "spi7"]).is_err());
+        assert!(Config::try_parse_from(["afterglow", "--spi-ss", "cs0"]).is_err());
+    }
+
+    #[test]
+    fn it_parses_white_balance_gains() {
+        assert_eq!(parse_white_balance("1,0.8,0.6"), Ok([1.0, 0.8, 0.6]));
+        assert!(parse_white_balance("1,0.8").is_err());
+        assert!(parse_white_balance("1,0.8,x").is_err());
+
+        let config = Config::try_parse_from(["afterglow"]).unwrap();
+        assert_eq!((config.brightness, config.white_balance), (31, [1.0; 3]));
+    }
+
+    #[test]
+    fn it_parses_i2c_addresses() {
+        assert_eq!(parse_i2c_address("0x40"), Ok(0x40));
+        assert_eq!(parse_i2c_address("64"), Ok(0x40));
+        assert_eq!(parse_i2c_address("0x77"), Ok(0x77));
+        assert!(parse_i2c_address("0x78").is_err());
+        assert!(parse_i2c_address("0x07").is_err());
+        assert!(parse_i2c_address("0xzz").is_err());
+    }
+
+    #[test]
+    fn it_parses_dmx_channels() {
+        assert_eq!(parse_dmx_channel("0:1"), Ok((0, 1)));
+        assert_eq!(parse_dmx_channel("35:510"), Ok((35, 510)));
+        assert!(parse_dmx_channel("0:0").is_err());
+        assert!(parse_dmx_channel("0:511").is_err());
+        assert!(parse_dmx_channel("0").is_err());
+        assert!(parse_dmx_channel("a:1").is_err());
+    }
+}
@@ -0,0 +1,226 @@
+use crate::color::{apply_brightness, mix};
+use crate::led::{AnyLedStrip, LEDStrip};
+use clap::ValueEnum;
+use std::f32::consts::TAU;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum EffectKind {
+    Rainbow,
+    Breath,
+    Chase,
+}
+
+pub struct RainbowEffect<const N: usize> {
+    degrees_per_frame: f32,
+    phase: f32,
+}
+
+impl<const N: usize> RainbowEffect<N> {
+    pub fn new(degrees_per_frame: f32) -> Self {
+        Self {
+            degrees_per_frame,
+            phase: 0.0,
+        }
+    }
+
+    pub fn tick(&mut self, strip: &mut LEDStrip<N>) {
+        for index in 0..N {
+            let hue = ((index * 360 / N) as f32 + self.phase) % 360.0;
+            strip.set_led_hsv(index, hue, 1.0, 1.0);
+        }
+
+        self.phase = (self.phase + self.degrees_per_frame).rem_euclid(360.0);
+    }
+}
+
+pub struct BreathEffect {
+    base_color: u32,
+    period_ms: u64,
+}
+
+impl BreathEffect {
+    pub fn new(base_color: u32, period_ms: u64) -> Self {
+        assert!(period_ms > 0, "period must be non-zero");
+
+        Self {
+            base_color,
+            period_ms,
+        }
+    }
+
+    pub fn brightness(&self, elapsed_ms: u64) -> f32 {
+        let t = (elapsed_ms % self.period_ms) as f32 / self.period_ms as f32;
+        0.5 * (1.0 - (TAU * t).cos())
+    }
+
+    pub fn tick(&mut self, elapsed_ms: u64, strip: &mut dyn AnyLedStrip) {
+        let color = apply_brightness(self.base_color, self.brightness(elapsed_ms));
+        for index in 0..strip.num_leds() {
+            strip.set_led(index, color);
+        }
+    }
+
+    pub fn modulate(&self, elapsed_ms: u64, strip: &mut dyn AnyLedStrip) {
+        let brightness = self.brightness(elapsed_ms);
+        for index in 0..strip.num_leds() {
+            let (r, g, b) = strip.get_led(index);
+            let color = u32::from_be_bytes([0, r, g, b]);
+            strip.set_led(index, apply_brightness(color, brightness));
+        }
+    }
+}
+
+pub struct ChaseEffect<const N: usize> {
+    pub highlight_width: usize,
+    pub blend_width: usize,
+    pub background_color: u32,
+    pub highlight_color: u32,
+    pub step_ms: u64,
+}
+
+impl<const N: usize> ChaseEffect<N> {
+    pub fn color(&self, index: usize, position: usize) -> u32 {
+        let offset = (index + N - position) % N;
+        if offset < self.highlight_width {
+            return self.highlight_color;
+        }
+
+        let distance = (offset + 1 - self.highlight_width).min(N - offset);
+        if distance > self.blend_width {
+            return self.background_color;
+        }
+
+        let amount = distance as f32 / (self.blend_width + 1) as f32;
+        mix(self.highlight_color, self.background_color, amount)
+    }
+
+    pub fn tick(&mut self, elapsed_ms: u64, strip: &mut LEDStrip<N>) {
+        let position = (elapsed_ms / self.step_ms.max(1)) as usize % N;
+        for index in 0..N {
+            strip.set_led(index, self.color(index, position));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::effects::{BreathEffect, ChaseEffect, RainbowEffect};
+    use crate::led::LEDStrip;
+
+    fn colors<const N: usize>(strip: &LEDStrip<N>) -> Vec<(u8, u8, u8)> {
+        (0..N).map(|index| strip.get_led(index)).collect()
+    }
+
+    #[test]
+    fn it_spreads_the_hue_wheel_across_the_strip() {
+        let mut strip: LEDStrip<3> = LEDStrip::new();
+        RainbowEffect::new(0.0).tick(&mut strip);
+        assert_eq!(colors(&strip), vec![(255, 0, 0), (0, 255, 0), (0, 0, 255)]);
+    }
+
+    #[test]
+    fn it_advances_the_phase_each_tick() {
+        let mut strip: LEDStrip<3> = LEDStrip::new();
+        let mut effect = RainbowEffect::new(120.0);
+
+        effect.tick(&mut strip);
+        assert_eq!(colors(&strip), vec![(255, 0, 0), (0, 255, 0), (0, 0, 255)]);
+
+        effect.tick(&mut strip);
+        assert_eq!(colors(&strip), vec![(0, 255, 0), (0, 0, 255), (255, 0, 0)]);
+
+        effect.tick(&mut strip);
+        effect.tick(&mut strip);
+        assert_eq!(colors(&strip), vec![(255, 0, 0), (0, 255, 0), (0, 0, 255)]);
+    }
+
+    #[test]
+    fn it_runs_backwards_with_a_negative_speed() {
+        let mut strip: LEDStrip<3> = LEDStrip::new();
+        let mut effect = RainbowEffect::new(-120.0);
+
+        effect.tick(&mut strip);
+        effect.tick(&mut strip);
+        assert_eq!(colors(&strip), vec![(0, 0, 255), (255, 0, 0), (0, 255, 0)]);
+    }
+
+    #[test]
+    fn it_follows_a_sinusoidal_breath_envelope() {
+        let effect = BreathEffect::new(0xffffff, 2000);
+        assert_eq!(effect.brightness(0), 0.0);
+        assert!((effect.brightness(500) - 0.5).abs() < 1e-6);
+        assert_eq!(effect.brightness(1000), 1.0);
+        assert!((effect.brightness(1500) - 0.5).abs() < 1e-6);
+        assert_eq!(effect.brightness(2000), 0.0);
+        assert_eq!(effect.brightness(3000), 1.0);
+    }
+
+    #[test]
+    fn it_breathes_the_base_color() {
+        let mut strip: LEDStrip<2> = LEDStrip::new();
+        let mut effect = BreathEffect::new(0x4b8040, 2000);
+
+        effect.tick(1000, &mut strip);
+        assert_eq!(colors(&strip), vec![(75, 128, 64), (75, 128, 64)]);
+
+        effect.tick(0, &mut strip);
+        assert_eq!(colors(&strip), vec![(0, 0, 0), (0, 0, 0)]);
+    }
+
+    #[test]
+    fn it_modulates_existing_colors() {
+        let mut strip = LEDStrip::new_with_data([0x4b8040, 0xffffff]);
+        let effect = BreathEffect::new(0x000000, 2000);
+
+        effect.modulate(500, &mut strip);
+        assert_eq!(colors(&strip), vec![(38, 64, 32), (128, 128, 128)]);
+    }
+
+    fn chase() -> ChaseEffect<8> {
+        ChaseEffect {
+            highlight_width: 2,
+            blend_width: 1,
+            background_color: 0x000000,
+            highlight_color: 0xc0c0c0,
+            step_ms: 100,
+        }
+    }
+
+    #[test]
+    fn it_blends_the_chase_highlight_into_the_background() {
+        let mut strip: LEDStrip<8> = LEDStrip::new();
+        chase().tick(0, &mut strip);
+        assert_eq!(
+            colors(&strip),
+            vec![
+                (192, 192, 192),
+                (192, 192, 192),
+                (96, 96, 96),
+                (0, 0, 0),
+                (0, 0, 0),
+                (0, 0, 0),
+                (0, 0, 0),
+                (96, 96, 96),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_advances_the_chase_one_led_per_step() {
+        let mut strip: LEDStrip<8> = LEDStrip::new();
+        let mut effect = chase();
+        let highlighted = |strip: &LEDStrip<8>| -> Vec<usize> {
+            (0..8)
+                .filter(|&index| strip.get_led(index) == (192, 192, 192))
+                .collect()
+        };
+
+        effect.tick(350, &mut strip);
+        assert_eq!(highlighted(&strip), vec![3, 4]);
+
+        effect.tick(700, &mut strip);
+        assert_eq!(highlighted(&strip), vec![0, 7]);
+        assert_eq!(strip.get_led(1), (96, 96, 96));
+        assert_eq!(strip.get_led(6), (96, 96, 96));
+    }
+}
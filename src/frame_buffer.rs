@@ -0,0 +1,86 @@
+use nokhwa::pixel_format::RgbFormat;
+use nokhwa::{Buffer, NokhwaError};
+
+// Owns the decoded RGB frame between captures so steady-state decoding
+// only reallocates when the camera resolution grows
+#[derive(Default)]
+pub struct ReusableFrameBuffer {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    #[cfg(debug_assertions)]
+    allocations: usize,
+}
+
+impl ReusableFrameBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decode_into(&mut self, frame: &Buffer) -> Result<(), NokhwaError> {
+        let resolution = frame.resolution();
+        let len = resolution.width() as usize * resolution.height() as usize * 3;
+        #[cfg(debug_assertions)]
+        if len > self.data.capacity() {
+            self.allocations += 1;
+        }
+        self.data.resize(len, 0);
+        self.width = resolution.width();
+        self.height = resolution.height();
+
+        frame.decode_image_to_buffer::<RgbFormat>(&mut self.data)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn allocations(&self) -> usize {
+        self.allocations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame_buffer::ReusableFrameBuffer;
+    use nokhwa::utils::{FrameFormat, Resolution};
+    use nokhwa::Buffer;
+
+    fn frame(width: u32, height: u32, value: u8) -> Buffer {
+        Buffer::new(
+            Resolution::new(width, height),
+            &vec![value; (width * height * 3) as usize],
+            FrameFormat::RAWRGB,
+        )
+    }
+
+    #[test]
+    fn it_reuses_the_allocation_across_frames() {
+        let mut buffer = ReusableFrameBuffer::new();
+        assert_eq!(buffer.allocations(), 0);
+
+        for value in 0..10 {
+            buffer.decode_into(&frame(4, 2, value)).unwrap();
+            assert_eq!(buffer.data(), &[value; 24]);
+        }
+        assert_eq!((buffer.width(), buffer.height()), (4, 2));
+        assert_eq!(buffer.allocations(), 1);
+
+        buffer.decode_into(&frame(2, 2, 0x40)).unwrap();
+        assert_eq!(buffer.data(), &[0x40; 12]);
+        assert_eq!(buffer.allocations(), 1);
+
+        buffer.decode_into(&frame(8, 4, 0x80)).unwrap();
+        assert_eq!((buffer.width(), buffer.height()), (8, 4));
+        assert_eq!(buffer.allocations(), 2);
+    }
+}
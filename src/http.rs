@@ -0,0 +1,168 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+use tracing::warn;
+
+// Requests are served one at a time, so a client that stalls is dropped
+// after this long instead of holding up everyone else
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_BODY_LENGTH: usize = 64 * 1024;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> io::Result<Option<Request>> {
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+        header.clear();
+    }
+    if content_length > MAX_BODY_LENGTH {
+        return Ok(None);
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    let mut parts = request_line.split_whitespace();
+    Ok(Some(Request {
+        method: parts.next().unwrap_or_default().to_string(),
+        path: parts.next().unwrap_or_default().to_string(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    }))
+}
+
+fn respond<F>(
+    stream: TcpStream,
+    timeout: Duration,
+    content_type: &str,
+    handler: &F,
+) -> io::Result<()>
+where
+    F: Fn(&Request) -> (&'static str, String),
+{
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut reader = BufReader::new(stream);
+    let (status, body) = match read_request(&mut reader)? {
+        Some(request) => handler(&request),
+        None => ("413 Payload Too Large", String::new()),
+    };
+    write!(
+        reader.get_mut(),
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+pub fn serve<F>(
+    addr: impl ToSocketAddrs,
+    name: &'static str,
+    content_type: &'static str,
+    handler: F,
+) -> io::Result<SocketAddr>
+where
+    F: Fn(&Request) -> (&'static str, String) + Send + 'static,
+{
+    serve_with_timeout(addr, CLIENT_TIMEOUT, name, content_type, handler)
+}
+
+fn serve_with_timeout<F>(
+    addr: impl ToSocketAddrs,
+    timeout: Duration,
+    name: &'static str,
+    content_type: &'static str,
+    handler: F,
+) -> io::Result<SocketAddr>
+where
+    F: Fn(&Request) -> (&'static str, String) + Send + 'static,
+{
+    let listener = TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| respond(stream, timeout, content_type, &handler));
+            if let Err(err) = result {
+                warn!("Failed to serve {} request: {}", name, err);
+            }
+        }
+    });
+
+    Ok(local_addr)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{serve_with_timeout, MAX_BODY_LENGTH};
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        time::Duration,
+    };
+
+    fn request(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn it_drops_stalled_clients_and_oversized_bodies() {
+        let addr = serve_with_timeout(
+            "127.0.0.1:0",
+            Duration::from_millis(100),
+            "test",
+            "text/plain",
+            |request| {
+                (
+                    "200 OK",
+                    format!("{} {} {}", request.method, request.path, request.body),
+                )
+            },
+        )
+        .unwrap();
+
+        let _stalled = TcpStream::connect(addr).unwrap();
+        assert_eq!(
+            request(
+                addr,
+                "POST /echo HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello"
+            ),
+            concat!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 16\r\n",
+                "Connection: close\r\n\r\nPOST /echo hello"
+            )
+        );
+
+        let response = request(
+            addr,
+            &format!(
+                "POST /echo HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+                MAX_BODY_LENGTH + 1
+            ),
+        );
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+}
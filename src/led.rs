@@ -1,4 +1,39 @@
+use crate::color::{
+    apply_brightness, apply_color_matrix, apply_desaturate, apply_gamma, apply_grayscale,
+    apply_hue_rotation, apply_inversion, clamp_u8, enhance_hue, hsv_to_rgb, rgb_to_rgbw,
+    ColorMatrix, HueEnhancement,
+};
+use clap::ValueEnum;
 use lazycell::LazyCell;
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{cell::RefCell, error::Error, fmt, ops::Range};
+use tracing::instrument;
+
+const MAX_GLOBAL_BRIGHTNESS: u8 = 0b11111;
+// SK6812 data is clocked out at 2.4 MHz with each bit stretched over three SPI
+// bits, so 32 low bytes hold the line low past its 80 us reset time
+const SK6812_RESET_BYTES: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ChipProfile {
+    #[default]
+    Apa102,
+    Sk9822,
+    Lpd8806,
+    #[value(name = "sk6812-rgbw")]
+    Sk6812Rgbw,
+}
+
+fn sk9822_gain(brightness: f32) -> (u8, f32) {
+    let requested = brightness * f32::from(MAX_GLOBAL_BRIGHTNESS);
+    let gain = requested.ceil() as u8;
+    if gain == 0 {
+        (0, 0.0)
+    } else {
+        (gain, requested / f32::from(gain))
+    }
+}
 
 #[derive(PartialEq)]
 #[cfg_attr(test, derive(Debug))]
@@ -17,17 +52,243 @@ impl APA102DataFrame {
 
     fn led_frame(data: u32) -> Self {
         let [_, r, g, b] = data.to_be_bytes();
+        APA102DataFrame::led_frame_rgb(r, g, b)
+    }
+
+    fn led_frame_rgb(r: u8, g: u8, b: u8) -> Self {
         APA102DataFrame(r, g, b)
     }
 
-    fn get_spi_data(&self) -> [u8; 4] {
+    fn scaled(&self, scale: f32, dither_error: Option<&mut [f32; 3]>) -> (u8, u8, u8) {
+        let APA102DataFrame(r, g, b) = self;
+        let mut channels = [*r, *g, *b].map(|channel| f32::from(channel) * scale);
+
+        if let Some(dither_error) = dither_error {
+            for (channel, error) in channels.iter_mut().zip(dither_error.iter_mut()) {
+                let target = *channel + *error;
+                *channel = target.round().clamp(0.0, 255.0);
+                *error = target - *channel;
+            }
+        }
+
+        let [r, g, b] = channels.map(clamp_u8);
+        (r, g, b)
+    }
+
+    fn get_spi_data(&self, global: u8, scale: f32, dither_error: Option<&mut [f32; 3]>) -> [u8; 4] {
+        let (r, g, b) = self.scaled(scale, dither_error);
+        [0b11100000 | global, b, g, r]
+    }
+
+    fn get_unscaled_spi_data(&self, global: u8) -> [u8; 4] {
+        let &APA102DataFrame(r, g, b) = self;
+        [0b11100000 | global, b, g, r]
+    }
+
+    fn get_lpd8806_spi_data(&self, scale: f32, dither_error: Option<&mut [f32; 3]>) -> [u8; 3] {
+        let (r, g, b) = self.scaled(scale, dither_error);
+        [0x80 | (g >> 1), 0x80 | (r >> 1), 0x80 | (b >> 1)]
+    }
+
+    fn get_sk6812_spi_data(
+        &self,
+        scale: f32,
+        dither_error: Option<&mut [f32; 3]>,
+        white_extraction: f32,
+    ) -> [u8; 12] {
+        let (r, g, b, w) = rgb_to_rgbw(self.scaled(scale, dither_error), white_extraction);
+
+        let mut spi_data = [0; 12];
+        for (encoded, channel) in spi_data.chunks_exact_mut(3).zip([g, r, b, w]) {
+            encoded.copy_from_slice(&encode_sk6812_byte(channel));
+        }
+        spi_data
+    }
+
+    fn color(&self) -> u32 {
         let APA102DataFrame(r, g, b) = self;
-        [0xff, *b, *g, *r]
+        u32::from_be_bytes([0, *r, *g, *b])
+    }
+}
+
+// Sends 0b110 for a 1 bit and 0b100 for a 0 bit
+fn encode_sk6812_byte(byte: u8) -> [u8; 3] {
+    let bits = (0..8).fold(0u32, |bits, bit| {
+        let high = if byte & (0x80 >> bit) != 0 {
+            0b110
+        } else {
+            0b100
+        };
+        (bits << 3) | high
+    });
+    let [_, encoded @ ..] = bits.to_be_bytes();
+    encoded
+}
+
+fn decode_sk6812_byte(encoded: &[u8]) -> u8 {
+    let bits = u32::from_be_bytes([0, encoded[0], encoded[1], encoded[2]]);
+    (0..8).fold(0, |byte, bit| {
+        (byte << 1) | ((bits >> (22 - bit * 3)) & 1) as u8
+    })
+}
+
+pub fn decode_spi_data(spi_data: &[u8]) -> Vec<(u8, u8, u8)> {
+    if is_sk6812_spi_data(spi_data) {
+        return spi_data
+            .chunks_exact(12)
+            .take_while(|frame| frame[0] != 0x00)
+            .map(|frame| {
+                let [g, r, b, w] = [0, 3, 6, 9].map(|start| decode_sk6812_byte(&frame[start..]));
+                (
+                    r.saturating_add(w),
+                    g.saturating_add(w),
+                    b.saturating_add(w),
+                )
+            })
+            .collect();
+    }
+
+    if is_lpd8806_spi_data(spi_data) {
+        return spi_data
+            .chunks_exact(3)
+            .take_while(|frame| frame.iter().all(|byte| byte & 0x80 != 0))
+            .map(|frame| (frame[1] << 1, frame[0] << 1, frame[2] << 1))
+            .collect();
+    }
+
+    let frames = spi_data.get(4..).unwrap_or_default();
+    let num_leds = frames.len() / 4 * 2 / 3;
+
+    frames
+        .chunks_exact(4)
+        .take(num_leds)
+        .map(|frame| (frame[3], frame[2], frame[1]))
+        .collect()
+}
+
+fn is_lpd8806_spi_data(spi_data: &[u8]) -> bool {
+    spi_data.len() >= 3 && spi_data[..3].iter().all(|byte| byte & 0x80 != 0)
+}
+
+// Encoded SK6812 data always starts 0b1x01x01x 0b01x01x01, which neither an
+// APA102 start frame nor an LPD8806 data frame can
+fn is_sk6812_spi_data(spi_data: &[u8]) -> bool {
+    spi_data.len() >= 2 && spi_data[0] & 0x80 != 0 && spi_data[1] & 0x80 == 0
+}
+
+pub fn slice_spi_data(spi_data: &[u8], leds: Range<usize>) -> Vec<u8> {
+    let num_leds = leds.len();
+
+    if is_sk6812_spi_data(spi_data) {
+        let mut sliced = spi_data[leds.start * 12..leds.end * 12].to_vec();
+        sliced.resize(num_leds * 12 + SK6812_RESET_BYTES, 0x00);
+        return sliced;
+    }
+
+    if is_lpd8806_spi_data(spi_data) {
+        let mut sliced = spi_data[leds.start * 3..leds.end * 3].to_vec();
+        sliced.resize(num_leds * 3 + num_leds.div_ceil(32), 0x00);
+        return sliced;
+    }
+
+    let mut sliced = Vec::with_capacity((num_leds + num_leds.div_ceil(2) + 1) * 4);
+    sliced.extend(APA102DataFrame::start_frame_spi_data());
+    sliced.extend(&spi_data[4 + leds.start * 4..4 + leds.end * 4]);
+    for _ in 0..num_leds.div_ceil(2) {
+        sliced.extend(APA102DataFrame::end_frame_spi_data());
+    }
+    sliced
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct LengthError {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} LED colors, got {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for LengthError {}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} bytes of LED state, got {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    OffsetOutOfBounds(usize),
+    BrightnessOutOfRange(u8),
+    InvalidGamma(f32),
+    NegativeGain(f32),
+    WhiteExtractionOutOfRange(f32),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::OffsetOutOfBounds(offset) => write!(f, "offset {} out of bounds", offset),
+            ConfigError::BrightnessOutOfRange(brightness) => {
+                write!(
+                    f,
+                    "brightness {} out of range (0 - {})",
+                    brightness, MAX_GLOBAL_BRIGHTNESS
+                )
+            }
+            ConfigError::InvalidGamma(gamma) => write!(f, "gamma {} must be positive", gamma),
+            ConfigError::NegativeGain(gain) => {
+                write!(f, "white balance gain {} must not be negative", gain)
+            }
+            ConfigError::WhiteExtractionOutOfRange(extraction) => {
+                write!(
+                    f,
+                    "white extraction {} out of range (0.0 - 1.0)",
+                    extraction
+                )
+            }
+        }
     }
 }
 
+impl Error for ConfigError {}
+
+pub trait AnyLedStrip {
+    fn num_leds(&self) -> usize;
+    fn get_led(&self, index: usize) -> (u8, u8, u8);
+    fn set_led(&mut self, index: usize, color: u32);
+}
+
 pub struct LEDStrip<const N: usize> {
     data: [APA102DataFrame; N],
+    offset: usize,
+    reversed: bool,
+    chip: ChipProfile,
+    brightness: f32,
+    gamma: f32,
+    white_balance: [f32; 3],
+    white_extraction: f32,
+    dither_errors: Option<RefCell<[[f32; 3]; N]>>,
     spi_data: LazyCell<Vec<u8>>,
 }
 
@@ -41,30 +302,75 @@ impl<const N: usize> LEDStrip<N> {
 
         Self {
             data: data.map(APA102DataFrame::led_frame),
+            offset: 0,
+            reversed: false,
+            chip: ChipProfile::default(),
+            brightness: 1.0,
+            gamma: 1.0,
+            white_balance: [1.0; 3],
+            white_extraction: 1.0,
+            dither_errors: None,
             spi_data: LazyCell::new(),
         }
     }
 
-    pub fn get_spi_data(&self) -> &Vec<u8> {
-        if !self.spi_data.filled() {
-            let num_end_frames = (N + 1) / 2;
-            let mut spi_data = Vec::with_capacity(N + num_end_frames + 1);
-            spi_data.extend(APA102DataFrame::start_frame_spi_data());
+    pub fn try_from_slice(data: &[u32]) -> Result<Self, LengthError> {
+        let data: [u32; N] = data.try_into().map_err(|_| LengthError {
+            expected: N,
+            actual: data.len(),
+        })?;
+        Ok(LEDStrip::new_with_data(data))
+    }
 
-            for frame in self.data.iter() {
-                spi_data.extend(frame.get_spi_data());
-            }
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        if data.len() != N * 3 {
+            return Err(ParseError {
+                expected: N * 3,
+                actual: data.len(),
+            });
+        }
 
-            for _ in 0..num_end_frames {
-                spi_data.extend(APA102DataFrame::end_frame_spi_data());
-            }
+        let mut led_strip = LEDStrip::new();
+        for (index, color) in data.chunks_exact(3).enumerate() {
+            led_strip.data[index] = APA102DataFrame::led_frame_rgb(color[0], color[1], color[2]);
+        }
+        Ok(led_strip)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn snapshot(&self) -> LEDStripSnapshot<'_, N> {
+        LEDStripSnapshot(self)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|&APA102DataFrame(r, g, b)| [r, g, b])
+            .collect()
+    }
 
-            self.spi_data.fill(spi_data).ok();
+    #[instrument(level = "trace", skip_all)]
+    pub fn get_spi_data(&self) -> &Vec<u8> {
+        if !self.spi_data.filled() {
+            let mut dither_errors = self.dither_errors.as_ref().map(RefCell::borrow_mut);
+            self.spi_data
+                .fill(self.build_spi_data(self.brightness, dither_errors.as_deref_mut()))
+                .ok();
         }
 
         self.spi_data.borrow().unwrap()
     }
 
+    // Dithers from a copy of the error state so that the next get_spi_data
+    // frame comes out the same as if this had never been called
+    pub fn get_spi_data_with_brightness(&self, brightness: f32) -> Vec<u8> {
+        let mut dither_errors = self.dither_errors.as_ref().map(|errors| *errors.borrow());
+        self.build_spi_data(
+            self.brightness * brightness.clamp(0.0, 1.0),
+            dither_errors.as_mut(),
+        )
+    }
+
     pub fn get_led(&self, index: usize) -> (u8, u8, u8) {
         assert!(index < N, "index out of bounds");
         let APA102DataFrame(r, g, b) = self.data[index];
@@ -75,15 +381,407 @@ impl<const N: usize> LEDStrip<N> {
         assert!(index < N, "index out of bounds");
 
         self.data[index] = APA102DataFrame::led_frame(color);
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_led_rgb(&mut self, index: usize, r: u8, g: u8, b: u8) {
+        assert!(index < N, "index out of bounds");
+
+        self.data[index] = APA102DataFrame::led_frame_rgb(r, g, b);
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_led_hsv(&mut self, index: usize, hue: f32, saturation: f32, value: f32) {
+        self.set_led(index, hsv_to_rgb(hue, saturation, value));
+    }
+
+    pub fn apply_hue_rotation_all(&mut self, degrees: f32) {
+        self.map_colors(|color| apply_hue_rotation(color, degrees));
+    }
+
+    pub fn enhance_hue_all(&mut self, params: &HueEnhancement) {
+        self.map_colors(|color| enhance_hue(color, params));
+    }
+
+    pub fn apply_color_matrix_all(&mut self, matrix: &ColorMatrix) {
+        self.map_colors(|color| apply_color_matrix(color, matrix));
+    }
+
+    pub fn apply(&mut self, f: impl Fn((u8, u8, u8)) -> (u8, u8, u8)) {
+        for frame in self.data.iter_mut() {
+            let (r, g, b) = f((frame.0, frame.1, frame.2));
+            *frame = APA102DataFrame::led_frame_rgb(r, g, b);
+        }
+        self.invalidate_spi_data();
+    }
+
+    pub fn invert_all(&mut self) {
+        self.map_colors(apply_inversion);
+    }
+
+    pub fn grayscale_all(&mut self) {
+        self.map_colors(apply_grayscale);
+    }
+
+    pub fn desaturate_all(&mut self, amount: f32) {
+        self.map_colors(|color| apply_desaturate(color, amount));
+    }
+
+    pub fn scale_brightness(&mut self, factor: f32) {
+        self.map_colors(|color| apply_brightness(color, factor));
+    }
+
+    pub fn set_led_offset(&mut self, offset: usize) {
+        assert!(offset < N, "offset out of bounds");
+
+        self.offset = offset;
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_reversed(&mut self, reversed: bool) {
+        self.reversed = reversed;
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_chip_profile(&mut self, chip: ChipProfile) {
+        self.chip = chip;
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness.clamp(0.0, 1.0);
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_dithering(&mut self, enabled: bool) {
+        self.dither_errors = enabled.then(|| RefCell::new([[0.0; 3]; N]));
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = gamma;
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_white_balance(&mut self, r: f32, g: f32, b: f32) {
+        self.white_balance = [r, g, b];
+        self.invalidate_spi_data();
+    }
+
+    pub fn set_white_extraction(&mut self, extraction: f32) {
+        self.white_extraction = extraction.clamp(0.0, 1.0);
+        self.invalidate_spi_data();
+    }
+
+    fn build_spi_data(
+        &self,
+        brightness: f32,
+        dither_errors: Option<&mut [[f32; 3]; N]>,
+    ) -> Vec<u8> {
+        match self.chip {
+            ChipProfile::Apa102 => {
+                self.build_apa102_spi_data(MAX_GLOBAL_BRIGHTNESS, brightness, dither_errors)
+            }
+            ChipProfile::Sk9822 => {
+                let (gain, scale) = sk9822_gain(brightness);
+                self.build_apa102_spi_data(gain, scale, dither_errors)
+            }
+            ChipProfile::Lpd8806 => self.build_lpd8806_spi_data(brightness, dither_errors),
+            ChipProfile::Sk6812Rgbw => self.build_sk6812_spi_data(brightness, dither_errors),
+        }
+    }
+
+    fn build_apa102_spi_data(
+        &self,
+        global: u8,
+        scale: f32,
+        mut dither_errors: Option<&mut [[f32; 3]; N]>,
+    ) -> Vec<u8> {
+        let num_end_frames = N.div_ceil(2);
+        let mut spi_data = Vec::with_capacity((N + num_end_frames + 1) * 4);
+        spi_data.extend(APA102DataFrame::start_frame_spi_data());
+
+        if dither_errors.is_none() && self.is_uncorrected(scale) {
+            for position in 0..N {
+                let index = self.logical_index(position);
+                spi_data.extend(self.data[index].get_unscaled_spi_data(global));
+            }
+        } else {
+            for position in 0..N {
+                let index = self.logical_index(position);
+                let dither_error = dither_errors.as_mut().map(|errors| &mut errors[index]);
+                spi_data.extend(
+                    self.corrected(index)
+                        .get_spi_data(global, scale, dither_error),
+                );
+            }
+        }
+
+        for _ in 0..num_end_frames {
+            spi_data.extend(APA102DataFrame::end_frame_spi_data());
+        }
+
+        spi_data
+    }
+
+    fn build_lpd8806_spi_data(
+        &self,
+        brightness: f32,
+        mut dither_errors: Option<&mut [[f32; 3]; N]>,
+    ) -> Vec<u8> {
+        let num_latch_bytes = N.div_ceil(32);
+        let mut spi_data = Vec::with_capacity(N * 3 + num_latch_bytes);
+
+        for position in 0..N {
+            let index = self.logical_index(position);
+            let dither_error = dither_errors.as_mut().map(|errors| &mut errors[index]);
+            spi_data.extend(
+                self.corrected(index)
+                    .get_lpd8806_spi_data(brightness, dither_error),
+            );
+        }
+        spi_data.resize(N * 3 + num_latch_bytes, 0x00);
+
+        spi_data
+    }
+
+    fn build_sk6812_spi_data(
+        &self,
+        brightness: f32,
+        mut dither_errors: Option<&mut [[f32; 3]; N]>,
+    ) -> Vec<u8> {
+        let mut spi_data = Vec::with_capacity(N * 12 + SK6812_RESET_BYTES);
+
+        for position in 0..N {
+            let index = self.logical_index(position);
+            let dither_error = dither_errors.as_mut().map(|errors| &mut errors[index]);
+            spi_data.extend(self.corrected(index).get_sk6812_spi_data(
+                brightness,
+                dither_error,
+                self.white_extraction,
+            ));
+        }
+        spi_data.resize(N * 12 + SK6812_RESET_BYTES, 0x00);
+
+        spi_data
+    }
+
+    // Skipping the per-channel float math when it cannot change anything keeps
+    // full-brightness frames cheap to build on slower boards
+    fn is_uncorrected(&self, scale: f32) -> bool {
+        scale == 1.0 && self.gamma == 1.0 && self.white_balance == [1.0; 3]
+    }
+
+    fn corrected(&self, index: usize) -> APA102DataFrame {
+        let APA102DataFrame(r, g, b) = self.data[index];
+        if self.gamma == 1.0 && self.white_balance == [1.0; 3] {
+            return APA102DataFrame(r, g, b);
+        }
+
+        let color = apply_gamma(self.data[index].color(), self.gamma, self.white_balance);
+        let [_, r, g, b] = color.to_be_bytes();
+        APA102DataFrame(r, g, b)
+    }
+
+    fn logical_index(&self, position: usize) -> usize {
+        if self.reversed {
+            (self.offset + N - position) % N
+        } else {
+            (position + N - self.offset) % N
+        }
+    }
+
+    fn map_colors(&mut self, f: impl Fn(u32) -> u32) {
+        self.apply(|(r, g, b)| {
+            let [_, r, g, b] = f(u32::from_be_bytes([0, r, g, b])).to_be_bytes();
+            (r, g, b)
+        });
+    }
+
+    fn invalidate_spi_data(&mut self) {
         if self.spi_data.filled() {
             self.spi_data = LazyCell::new();
         }
     }
 }
 
+impl<const N: usize> Default for LEDStrip<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct LEDStripBuilder<const N: usize> {
+    offset: usize,
+    reversed: bool,
+    chip: ChipProfile,
+    brightness: u8,
+    dithering: bool,
+    gamma: f32,
+    white_balance: [f32; 3],
+    white_extraction: f32,
+}
+
+impl<const N: usize> Default for LEDStripBuilder<N> {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            reversed: false,
+            chip: ChipProfile::default(),
+            brightness: MAX_GLOBAL_BRIGHTNESS,
+            dithering: false,
+            gamma: 1.0,
+            white_balance: [1.0; 3],
+            white_extraction: 1.0,
+        }
+    }
+}
+
+impl<const N: usize> LEDStripBuilder<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn reversed(mut self, reversed: bool) -> Self {
+        self.reversed = reversed;
+        self
+    }
+
+    pub fn chip(mut self, chip: ChipProfile) -> Self {
+        self.chip = chip;
+        self
+    }
+
+    pub fn brightness(mut self, brightness: u8) -> Self {
+        self.brightness = brightness;
+        self
+    }
+
+    pub fn dithering(mut self, enabled: bool) -> Self {
+        self.dithering = enabled;
+        self
+    }
+
+    pub fn gamma(mut self, gamma: f32) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    pub fn white_balance(mut self, r: f32, g: f32, b: f32) -> Self {
+        self.white_balance = [r, g, b];
+        self
+    }
+
+    pub fn white_extraction(mut self, extraction: f32) -> Self {
+        self.white_extraction = extraction;
+        self
+    }
+
+    pub fn build(&self) -> Result<LEDStrip<N>, ConfigError> {
+        let mut led_strip = LEDStrip::new();
+        self.configure(&mut led_strip)?;
+        Ok(led_strip)
+    }
+
+    pub fn configure(&self, led_strip: &mut LEDStrip<N>) -> Result<(), ConfigError> {
+        if self.offset >= N {
+            return Err(ConfigError::OffsetOutOfBounds(self.offset));
+        }
+        if self.brightness > MAX_GLOBAL_BRIGHTNESS {
+            return Err(ConfigError::BrightnessOutOfRange(self.brightness));
+        }
+        if self.gamma <= 0.0 || !self.gamma.is_finite() {
+            return Err(ConfigError::InvalidGamma(self.gamma));
+        }
+        if let Some(&gain) = self
+            .white_balance
+            .iter()
+            .find(|gain| gain.is_nan() || **gain < 0.0)
+        {
+            return Err(ConfigError::NegativeGain(gain));
+        }
+        if !(0.0..=1.0).contains(&self.white_extraction) {
+            return Err(ConfigError::WhiteExtractionOutOfRange(
+                self.white_extraction,
+            ));
+        }
+
+        led_strip.set_led_offset(self.offset);
+        led_strip.set_reversed(self.reversed);
+        led_strip.set_chip_profile(self.chip);
+        led_strip.set_brightness(f32::from(self.brightness) / f32::from(MAX_GLOBAL_BRIGHTNESS));
+        led_strip.set_dithering(self.dithering);
+        led_strip.set_gamma(self.gamma);
+        let [r, g, b] = self.white_balance;
+        led_strip.set_white_balance(r, g, b);
+        led_strip.set_white_extraction(self.white_extraction);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+pub struct LEDStripSnapshot<'a, const N: usize>(&'a LEDStrip<N>);
+
+#[cfg(feature = "serde")]
+impl<const N: usize> Serialize for LEDStripSnapshot<'_, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.0
+                .data
+                .iter()
+                .map(|frame| format!("#{:06x}", frame.color())),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq, Eq)]
+pub struct LedColors(pub Vec<u32>);
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for LedColors {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|color| {
+                color
+                    .strip_prefix('#')
+                    .ok_or_else(|| format!("expected a #RRGGBB color, got: {}", color))
+                    .and_then(crate::color::parse_hex_color)
+            })
+            .collect::<Result<_, _>>()
+            .map(LedColors)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl<const N: usize> AnyLedStrip for LEDStrip<N> {
+    fn num_leds(&self) -> usize {
+        N
+    }
+
+    fn get_led(&self, index: usize) -> (u8, u8, u8) {
+        LEDStrip::get_led(self, index)
+    }
+
+    fn set_led(&mut self, index: usize, color: u32) {
+        LEDStrip::set_led(self, index, color);
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::led::{APA102DataFrame, LEDStrip};
+    #[cfg(feature = "serde")]
+    use crate::led::LedColors;
+    use crate::led::{
+        decode_spi_data, slice_spi_data, APA102DataFrame, ChipProfile, ConfigError, LEDStrip,
+        LEDStripBuilder, LengthError, ParseError, MAX_GLOBAL_BRIGHTNESS,
+    };
 
     #[test]
     fn it_builds_grayscale_frames() {
@@ -115,6 +813,73 @@ mod tests {
         let _led_strip = LEDStrip::<0>::new();
     }
 
+    #[test]
+    fn it_builds_an_led_strip_from_a_slice() {
+        let colors = vec![0xff0000, 0x00ff00, 0x4b8040];
+        let led_strip = LEDStrip::<3>::try_from_slice(&colors).unwrap();
+        assert_eq!(
+            led_strip.data,
+            [
+                APA102DataFrame(255, 0, 0),
+                APA102DataFrame(0, 255, 0),
+                APA102DataFrame(75, 128, 64),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_a_slice_of_the_wrong_length() {
+        let colors = vec![0xff0000, 0x00ff00];
+        assert_eq!(
+            LEDStrip::<3>::try_from_slice(&colors).err(),
+            Some(LengthError {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            LEDStrip::<1>::try_from_slice(&colors)
+                .err()
+                .unwrap()
+                .to_string(),
+            "expected 1 LED colors, got 2"
+        );
+    }
+
+    #[test]
+    fn it_round_trips_through_bytes() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x4b8040]);
+        assert_eq!(
+            led_strip.to_bytes(),
+            vec![0xff, 0x00, 0x00, 0x00, 0xff, 0x00, 0x4b, 0x80, 0x40]
+        );
+
+        let mut restored = LEDStrip::<3>::from_bytes(&led_strip.to_bytes()).unwrap();
+        assert_eq!(restored.get_spi_data(), led_strip.get_spi_data());
+
+        for strip in [&mut led_strip, &mut restored] {
+            strip.set_led_offset(1);
+            strip.set_reversed(true);
+            strip.set_chip_profile(ChipProfile::Lpd8806);
+        }
+        assert_eq!(restored.get_spi_data(), led_strip.get_spi_data());
+    }
+
+    #[test]
+    fn it_rejects_state_of_the_wrong_length() {
+        assert_eq!(
+            LEDStrip::<3>::from_bytes(&[0xff; 8]).err(),
+            Some(ParseError {
+                expected: 9,
+                actual: 8
+            })
+        );
+        assert_eq!(
+            LEDStrip::<1>::from_bytes(&[]).err().unwrap().to_string(),
+            "expected 3 bytes of LED state, got 0"
+        );
+    }
+
     #[test]
     fn it_makes_frames_for_a_single_led_strip() {
         let led_strip = LEDStrip::new_with_data([0x4b8040]);
@@ -211,4 +976,554 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn it_sets_an_led_from_separate_channels() {
+        let mut led_strip = LEDStrip::new_with_data([0x000000, 0x000000]);
+        led_strip.get_spi_data();
+
+        led_strip.set_led_rgb(1, 75, 128, 64);
+
+        let expected = LEDStrip::new_with_data([0x000000, 0x4b8040]);
+        assert_eq!(led_strip.data, expected.data);
+        assert_eq!(led_strip.get_spi_data(), expected.get_spi_data());
+        assert_eq!(
+            APA102DataFrame::led_frame_rgb(75, 128, 64),
+            APA102DataFrame::led_frame(0x4b8040)
+        );
+    }
+
+    #[test]
+    fn it_rotates_the_hue_of_all_leds() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x808080]);
+        led_strip.get_spi_data();
+
+        led_strip.apply_hue_rotation_all(120.0);
+
+        assert_eq!(
+            led_strip.data,
+            [
+                APA102DataFrame(0, 255, 0),
+                APA102DataFrame(0, 0, 255),
+                APA102DataFrame(255, 0, 0),
+                APA102DataFrame(128, 128, 128),
+            ]
+        );
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0x00, 0xff, 0x00, // Data frame
+                0xff, 0xff, 0x00, 0x00, // Data frame
+                0xff, 0x00, 0x00, 0xff, // Data frame
+                0xff, 0x80, 0x80, 0x80, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+    }
+
+    #[test]
+    fn it_inverts_all_leds() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x000000, 0x4b8040]);
+        led_strip.get_spi_data();
+
+        led_strip.invert_all();
+
+        assert_eq!(
+            led_strip.data,
+            [
+                APA102DataFrame(0, 255, 255),
+                APA102DataFrame(255, 255, 255),
+                APA102DataFrame(180, 127, 191),
+            ]
+        );
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0xff, 0xff, 0x00, // Data frame
+                0xff, 0xff, 0xff, 0xff, // Data frame
+                0xff, 0xbf, 0x7f, 0xb4, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+    }
+
+    #[test]
+    fn it_applies_a_function_to_all_leds() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x000000, 0x4b8040]);
+        led_strip.get_spi_data();
+
+        led_strip.apply(|(r, g, b)| (255 - r, 255 - g, 255 - b));
+
+        assert_eq!(
+            led_strip.data,
+            [
+                APA102DataFrame(0, 255, 255),
+                APA102DataFrame(255, 255, 255),
+                APA102DataFrame(180, 127, 191),
+            ]
+        );
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0xff, 0xff, 0x00, // Data frame
+                0xff, 0xff, 0xff, 0xff, // Data frame
+                0xff, 0xbf, 0x7f, 0xb4, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+    }
+
+    #[test]
+    fn it_converts_all_leds_to_grayscale() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff]);
+        led_strip.grayscale_all();
+
+        assert_eq!(
+            led_strip.data,
+            [
+                APA102DataFrame(54, 54, 54),
+                APA102DataFrame(182, 182, 182),
+                APA102DataFrame(18, 18, 18),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_offsets_leds_in_spi_data() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x4b8040]);
+        led_strip.get_spi_data();
+
+        led_strip.set_led_offset(2);
+
+        assert_eq!(led_strip.get_led(0), (255, 0, 0));
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0xff, 0x00, 0x00, // Data frame
+                0xff, 0x40, 0x80, 0x4b, // Data frame
+                0xff, 0x00, 0x00, 0xff, // Data frame
+                0xff, 0x00, 0xff, 0x00, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reverses_leds_in_spi_data() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x4b8040]);
+        led_strip.get_spi_data();
+
+        led_strip.set_reversed(true);
+
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0x00, 0x00, 0xff, // Data frame
+                0xff, 0x40, 0x80, 0x4b, // Data frame
+                0xff, 0xff, 0x00, 0x00, // Data frame
+                0xff, 0x00, 0xff, 0x00, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+    }
+
+    #[test]
+    fn it_offsets_reversed_leds_in_spi_data() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x4b8040]);
+        led_strip.set_led_offset(2);
+        led_strip.set_reversed(true);
+
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0xff, 0x00, 0x00, // Data frame
+                0xff, 0x00, 0xff, 0x00, // Data frame
+                0xff, 0x00, 0x00, 0xff, // Data frame
+                0xff, 0x40, 0x80, 0x4b, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "offset out of bounds")]
+    fn it_throws_when_offsetting_past_the_end_of_the_strip() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x4b8040]);
+        led_strip.set_led_offset(4);
+    }
+
+    #[test]
+    fn it_decodes_spi_data_back_into_colors() {
+        let led_strip = LEDStrip::new_with_data([0x4b8040]);
+        assert_eq!(
+            decode_spi_data(led_strip.get_spi_data()),
+            vec![(75, 128, 64)]
+        );
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0xffffff]);
+        assert_eq!(
+            decode_spi_data(led_strip.get_spi_data()),
+            vec![(255, 0, 0), (0, 255, 0), (255, 255, 255)]
+        );
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0xffffff]);
+        assert_eq!(
+            decode_spi_data(led_strip.get_spi_data()),
+            vec![(255, 0, 0), (0, 255, 0), (0, 0, 255), (255, 255, 255)]
+        );
+
+        assert_eq!(decode_spi_data(&[]), vec![]);
+    }
+
+    #[test]
+    fn it_keeps_apa102_and_sk9822_frames_identical_at_full_brightness() {
+        let mut led_strip = LEDStrip::new_with_data([0x4b8040]);
+        let apa102 = led_strip.get_spi_data().clone();
+
+        led_strip.set_chip_profile(ChipProfile::Sk9822);
+
+        assert_eq!(led_strip.get_spi_data(), &apa102);
+    }
+
+    #[test]
+    fn it_dims_apa102_and_sk9822_frames_at_half_brightness() {
+        let mut led_strip = LEDStrip::new_with_data([0x4b8040, 0xffffff]);
+        led_strip.set_brightness(0.5);
+
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0x20, 0x40, 0x26, // Data frame
+                0xff, 0x80, 0x80, 0x80, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+
+        led_strip.set_chip_profile(ChipProfile::Sk9822);
+
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xf0, 0x3e, 0x7c, 0x49, // Data frame
+                0xf0, 0xf7, 0xf7, 0xf7, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+    }
+
+    #[test]
+    fn it_makes_scaled_spi_data_without_changing_the_led_strip() {
+        let led_strip = LEDStrip::new_with_data([0x4b8040, 0xffffff]);
+        let spi_data = led_strip.get_spi_data().clone();
+
+        assert_eq!(
+            led_strip.get_spi_data_with_brightness(0.5),
+            &[
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0x20, 0x40, 0x26, // Data frame
+                0xff, 0x80, 0x80, 0x80, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+        assert_eq!(led_strip.get_spi_data_with_brightness(1.0), spi_data);
+        assert_eq!(led_strip.get_spi_data(), &spi_data);
+        assert_eq!(led_strip.get_led(0), (0x4b, 0x80, 0x40));
+    }
+
+    #[test]
+    fn it_makes_scaled_spi_data_without_advancing_the_dither_state() {
+        let dithered_strip = || {
+            let mut led_strip = LEDStrip::new_with_data([0x010101, 0x030303]);
+            led_strip.set_brightness(0.5);
+            led_strip.set_dithering(true);
+            led_strip
+        };
+        let mut led_strip = dithered_strip();
+        let mut untouched = dithered_strip();
+
+        for _ in 0..4 {
+            let scaled = led_strip.get_spi_data_with_brightness(0.5);
+            assert_eq!(led_strip.get_spi_data_with_brightness(0.5), scaled);
+            assert_eq!(led_strip.get_spi_data(), untouched.get_spi_data());
+
+            led_strip.set_led(0, 0x010101);
+            untouched.set_led(0, 0x010101);
+        }
+    }
+
+    #[test]
+    fn it_copies_bytes_directly_without_corrections() {
+        for channel in 0..=255 {
+            let frame = APA102DataFrame(channel, channel / 2, 255 - channel);
+            assert_eq!(
+                frame.get_unscaled_spi_data(MAX_GLOBAL_BRIGHTNESS),
+                frame.get_spi_data(MAX_GLOBAL_BRIGHTNESS, 1.0, None)
+            );
+        }
+
+        let mut led_strip = LEDStrip::new_with_data([0x4b8040, 0xff0000, 0x102030]);
+        let uncorrected = led_strip.get_spi_data().clone();
+        assert_eq!(
+            uncorrected,
+            [
+                0x00, 0x00, 0x00, 0x00, // Start frame
+                0xff, 0x40, 0x80, 0x4b, // Data frame
+                0xff, 0x00, 0x00, 0xff, // Data frame
+                0xff, 0x30, 0x20, 0x10, // Data frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+                0xff, 0xff, 0xff, 0xff, // End frame
+            ]
+        );
+
+        led_strip.set_gamma(2.2);
+        assert_ne!(led_strip.get_spi_data(), &uncorrected);
+        led_strip.set_gamma(1.0);
+        led_strip.set_white_balance(1.0, 0.9, 1.0);
+        assert_ne!(led_strip.get_spi_data(), &uncorrected);
+        led_strip.set_white_balance(1.0, 1.0, 1.0);
+        assert_eq!(led_strip.get_spi_data(), &uncorrected);
+    }
+
+    #[test]
+    fn it_turns_sk9822_frames_off_at_zero_brightness() {
+        let mut led_strip = LEDStrip::new_with_data([0xffffff]);
+        led_strip.set_chip_profile(ChipProfile::Sk9822);
+        led_strip.set_brightness(0.0);
+
+        assert_eq!(&led_strip.get_spi_data()[4..8], &[0xe0, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn it_makes_lpd8806_frames_for_an_led_strip() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x4b8040]);
+        led_strip.set_chip_profile(ChipProfile::Lpd8806);
+
+        assert_eq!(
+            led_strip.get_spi_data(),
+            &[
+                0x80, 0xff, 0x80, // Data frame
+                0xff, 0x80, 0x80, // Data frame
+                0xc0, 0xa5, 0xa0, // Data frame
+                0x00, // Latch
+            ]
+        );
+        assert_eq!(
+            decode_spi_data(led_strip.get_spi_data()),
+            vec![(254, 0, 0), (0, 254, 0), (74, 128, 64)]
+        );
+    }
+
+    #[test]
+    fn it_sizes_lpd8806_latches_by_strip_length() {
+        fn latch_length<const N: usize>() -> usize {
+            let mut led_strip = LEDStrip::<N>::new_with_data([0xffffff; N]);
+            led_strip.set_chip_profile(ChipProfile::Lpd8806);
+
+            let spi_data = led_strip.get_spi_data();
+            assert_eq!(decode_spi_data(spi_data).len(), N);
+            assert!(spi_data[..N * 3].iter().all(|&byte| byte == 0xff));
+            assert!(spi_data[N * 3..].iter().all(|&byte| byte == 0x00));
+            spi_data.len() - N * 3
+        }
+
+        assert_eq!(latch_length::<1>(), 1);
+        assert_eq!(latch_length::<32>(), 1);
+        assert_eq!(latch_length::<33>(), 2);
+    }
+
+    #[test]
+    fn it_slices_spi_data_into_shorter_strips() {
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x4b8040]);
+        let spi_data = led_strip.get_spi_data();
+
+        assert_eq!(
+            slice_spi_data(spi_data, 0..1),
+            LEDStrip::new_with_data([0xff0000]).get_spi_data().clone()
+        );
+        assert_eq!(
+            slice_spi_data(spi_data, 1..4),
+            LEDStrip::new_with_data([0x00ff00, 0x0000ff, 0x4b8040])
+                .get_spi_data()
+                .clone()
+        );
+        assert_eq!(slice_spi_data(spi_data, 0..4), spi_data.clone());
+    }
+
+    #[test]
+    fn it_slices_lpd8806_spi_data_into_shorter_strips() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff]);
+        led_strip.set_chip_profile(ChipProfile::Lpd8806);
+
+        let mut expected = LEDStrip::new_with_data([0x00ff00, 0x0000ff]);
+        expected.set_chip_profile(ChipProfile::Lpd8806);
+
+        assert_eq!(
+            slice_spi_data(led_strip.get_spi_data(), 1..3),
+            expected.get_spi_data().clone()
+        );
+    }
+
+    #[test]
+    fn it_makes_sk6812_rgbw_frames_for_an_led_strip() {
+        let mut led_strip = LEDStrip::new_with_data([0x808080, 0xff0000]);
+        led_strip.set_chip_profile(ChipProfile::Sk6812Rgbw);
+
+        let spi_data = led_strip.get_spi_data();
+        assert_eq!(spi_data.len(), 2 * 12 + 32);
+        assert_eq!(
+            &spi_data[..24],
+            &[
+                0x92, 0x49, 0x24, // Green
+                0x92, 0x49, 0x24, // Red
+                0x92, 0x49, 0x24, // Blue
+                0xd2, 0x49, 0x24, // White
+                0x92, 0x49, 0x24, // Green
+                0xdb, 0x6d, 0xb6, // Red
+                0x92, 0x49, 0x24, // Blue
+                0x92, 0x49, 0x24, // White
+            ]
+        );
+        assert!(spi_data[24..].iter().all(|&byte| byte == 0x00));
+        assert_eq!(
+            decode_spi_data(spi_data),
+            vec![(128, 128, 128), (255, 0, 0)]
+        );
+
+        led_strip.set_white_extraction(0.0);
+        assert_eq!(
+            &led_strip.get_spi_data()[..9],
+            &[0xd2, 0x49, 0x24].repeat(3)[..]
+        );
+        assert_eq!(&led_strip.get_spi_data()[9..12], &[0x92, 0x49, 0x24]);
+    }
+
+    #[test]
+    fn it_slices_sk6812_spi_data_into_shorter_strips() {
+        let mut led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x4b8040]);
+        led_strip.set_chip_profile(ChipProfile::Sk6812Rgbw);
+
+        let mut expected = LEDStrip::new_with_data([0x00ff00, 0x4b8040]);
+        expected.set_chip_profile(ChipProfile::Sk6812Rgbw);
+
+        assert_eq!(
+            slice_spi_data(led_strip.get_spi_data(), 1..3),
+            expected.get_spi_data().clone()
+        );
+        assert_eq!(
+            decode_spi_data(&slice_spi_data(led_strip.get_spi_data(), 1..3)),
+            vec![(0, 255, 0), (75, 128, 64)]
+        );
+    }
+
+    #[test]
+    fn it_dithers_sub_lsb_values_across_frames() {
+        const FRAMES: usize = 100;
+
+        let average_output = |dithering: bool| -> (f32, f32) {
+            let mut led_strip = LEDStrip::new_with_data([0x010300]);
+            led_strip.set_brightness(0.25);
+            led_strip.set_dithering(dithering);
+
+            let (mut r, mut g) = (0, 0);
+            for _ in 0..FRAMES {
+                led_strip.set_led(0, 0x010300);
+                let (frame_r, frame_g, _) = decode_spi_data(led_strip.get_spi_data())[0];
+                r += u32::from(frame_r);
+                g += u32::from(frame_g);
+            }
+            (r as f32 / FRAMES as f32, g as f32 / FRAMES as f32)
+        };
+
+        assert_eq!(average_output(false), (0.0, 1.0));
+
+        let (r, g) = average_output(true);
+        assert!((r - 0.25).abs() < 0.02);
+        assert!((g - 0.75).abs() < 0.02);
+    }
+
+    #[test]
+    fn it_keeps_dithered_frames_stable_until_the_strip_changes() {
+        let mut led_strip = LEDStrip::new_with_data([0x010101]);
+        led_strip.set_brightness(0.5);
+        led_strip.set_dithering(true);
+
+        let first = led_strip.get_spi_data().clone();
+        assert_eq!(led_strip.get_spi_data(), &first);
+
+        led_strip.set_led(0, 0x010101);
+        assert_ne!(led_strip.get_spi_data(), &first);
+    }
+
+    #[test]
+    fn it_builds_a_configured_strip() {
+        let mut led_strip: LEDStrip<4> = LEDStripBuilder::new()
+            .offset(1)
+            .reversed(true)
+            .brightness(15)
+            .gamma(2.0)
+            .white_balance(1.0, 0.5, 0.0)
+            .build()
+            .unwrap();
+        led_strip.set_led(0, 0xffffff);
+
+        assert_eq!(
+            decode_spi_data(led_strip.get_spi_data()),
+            vec![(0, 0, 0), (123, 62, 0), (0, 0, 0), (0, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn it_rejects_out_of_range_settings() {
+        let builder: LEDStripBuilder<4> = LEDStripBuilder::new();
+        assert_eq!(
+            builder.brightness(32).build().err(),
+            Some(ConfigError::BrightnessOutOfRange(32))
+        );
+
+        let builder: LEDStripBuilder<4> = LEDStripBuilder::new();
+        assert_eq!(
+            builder.offset(4).build().err(),
+            Some(ConfigError::OffsetOutOfBounds(4))
+        );
+
+        let builder: LEDStripBuilder<4> = LEDStripBuilder::new();
+        assert_eq!(
+            builder.white_balance(1.0, -0.5, 1.0).build().err(),
+            Some(ConfigError::NegativeGain(-0.5))
+        );
+
+        let builder: LEDStripBuilder<4> = LEDStripBuilder::new();
+        assert_eq!(
+            builder.white_extraction(-0.1).build().err(),
+            Some(ConfigError::WhiteExtractionOutOfRange(-0.1))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn it_round_trips_a_strip_snapshot_through_json() {
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x4b8040, 0x000000, 0x0000ff]);
+
+        let json = serde_json::to_string(&led_strip.snapshot()).unwrap();
+        assert_eq!(json, r##"["#ff0000","#4b8040","#000000","#0000ff"]"##);
+
+        let LedColors(colors) = serde_json::from_str(&json).unwrap();
+        let replayed: LEDStrip<4> = LEDStrip::try_from_slice(&colors).unwrap();
+        assert_eq!(replayed.to_bytes(), led_strip.to_bytes());
+        assert!(serde_json::from_str::<LedColors>(r#"["ff0000"]"#).is_err());
+    }
 }
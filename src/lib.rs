@@ -0,0 +1,14 @@
+#![deny(clippy::all)]
+
+pub mod brightness;
+pub mod camera_format;
+pub mod color;
+pub mod config;
+pub mod effects;
+pub mod frame_buffer;
+pub mod led;
+pub mod logging;
+pub mod power;
+pub mod segment_map;
+pub mod spi_settings;
+pub mod test_pattern;
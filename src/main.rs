@@ -1,16 +1,51 @@
 #![deny(clippy::all)]
 
+mod preview;
+
+use afterglow::brightness::mean_luminance;
+use afterglow::camera_format::{format_mismatch, supported_formats, FormatOption};
+use afterglow::color::{
+    apply_brightness, apply_color_matrix, apply_desaturate, apply_gamma, apply_grayscale,
+    apply_hue_rotation, apply_inversion, enhance_hue, HueEnhancement,
+};
+use afterglow::config::Config;
+use afterglow::frame_buffer::ReusableFrameBuffer;
+use afterglow::logging::init_logging;
+use afterglow::power::PowerBudget;
+use afterglow::segment_map::{
+    average_indexed_segment_colors, inner_radius, outer_radius, SegmentIndex, SegmentMap,
+};
+use clap::Parser;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Select;
-use minifb::{Key, Window, WindowOptions};
+use minifb::{Key, KeyRepeat, ScaleMode, Window, WindowOptions};
 use nokhwa::pixel_format::RgbFormat;
-use nokhwa::utils::{
-    CameraFormat, CameraIndex, FrameFormat, RequestedFormat, RequestedFormatType, Resolution,
-};
+use nokhwa::utils::{CameraFormat, CameraIndex, RequestedFormat, RequestedFormatType};
 use nokhwa::Camera;
-use std::cmp::Ordering;
-use std::f64::consts::{PI, TAU};
+use preview::{draw_circle, draw_led_ring, draw_rect, render_segment_colors, write_png};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
 use std::{thread, time::Duration};
+use tracing::{info, instrument, warn};
+
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct DebuggerArgs {
+    /// Print the segment colors of a single frame instead of opening a window
+    #[arg(long)]
+    no_window: bool,
+
+    /// Also write the visualized segment map to a PNG when running without a
+    /// window
+    #[arg(long, value_name = "PATH", requires = "no_window")]
+    preview_png: Option<PathBuf>,
+
+    #[command(flatten)]
+    config: Config,
+}
+
+const NUM_LEDS: usize = 50;
 
 fn from_u64_rgb(r: u64, g: u64, b: u64) -> u32 {
     let (r, g, b): (u32, u32, u32) = (
@@ -44,41 +79,26 @@ fn prompt_camera_device() -> CameraIndex {
     devices[selection].index().clone()
 }
 
-fn prompt_camera(camera_index: CameraIndex) -> Camera {
+fn prompt_camera(camera_index: CameraIndex, strict_format: bool) -> Camera {
     let mut camera = Camera::new(
         camera_index,
         RequestedFormat::new::<RgbFormat>(RequestedFormatType::None),
     )
     .expect("Unable to build camera");
-    let camera_resolutions = camera
-        .compatible_list_by_resolution(FrameFormat::YUYV)
-        .expect("Unable to get available camera resolutions");
-
-    let mut resolutions: Vec<&Resolution> = camera_resolutions.keys().collect();
-    resolutions.sort_by(|a, b| match a.width().cmp(&b.width()) {
-        Ordering::Equal => a.height().cmp(&b.height()),
-        ord => ord,
-    });
-    let resolution_options: Vec<String> = resolutions
-        .iter()
-        .map(|resolution| {
-            format!(
-                "{}\t(fps options: {:?})",
-                resolution,
-                camera_resolutions.get(resolution).unwrap()
-            )
-        })
-        .collect();
-    let selected_resolution_index = Select::with_theme(&ColorfulTheme::default())
+    let format_options = supported_formats(&mut camera);
+    if format_options.is_empty() {
+        panic!("No YUYV or MJPEG capture formats available");
+    }
+
+    let format_labels: Vec<String> = format_options.iter().map(FormatOption::label).collect();
+    let selected_format = &format_options[Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Select capture resolution")
-        .items(&resolution_options)
+        .items(&format_labels)
         .default(0)
         .interact()
-        .expect("Must choose a resolution");
+        .expect("Must choose a resolution")];
 
-    let fps_options = camera_resolutions
-        .get(resolutions[selected_resolution_index])
-        .unwrap();
+    let fps_options = &selected_format.frame_rates;
     let selected_fps_index = Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Select capture fps")
         .items(fps_options)
@@ -86,67 +106,155 @@ fn prompt_camera(camera_index: CameraIndex) -> Camera {
         .interact()
         .expect("Must choose an fps option");
 
+    let requested_format = CameraFormat::new(
+        selected_format.resolution,
+        selected_format.format,
+        fps_options[selected_fps_index],
+    );
     camera
         .set_camera_requset(RequestedFormat::new::<RgbFormat>(
-            RequestedFormatType::Closest(CameraFormat::new(
-                *resolutions[selected_resolution_index],
-                FrameFormat::YUYV,
-                fps_options[selected_fps_index],
-            )),
+            RequestedFormatType::Closest(requested_format),
         ))
         .expect("Failed to set camera format");
 
+    if let Some(mismatch) =
+        format_mismatch(&requested_format, camera.resolution(), camera.frame_rate())
+    {
+        if strict_format {
+            panic!("{}", mismatch);
+        }
+        warn!("{}", mismatch);
+    }
+
     camera
 }
 
-fn build_segment_map(num_leds: usize, width: u32, height: u32) -> Vec<Option<usize>> {
-    let mut segment_table: Vec<Option<usize>> =
-        Vec::with_capacity((width * height).try_into().unwrap());
-
-    let width = width as i32;
-    let height = height as i32;
-    let half_width = width / 2;
-    let half_height = height / 2;
-    let edge = half_width.min(half_height) / 2;
-
-    let theta_scalar = (num_leds as f64) / TAU;
-
-    for y in 0..height {
-        let dy = (y - half_height) as f64;
-        for x in 0..width {
-            let dx = (half_width - x) as f64;
-            segment_table.push(if dx.hypot(dy) >= edge.into() {
-                let theta = dy.atan2(dx) + PI;
-                let segment = ((theta * theta_scalar).floor() as usize).min(num_leds - 1);
-                Some(segment)
+#[instrument(level = "trace", skip_all)]
+fn compute_segment_colors(
+    decoded_image: &[u8],
+    segment_index: &SegmentIndex,
+    config: &Config,
+    hue_enhancement: &Option<HueEnhancement>,
+) -> Vec<u32> {
+    let brightness = if config.auto_brightness {
+        config
+            .auto_brightness_curve
+            .brightness(mean_luminance(decoded_image), config.auto_brightness_min)
+    } else {
+        1.0
+    };
+
+    let mut colors: Vec<u32> = average_indexed_segment_colors(decoded_image, segment_index)
+        .expect("Camera frame does not match the segment map")
+        .into_iter()
+        .map(|color| {
+            let color = match &config.color_matrix {
+                Some(color_matrix) => apply_color_matrix(color, color_matrix),
+                None => color,
+            };
+            let color = apply_hue_rotation(color, config.hue_rotation_degrees);
+            let color = match hue_enhancement {
+                Some(hue_enhancement) => enhance_hue(color, hue_enhancement),
+                None => color,
+            };
+            let color = if config.grayscale {
+                apply_grayscale(color)
             } else {
-                None
-            });
-        }
+                apply_desaturate(color, config.desaturate)
+            };
+            let color = if config.invert {
+                apply_inversion(color)
+            } else {
+                color
+            };
+            let color = apply_brightness(color, brightness);
+            apply_gamma(color, config.gamma, config.white_balance)
+        })
+        .collect();
+
+    if let Some(max_milliamps) = config.max_milliamps {
+        PowerBudget::new(max_milliamps, NUM_LEDS).scale(&mut colors);
+    }
+    colors
+}
+
+fn build_preview_segment_map(width: u32, height: u32, config: &Config) -> SegmentMap {
+    config
+        .segment_map(NUM_LEDS, width, height)
+        .expect("Invalid segment map configuration")
+}
+
+fn start_headless_preview(mut camera: Camera, config: &Config, preview_png: Option<&Path>) {
+    let resolution = camera.resolution();
+    let segment_map = build_preview_segment_map(resolution.width(), resolution.height(), config);
+    let segment_index = config.segment_index(&segment_map);
+
+    let frame = camera.frame().expect("Unable to get frame from camera");
+    let mut frame_buffer = ReusableFrameBuffer::new();
+    frame_buffer.decode_into(&frame).unwrap();
+    let segment_colors = compute_segment_colors(
+        frame_buffer.data(),
+        &segment_index,
+        config,
+        &config.hue_enhancement(),
+    );
+
+    for (index, color) in segment_colors.iter().enumerate() {
+        println!(
+            "LED {:>3}: #{:06x} \x1b[48;2;{};{};{}m    \x1b[0m",
+            index,
+            color,
+            (color >> 16) & 0xff,
+            (color >> 8) & 0xff,
+            color & 0xff
+        );
     }
 
-    segment_table
+    if let Some(path) = preview_png {
+        let file = File::create(path).expect("Unable to create preview PNG");
+        write_png(
+            BufWriter::new(file),
+            &render_segment_colors(segment_map.pixels(), &segment_colors),
+            resolution.width(),
+            resolution.height(),
+        )
+        .expect("Unable to write preview PNG");
+        info!("Wrote segment map preview to {}", path.display());
+    }
 }
 
-fn start_visual_debugger(mut camera: Camera) {
+fn tuning_title(config: &Config) -> String {
+    format!(
+        "afterglow - gamma {:.1} (G), saturation {:+.1} (S), edge {:.2} (up/down), Tab for split view",
+        config.gamma, config.saturation_boost, config.edge_fraction
+    )
+}
+
+fn step(value: f32, delta: f32, min: f32, max: f32) -> f32 {
+    ((value + delta) * 100.0)
+        .round()
+        .clamp(min * 100.0, max * 100.0)
+        / 100.0
+}
+
+fn start_visual_debugger(mut camera: Camera, config: &Config) {
     let resolution = camera.resolution();
-    let width = resolution.width();
-    let height = resolution.height();
+    let mut config = config.clone();
 
-    const NUM_LEDS: usize = 50;
-    let segment_map = build_segment_map(NUM_LEDS, width, height);
+    let mut segment_map =
+        build_preview_segment_map(resolution.width(), resolution.height(), &config);
+    let mut segment_index = config.segment_index(&segment_map);
 
-    let width = width.try_into().unwrap();
-    let height: usize = height.try_into().unwrap();
-    let window_height = height * 2;
+    let width: usize = resolution.width().try_into().unwrap();
+    let height: usize = resolution.height().try_into().unwrap();
+    let ring_thickness = (width.min(height) / 16).max(2) as f64;
 
     let mut window: Window = Window::new(
-        "afterglow",
+        &tuning_title(&config),
         width,
-        window_height,
+        height,
         WindowOptions {
-            title: false,
-            borderless: true,
+            scale_mode: ScaleMode::AspectRatioStretch,
             ..WindowOptions::default()
         },
     )
@@ -154,63 +262,95 @@ fn start_visual_debugger(mut camera: Camera) {
 
     let frame_delay = Duration::from_millis((1000 / camera.frame_rate()).into());
 
-    let mut source_image = Vec::with_capacity(width * height);
-    for _ in 0..width * height {
-        source_image.push(0);
-    }
+    let mut hue_enhancement = config.hue_enhancement();
+
+    let mut source_image = vec![0; width * height];
+    let mut frame_buffer = ReusableFrameBuffer::new();
 
+    let mut split_view = false;
     while window.is_open() && !window.is_key_down(Key::Escape) {
+        let shift = window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift);
+        let direction = if shift { 1.0 } else { -1.0 };
+        let mut tuned = false;
+        for key in window.get_keys_pressed(KeyRepeat::Yes) {
+            match key {
+                Key::Tab => split_view = !split_view,
+                Key::G => config.gamma = step(config.gamma, direction * 0.1, 0.1, 5.0),
+                Key::S => {
+                    config.saturation_boost =
+                        step(config.saturation_boost, direction * 0.1, -1.0, 3.0);
+                    hue_enhancement = config.hue_enhancement();
+                }
+                Key::Up | Key::Down => {
+                    let delta = if key == Key::Up { 0.05 } else { -0.05 };
+                    config.edge_fraction =
+                        step(config.edge_fraction as f32, delta, 0.0, 0.95).into();
+                    segment_map =
+                        build_preview_segment_map(resolution.width(), resolution.height(), &config);
+                    segment_index = config.segment_index(&segment_map);
+                }
+                _ => continue,
+            }
+            tuned = true;
+        }
+        if tuned {
+            window.set_title(&tuning_title(&config));
+        }
+
         let frame = camera.frame().expect("Unable to get frame from camera");
-        let decoded_image = frame.decode_image::<RgbFormat>().unwrap();
+        frame_buffer.decode_into(&frame).unwrap();
 
-        let mut led_values: [(u64, u64, u64); NUM_LEDS] = [(0, 0, 0); NUM_LEDS];
-        let mut counts: [u64; NUM_LEDS] = [0; NUM_LEDS];
-        for (index, pixel) in decoded_image.chunks_exact(3).enumerate() {
-            let (r, g, b) = (
+        for (index, pixel) in frame_buffer.data().chunks_exact(3).enumerate() {
+            source_image[index] = from_u64_rgb(
                 u64::from(pixel[0]),
                 u64::from(pixel[1]),
                 u64::from(pixel[2]),
             );
-
-            source_image[index] = from_u64_rgb(r, g, b);
-
-            if let Some(segment) = segment_map[index] {
-                if counts[segment] == 0 {
-                    led_values[segment].0 = r.pow(2);
-                    led_values[segment].1 = g.pow(2);
-                    led_values[segment].2 = b.pow(2);
-                } else {
-                    led_values[segment].0 += r.pow(2);
-                    led_values[segment].1 += g.pow(2);
-                    led_values[segment].2 += b.pow(2);
-                }
-                counts[segment] += 1;
-            }
         }
 
-        let image_buffer: Vec<u32> = (0..(width * window_height))
-            .map(|index| {
-                if index < width * height {
-                    match segment_map[index] {
-                        Some(segment) => {
-                            let (r, g, b) = led_values[segment];
-                            let count = counts[segment];
-                            from_u64_rgb(
-                                ((r / count) as f64).sqrt() as u64,
-                                ((g / count) as f64).sqrt() as u64,
-                                ((b / count) as f64).sqrt() as u64,
-                            )
-                        }
-                        None => 0,
-                    }
-                } else {
-                    source_image[index - width * height]
-                }
-            })
-            .collect();
+        let segment_colors = compute_segment_colors(
+            frame_buffer.data(),
+            &segment_index,
+            &config,
+            &hue_enhancement,
+        );
+
+        let (image_buffer, buffer_height) = if split_view {
+            let mut image_buffer = render_segment_colors(segment_map.pixels(), &segment_colors);
+            image_buffer.extend_from_slice(&source_image);
+            (image_buffer, height * 2)
+        } else {
+            let mut image_buffer = source_image.clone();
+            let crop_region = config.crop_region(resolution.width(), resolution.height());
+            let (crop_x, crop_y, crop_width, crop_height) =
+                crop_region.unwrap_or((0, 0, resolution.width(), resolution.height()));
+            let center = (
+                (crop_x + crop_width / 2) as usize,
+                (crop_y + crop_height / 2) as usize,
+            );
+            draw_led_ring(
+                &mut image_buffer,
+                &segment_colors,
+                segment_map.pixels(),
+                width,
+                center,
+                inner_radius(crop_width, crop_height, config.edge_fraction),
+                ring_thickness,
+            );
+            for radius in [
+                inner_radius(crop_width, crop_height, config.edge_fraction),
+                outer_radius(crop_width, crop_height, config.outer_fraction),
+            ] {
+                draw_circle(&mut image_buffer, width, center, radius, 0xffffff);
+            }
+            if let Some(region) = crop_region {
+                draw_rect(&mut image_buffer, width, region, 0xffffff);
+            }
+            (image_buffer, height)
+        };
 
         window
-            .update_with_buffer(&image_buffer, width, window_height)
+            .update_with_buffer(&image_buffer, width, buffer_height)
             .unwrap();
 
         thread::sleep(frame_delay);
@@ -218,10 +358,21 @@ fn start_visual_debugger(mut camera: Camera) {
 }
 
 fn main() {
+    let args = DebuggerArgs::parse();
+    let config = args
+        .config
+        .with_config_file()
+        .expect("Unable to load the config file");
+    init_logging(config.log_level, config.log_format);
+
     let camera_index = prompt_camera_device();
-    let mut camera = prompt_camera(camera_index);
+    let mut camera = prompt_camera(camera_index, config.strict_format);
 
     camera.open_stream().expect("Unable to open stream");
 
-    start_visual_debugger(camera);
+    if args.no_window {
+        start_headless_preview(camera, &config, args.preview_png.as_deref());
+    } else {
+        start_visual_debugger(camera, &config);
+    }
 }
@@ -0,0 +1,229 @@
+use crate::http::serve;
+use crate::output::OutputSink;
+use std::{
+    fmt::{Display, Write as _},
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+pub struct Metrics {
+    frames_captured: AtomicU64,
+    frames_dropped: AtomicU64,
+    sink_write_errors: AtomicU64,
+    fps: AtomicU64,
+    segment_compute_nanos: AtomicU64,
+    segment_computes: AtomicU64,
+    sink_write_nanos: AtomicU64,
+    milliamps: AtomicU64,
+}
+
+impl Metrics {
+    pub fn frame_captured(&self) {
+        self.frames_captured.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn frame_dropped(&self) {
+        self.frames_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_fps(&self, fps: f64) {
+        self.fps.store(fps.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn segment_computed(&self, duration: Duration) {
+        self.segment_compute_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+        self.segment_computes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_milliamps(&self, milliamps: f32) {
+        self.milliamps
+            .store(f64::from(milliamps).to_bits(), Ordering::Relaxed);
+    }
+
+    fn sink_written(&self, duration: Duration, ok: bool) {
+        self.sink_write_nanos
+            .store(duration.as_nanos() as u64, Ordering::Relaxed);
+        if !ok {
+            self.sink_write_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn render(&self) -> String {
+        let segment_computes = self.segment_computes.load(Ordering::Relaxed);
+        let average_segment_compute_seconds = if segment_computes == 0 {
+            0.0
+        } else {
+            self.segment_compute_nanos.load(Ordering::Relaxed) as f64
+                / segment_computes as f64
+                / 1e9
+        };
+
+        let mut out = String::new();
+        write_metric(
+            &mut out,
+            "afterglow_frames_captured_total",
+            "counter",
+            "Camera frames captured",
+            self.frames_captured.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "afterglow_frames_dropped_total",
+            "counter",
+            "Camera frames that could not be decoded",
+            self.frames_dropped.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "afterglow_sink_write_errors_total",
+            "counter",
+            "Failed writes to the LED outputs",
+            self.sink_write_errors.load(Ordering::Relaxed),
+        );
+        write_metric(
+            &mut out,
+            "afterglow_fps",
+            "gauge",
+            "Current capture frame rate",
+            f64::from_bits(self.fps.load(Ordering::Relaxed)),
+        );
+        write_metric(
+            &mut out,
+            "afterglow_segment_compute_seconds",
+            "gauge",
+            "Average time spent computing segment colors",
+            average_segment_compute_seconds,
+        );
+        write_metric(
+            &mut out,
+            "afterglow_spi_write_seconds",
+            "gauge",
+            "Duration of the last write to the LED outputs",
+            self.sink_write_nanos.load(Ordering::Relaxed) as f64 / 1e9,
+        );
+        write_metric(
+            &mut out,
+            "afterglow_led_milliamps",
+            "gauge",
+            "Estimated LED power draw",
+            f64::from_bits(self.milliamps.load(Ordering::Relaxed)),
+        );
+
+        out
+    }
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl Display) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+pub fn serve_metrics(addr: &str, metrics: Arc<Metrics>) -> io::Result<SocketAddr> {
+    serve(
+        addr,
+        "metrics",
+        "text/plain; version=0.0.4",
+        move |request| match request.path.as_str() {
+            "/metrics" => ("200 OK", metrics.render()),
+            _ => ("404 Not Found", String::new()),
+        },
+    )
+}
+
+pub struct MeteredSink {
+    sink: Box<dyn OutputSink>,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredSink {
+    pub fn new(sink: Box<dyn OutputSink>, metrics: Arc<Metrics>) -> Self {
+        Self { sink, metrics }
+    }
+}
+
+impl OutputSink for MeteredSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let start = Instant::now();
+        let result = self.sink.write(spi_data);
+        self.metrics.sink_written(start.elapsed(), result.is_ok());
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{serve_metrics, MeteredSink, Metrics};
+    use crate::output::{OutputSink, VecSink};
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+        sync::Arc,
+        time::Duration,
+    };
+
+    fn scrape(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    fn metric(response: &str, name: &str) -> f64 {
+        response
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn it_serves_metrics_in_prometheus_format() {
+        let metrics = Arc::new(Metrics::default());
+        let addr = serve_metrics("127.0.0.1:0", Arc::clone(&metrics)).unwrap();
+
+        let response = scrape(addr, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        for name in [
+            "afterglow_frames_captured_total",
+            "afterglow_frames_dropped_total",
+            "afterglow_sink_write_errors_total",
+            "afterglow_fps",
+            "afterglow_segment_compute_seconds",
+            "afterglow_spi_write_seconds",
+            "afterglow_led_milliamps",
+        ] {
+            assert!(response.contains(&format!("# TYPE {} ", name)));
+        }
+        assert_eq!(metric(&response, "afterglow_frames_captured_total"), 0.0);
+
+        metrics.frame_captured();
+        metrics.frame_captured();
+        metrics.frame_dropped();
+        metrics.segment_computed(Duration::from_millis(2));
+        metrics.segment_computed(Duration::from_millis(4));
+        let mut sink = MeteredSink::new(Box::new(VecSink::default()), Arc::clone(&metrics));
+        sink.write(&[0x00; 8]).unwrap();
+
+        let response = scrape(addr, "/metrics");
+        assert_eq!(metric(&response, "afterglow_frames_captured_total"), 2.0);
+        assert_eq!(metric(&response, "afterglow_frames_dropped_total"), 1.0);
+        assert_eq!(metric(&response, "afterglow_sink_write_errors_total"), 0.0);
+        assert_eq!(
+            metric(&response, "afterglow_segment_compute_seconds"),
+            0.003
+        );
+
+        assert!(scrape(addr, "/").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+}
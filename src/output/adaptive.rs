@@ -0,0 +1,151 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+fn max_color_distance(a: &[(u8, u8, u8)], b: &[(u8, u8, u8)]) -> f32 {
+    if a.len() != b.len() {
+        return f32::INFINITY;
+    }
+
+    a.iter()
+        .zip(b)
+        .map(|(&(r1, g1, b1), &(r2, g2, b2))| {
+            let dr = f32::from(r1) - f32::from(r2);
+            let dg = f32::from(g1) - f32::from(g2);
+            let db = f32::from(b1) - f32::from(b2);
+            (dr * dr + dg * dg + db * db).sqrt()
+        })
+        .fold(0.0, f32::max)
+}
+
+pub struct AdaptiveSink<S> {
+    sink: S,
+    change_threshold: f32,
+    force_write_interval: u32,
+    last_written: Option<Vec<(u8, u8, u8)>>,
+    unchanged_frame_count: u32,
+    report_start: Option<Instant>,
+    frames: u64,
+    skipped_frames: u64,
+}
+
+impl<S: OutputSink> AdaptiveSink<S> {
+    pub fn new(sink: S, change_threshold: f32, force_write_interval: u32) -> Self {
+        assert!(
+            force_write_interval > 0,
+            "Writes must be forced at least every frame"
+        );
+
+        Self {
+            sink,
+            change_threshold,
+            force_write_interval,
+            last_written: None,
+            unchanged_frame_count: 0,
+            report_start: None,
+            frames: 0,
+            skipped_frames: 0,
+        }
+    }
+
+    fn report(&mut self, timestamp: Instant) {
+        let report_start = *self.report_start.get_or_insert(timestamp);
+        if timestamp.duration_since(report_start) < REPORT_INTERVAL {
+            return;
+        }
+
+        if self.skipped_frames > 0 {
+            info!(
+                skipped = self.skipped_frames,
+                frames = self.frames,
+                "Skipped unchanged SPI frames"
+            );
+        }
+        self.report_start = Some(timestamp);
+        self.frames = 0;
+        self.skipped_frames = 0;
+    }
+
+    fn record(&mut self, timestamp: Instant, spi_data: &[u8]) -> io::Result<()> {
+        self.report(timestamp);
+        self.frames += 1;
+
+        let colors = decode_spi_data(spi_data);
+        let changed = self
+            .last_written
+            .as_ref()
+            .is_none_or(|last| max_color_distance(last, &colors) > self.change_threshold);
+
+        if !changed && self.unchanged_frame_count + 1 < self.force_write_interval {
+            self.unchanged_frame_count += 1;
+            self.skipped_frames += 1;
+            return Ok(());
+        }
+
+        self.sink.write(spi_data)?;
+        self.last_written = Some(colors);
+        self.unchanged_frame_count = 0;
+
+        Ok(())
+    }
+}
+
+impl<S: OutputSink> OutputSink for AdaptiveSink<S> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.record(Instant::now(), spi_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::adaptive::{max_color_distance, AdaptiveSink};
+    use crate::output::VecSink;
+    use afterglow::led::LEDStrip;
+    use std::time::Instant;
+
+    #[test]
+    fn it_measures_the_largest_per_led_distance() {
+        assert_eq!(
+            max_color_distance(&[(0, 0, 0), (10, 10, 10)], &[(0, 0, 0), (10, 13, 14)]),
+            5.0
+        );
+        assert_eq!(
+            max_color_distance(&[(0, 0, 0)], &[(0, 0, 0), (0, 0, 0)]),
+            f32::INFINITY
+        );
+    }
+
+    #[test]
+    fn it_skips_frames_within_the_change_threshold() {
+        let mut sink = AdaptiveSink::new(VecSink::default(), 2.0, 100);
+        let now = Instant::now();
+
+        for color in [0x000000, 0x000001, 0x000100, 0x000003, 0x000004] {
+            let led_strip = LEDStrip::new_with_data([0x808080, color]);
+            sink.record(now, led_strip.get_spi_data()).unwrap();
+        }
+
+        assert_eq!(sink.sink.frames.len(), 2);
+        assert_eq!(sink.skipped_frames, 3);
+    }
+
+    #[test]
+    fn it_forces_a_write_after_the_interval() {
+        let mut sink = AdaptiveSink::new(VecSink::default(), 0.0, 3);
+        let led_strip = LEDStrip::new_with_data([0x4b8040]);
+        let now = Instant::now();
+
+        for _ in 0..7 {
+            sink.record(now, led_strip.get_spi_data()).unwrap();
+        }
+
+        assert_eq!(sink.sink.frames.len(), 3);
+        assert_eq!(sink.unchanged_frame_count, 0);
+    }
+}
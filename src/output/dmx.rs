@@ -0,0 +1,163 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const START_OF_MESSAGE: u8 = 0x7e;
+const END_OF_MESSAGE: u8 = 0xe7;
+const SEND_DMX_LABEL: u8 = 6;
+const DMX_START_CODE: u8 = 0x00;
+const DMX_CHANNELS: usize = 512;
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(25);
+
+fn encode_message(universe: &[u8; DMX_CHANNELS]) -> Vec<u8> {
+    let len = (universe.len() + 1) as u16;
+
+    let mut message = Vec::with_capacity(universe.len() + 6);
+    message.push(START_OF_MESSAGE);
+    message.push(SEND_DMX_LABEL);
+    message.extend(len.to_le_bytes());
+    message.push(DMX_START_CODE);
+    message.extend(universe);
+    message.push(END_OF_MESSAGE);
+
+    message
+}
+
+pub struct DmxUsbSink<W: Write> {
+    writer: W,
+    channels: Vec<(usize, usize)>,
+    universe: [u8; DMX_CHANNELS],
+    last_sent: Option<Instant>,
+}
+
+impl DmxUsbSink<File> {
+    pub fn open(path: &Path, channels: &[(usize, u16)]) -> io::Result<Self> {
+        let port = OpenOptions::new().write(true).open(path)?;
+        Ok(DmxUsbSink::new(port, channels))
+    }
+}
+
+impl<W: Write> DmxUsbSink<W> {
+    pub fn new(writer: W, channels: &[(usize, u16)]) -> Self {
+        let channels = channels
+            .iter()
+            .map(|&(led, channel)| {
+                assert!(
+                    (1..=DMX_CHANNELS - 2).contains(&usize::from(channel)),
+                    "DMX start channel {} leaves no room for an RGB triplet",
+                    channel
+                );
+                (led, usize::from(channel) - 1)
+            })
+            .collect();
+
+        Self {
+            writer,
+            channels,
+            universe: [0; DMX_CHANNELS],
+            last_sent: None,
+        }
+    }
+
+    fn record(&mut self, timestamp: Instant, colors: &[(u8, u8, u8)]) -> io::Result<()> {
+        if let Some(last_sent) = self.last_sent {
+            if timestamp.duration_since(last_sent) < MIN_FRAME_INTERVAL {
+                return Ok(());
+            }
+        }
+
+        for &(led, channel) in &self.channels {
+            if let Some(&(r, g, b)) = colors.get(led) {
+                self.universe[channel..channel + 3].copy_from_slice(&[r, g, b]);
+            }
+        }
+
+        self.writer.write_all(&encode_message(&self.universe))?;
+        self.writer.flush()?;
+        self.last_sent = Some(timestamp);
+
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputSink for DmxUsbSink<W> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.record(Instant::now(), &decode_spi_data(spi_data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::dmx::{encode_message, DmxUsbSink, DMX_CHANNELS};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn it_frames_enttec_pro_messages() {
+        let mut universe = [0; DMX_CHANNELS];
+        universe[0] = 0xff;
+        universe[DMX_CHANNELS - 1] = 0x80;
+
+        let message = encode_message(&universe);
+        assert_eq!(message.len(), DMX_CHANNELS + 6);
+        assert_eq!(
+            message[..6],
+            [
+                0x7e, // Start of message
+                0x06, // Send DMX label
+                0x01, 0x02, // Length
+                0x00, // DMX start code
+                0xff, // Channel 1
+            ]
+        );
+        assert_eq!(message[message.len() - 2..], [0x80, 0xe7]);
+    }
+
+    #[test]
+    fn it_places_leds_at_their_start_channels() {
+        let mut sink = DmxUsbSink::new(Vec::new(), &[(1, 1), (0, 10), (5, 510)]);
+        sink.record(
+            Instant::now(),
+            &[(0xff, 0x00, 0x00), (0x4b, 0x80, 0x40), (0x01, 0x02, 0x03)],
+        )
+        .unwrap();
+
+        let universe = &sink.writer[5..5 + DMX_CHANNELS];
+        assert_eq!(universe[0..3], [0x4b, 0x80, 0x40]);
+        assert_eq!(universe[3..9], [0x00; 6]);
+        assert_eq!(universe[9..12], [0xff, 0x00, 0x00]);
+        assert!(universe[12..].iter().all(|&channel| channel == 0x00));
+    }
+
+    #[test]
+    fn it_holds_channels_of_missing_leds() {
+        let mut sink = DmxUsbSink::new(Vec::new(), &[(0, 1), (1, 4)]);
+        let start = Instant::now();
+        sink.record(start, &[(0xff, 0x00, 0x00), (0x00, 0xff, 0x00)])
+            .unwrap();
+        sink.writer.clear();
+
+        sink.record(start + Duration::from_millis(25), &[(0x00, 0x00, 0xff)])
+            .unwrap();
+        assert_eq!(sink.writer[5..11], [0x00, 0x00, 0xff, 0x00, 0xff, 0x00]);
+    }
+
+    #[test]
+    fn it_throttles_to_the_dmx_refresh_rate() {
+        let mut sink = DmxUsbSink::new(Vec::new(), &[(0, 1)]);
+        let start = Instant::now();
+        for elapsed_ms in [0, 10, 24, 25, 40, 50] {
+            sink.record(
+                start + Duration::from_millis(elapsed_ms),
+                &[(0xff, 0x00, 0x00)],
+            )
+            .unwrap();
+        }
+
+        assert_eq!(sink.writer.len(), 3 * (DMX_CHANNELS + 6));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::output::OutputSink;
+use afterglow::color::luminance;
+use afterglow::led::decode_spi_data;
+use std::{
+    io::{self, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(1);
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+pub struct DryRunSink<W: Write> {
+    writer: W,
+    dump_every: Option<u64>,
+    frame_count: u64,
+    last_frame: Option<Instant>,
+    summary_start: Option<Instant>,
+    summary_intervals: u32,
+    summary_interval_total: Duration,
+}
+
+impl DryRunSink<Stdout> {
+    pub fn stdout(dump_every: Option<u64>) -> Self {
+        DryRunSink::new(io::stdout(), dump_every)
+    }
+}
+
+impl<W: Write> DryRunSink<W> {
+    pub fn new(writer: W, dump_every: Option<u64>) -> Self {
+        assert!(
+            dump_every != Some(0),
+            "Dry run dump interval must be at least 1"
+        );
+
+        Self {
+            writer,
+            dump_every,
+            frame_count: 0,
+            last_frame: None,
+            summary_start: None,
+            summary_intervals: 0,
+            summary_interval_total: Duration::ZERO,
+        }
+    }
+
+    fn record(&mut self, timestamp: Instant, spi_data: &[u8]) -> io::Result<()> {
+        let colors = decode_spi_data(spi_data);
+        self.frame_count += 1;
+
+        if let Some(last_frame) = self.last_frame.replace(timestamp) {
+            self.summary_intervals += 1;
+            self.summary_interval_total += timestamp.duration_since(last_frame);
+        }
+
+        if let Some(dump_every) = self.dump_every {
+            if (self.frame_count - 1).is_multiple_of(dump_every) {
+                let dump: Vec<String> = colors.iter().map(|&color| hex(color)).collect();
+                writeln!(
+                    self.writer,
+                    "frame {}: {}",
+                    self.frame_count,
+                    dump.join(" ")
+                )?;
+            }
+        }
+
+        let summary_start = *self.summary_start.get_or_insert(timestamp);
+        if timestamp.duration_since(summary_start) >= SUMMARY_INTERVAL {
+            self.write_summary(&colors)?;
+            self.summary_start = Some(timestamp);
+            self.summary_intervals = 0;
+            self.summary_interval_total = Duration::ZERO;
+        }
+
+        self.writer.flush()
+    }
+
+    fn write_summary(&mut self, colors: &[(u8, u8, u8)]) -> io::Result<()> {
+        let average_interval = self.summary_interval_total / self.summary_intervals.max(1);
+        let brightest = colors
+            .iter()
+            .max_by(|(r1, g1, b1), (r2, g2, b2)| {
+                luminance(*r1, *g1, *b1).total_cmp(&luminance(*r2, *g2, *b2))
+            })
+            .copied();
+
+        match (colors.first(), colors.last(), brightest) {
+            (Some(&first), Some(&last), Some(brightest)) => writeln!(
+                self.writer,
+                "{} frames, {:.1} ms average interval, first {}, last {}, brightest {}",
+                self.frame_count,
+                average_interval.as_secs_f64() * 1000.0,
+                hex(first),
+                hex(last),
+                hex(brightest)
+            ),
+            _ => writeln!(
+                self.writer,
+                "{} frames, {:.1} ms average interval, no LEDs",
+                self.frame_count,
+                average_interval.as_secs_f64() * 1000.0
+            ),
+        }
+    }
+}
+
+impl<W: Write> OutputSink for DryRunSink<W> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.record(Instant::now(), spi_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::dry_run::DryRunSink;
+    use afterglow::led::LEDStrip;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn it_prints_a_summary_every_second() {
+        let led_strip = LEDStrip::new_with_data([0x102030, 0xffffff, 0x0000ff]);
+        let mut sink = DryRunSink::new(Vec::new(), None);
+        let start = Instant::now();
+
+        for frame in 0..=40 {
+            sink.record(
+                start + Duration::from_millis(frame * 25),
+                led_strip.get_spi_data(),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "41 frames, 25.0 ms average interval, first #102030, last #0000ff, brightest #ffffff\n"
+        );
+    }
+
+    #[test]
+    fn it_dumps_every_kth_frame_in_full() {
+        let mut led_strip: LEDStrip<2> = LEDStrip::new();
+        let mut sink = DryRunSink::new(Vec::new(), Some(2));
+        let start = Instant::now();
+
+        for frame in 0..4 {
+            led_strip.set_led(0, frame * 0x10);
+            sink.record(
+                start + Duration::from_millis(u64::from(frame)),
+                led_strip.get_spi_data(),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "frame 1: #000000 #000000\nframe 3: #000020 #000000\n"
+        );
+    }
+}
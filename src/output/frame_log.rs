@@ -0,0 +1,313 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+const LOG_MAGIC: [u8; 4] = *b"AGFL";
+const LOG_VERSION: u16 = 1;
+const HEADER_LEN: usize = 8;
+const TIMESTAMP_LEN: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoggedFrame {
+    pub timestamp: Duration,
+    pub colors: Vec<(u8, u8, u8)>,
+}
+
+pub struct FrameLogSink<W: Write> {
+    writer: W,
+    led_count: Option<u16>,
+    started: Option<Instant>,
+}
+
+impl FrameLogSink<BufWriter<File>> {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(FrameLogSink::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> FrameLogSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            led_count: None,
+            started: None,
+        }
+    }
+
+    fn record(&mut self, timestamp: Instant, colors: &[(u8, u8, u8)]) -> io::Result<()> {
+        let led_count: u16 = colors
+            .len()
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Too many LEDs to log"))?;
+
+        match self.led_count {
+            Some(expected) if expected != led_count => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Expected {} LEDs per frame, got {}", expected, led_count),
+                ));
+            }
+            Some(_) => {}
+            None => {
+                self.writer.write_all(&LOG_MAGIC)?;
+                self.writer.write_all(&LOG_VERSION.to_be_bytes())?;
+                self.writer.write_all(&led_count.to_be_bytes())?;
+                self.led_count = Some(led_count);
+            }
+        }
+
+        let started = *self.started.get_or_insert(timestamp);
+        let timestamp_us = timestamp.duration_since(started).as_micros() as u64;
+
+        let mut frame = Vec::with_capacity(TIMESTAMP_LEN + colors.len() * 3);
+        frame.extend(timestamp_us.to_be_bytes());
+        for &(r, g, b) in colors {
+            frame.extend([r, g, b]);
+        }
+        self.writer.write_all(&frame)?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> OutputSink for FrameLogSink<W> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.record(Instant::now(), &decode_spi_data(spi_data))
+    }
+}
+
+pub struct FrameLogReader<R: Read> {
+    reader: R,
+    led_count: usize,
+    last_timestamp: Duration,
+}
+
+impl FrameLogReader<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        FrameLogReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> FrameLogReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+
+        if header[0..4] != LOG_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an afterglow frame log",
+            ));
+        }
+        let version = u16::from_be_bytes(header[4..6].try_into().unwrap());
+        if version != LOG_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported frame log version {}", version),
+            ));
+        }
+
+        Ok(Self {
+            reader,
+            led_count: usize::from(u16::from_be_bytes(header[6..8].try_into().unwrap())),
+            last_timestamp: Duration::ZERO,
+        })
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.led_count
+    }
+
+    pub fn next_frame(&mut self) -> io::Result<Option<LoggedFrame>> {
+        let mut frame = vec![0; TIMESTAMP_LEN + self.led_count * 3];
+        loop {
+            match self.reader.read_exact(&mut frame) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err),
+            }
+
+            let timestamp = Duration::from_micros(u64::from_be_bytes(
+                frame[..TIMESTAMP_LEN].try_into().unwrap(),
+            ));
+            if timestamp < self.last_timestamp {
+                warn!(
+                    "Skipping frame at {:?} logged before the previous frame",
+                    timestamp
+                );
+                continue;
+            }
+            self.last_timestamp = timestamp;
+
+            return Ok(Some(LoggedFrame {
+                timestamp,
+                colors: frame[TIMESTAMP_LEN..]
+                    .chunks_exact(3)
+                    .map(|color| (color[0], color[1], color[2]))
+                    .collect(),
+            }));
+        }
+    }
+}
+
+pub fn replay_frames<R: Read>(
+    log: &mut FrameLogReader<R>,
+    speed: f64,
+    mut play: impl FnMut(&[(u8, u8, u8)]) -> io::Result<()>,
+    mut sleep: impl FnMut(Duration),
+) -> io::Result<usize> {
+    assert!(speed > 0.0, "Replay speed must be positive");
+
+    let mut previous: Option<Duration> = None;
+    let mut frames = 0;
+    while let Some(frame) = log.next_frame()? {
+        if let Some(previous) = previous {
+            sleep((frame.timestamp - previous).div_f64(speed));
+        }
+        previous = Some(frame.timestamp);
+
+        play(&frame.colors)?;
+        frames += 1;
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::frame_log::{
+        replay_frames, FrameLogReader, FrameLogSink, LoggedFrame, HEADER_LEN,
+    };
+    use std::{
+        io,
+        time::{Duration, Instant},
+    };
+
+    type TimedColors = (u64, Vec<(u8, u8, u8)>);
+
+    fn logged(frames: &[TimedColors]) -> Vec<u8> {
+        let mut sink = FrameLogSink::new(Vec::new());
+        let start = Instant::now();
+        for (timestamp_ms, colors) in frames {
+            sink.record(start + Duration::from_millis(*timestamp_ms), colors)
+                .unwrap();
+        }
+        sink.writer
+    }
+
+    #[test]
+    fn it_writes_a_header_and_timestamped_frames() {
+        assert_eq!(
+            logged(&[(0, vec![(0xff, 0x00, 0x00)]), (5, vec![(0x4b, 0x80, 0x40)])]),
+            vec![
+                b'A', b'G', b'F', b'L', // Magic
+                0x00, 0x01, // Version
+                0x00, 0x01, // LED count
+                0, 0, 0, 0, 0, 0, 0x00, 0x00, // Timestamp
+                0xff, 0x00, 0x00, // LED 0
+                0, 0, 0, 0, 0, 0, 0x13, 0x88, // Timestamp
+                0x4b, 0x80, 0x40, // LED 0
+            ]
+        );
+    }
+
+    #[test]
+    fn it_round_trips_frames() {
+        let frames = vec![
+            (0, vec![(1, 2, 3), (4, 5, 6)]),
+            (33, vec![(7, 8, 9), (10, 11, 12)]),
+            (67, vec![(13, 14, 15), (16, 17, 18)]),
+        ];
+        let data = logged(&frames);
+
+        let mut log = FrameLogReader::new(data.as_slice()).unwrap();
+        assert_eq!(log.led_count(), 2);
+        for (timestamp_ms, colors) in frames {
+            assert_eq!(
+                log.next_frame().unwrap(),
+                Some(LoggedFrame {
+                    timestamp: Duration::from_millis(timestamp_ms),
+                    colors,
+                })
+            );
+        }
+        assert_eq!(log.next_frame().unwrap(), None);
+    }
+
+    #[test]
+    fn it_rejects_logs_with_a_bad_header() {
+        let mut data = logged(&[(0, vec![(1, 2, 3)])]);
+        data[0] = b'X';
+        assert_eq!(
+            FrameLogReader::new(data.as_slice()).err().unwrap().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(FrameLogReader::new(&data[..HEADER_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn it_skips_truncated_and_out_of_order_frames() {
+        let mut data = logged(&[
+            (0, vec![(1, 2, 3)]),
+            (20, vec![(4, 5, 6)]),
+            (30, vec![(7, 8, 9)]),
+            (40, vec![(10, 11, 12)]),
+        ]);
+        data[HEADER_LEN + 2 * 11 + 6] = 0x00;
+        data.truncate(data.len() - 2);
+
+        let mut log = FrameLogReader::new(data.as_slice()).unwrap();
+        assert_eq!(log.next_frame().unwrap().unwrap().colors, vec![(1, 2, 3)]);
+        assert_eq!(log.next_frame().unwrap().unwrap().colors, vec![(4, 5, 6)]);
+        assert_eq!(log.next_frame().unwrap(), None);
+    }
+
+    #[test]
+    fn it_scales_replay_speed() {
+        let data = logged(&[
+            (0, vec![(1, 2, 3)]),
+            (40, vec![(4, 5, 6)]),
+            (100, vec![(7, 8, 9)]),
+        ]);
+
+        let replay = |speed: f64| {
+            let mut log = FrameLogReader::new(data.as_slice()).unwrap();
+            let mut played = Vec::new();
+            let mut sleeps = Vec::new();
+            let frames = replay_frames(
+                &mut log,
+                speed,
+                |colors| {
+                    played.push(colors.to_vec());
+                    Ok(())
+                },
+                |delay| sleeps.push(delay),
+            )
+            .unwrap();
+            assert_eq!(frames, 3);
+            assert_eq!(
+                played,
+                vec![vec![(1, 2, 3)], vec![(4, 5, 6)], vec![(7, 8, 9)]]
+            );
+            sleeps
+        };
+
+        assert_eq!(
+            replay(1.0),
+            vec![Duration::from_millis(40), Duration::from_millis(60)]
+        );
+        assert_eq!(
+            replay(2.0),
+            vec![Duration::from_millis(20), Duration::from_millis(30)]
+        );
+        assert_eq!(
+            replay(0.5),
+            vec![Duration::from_millis(80), Duration::from_millis(120)]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use gif::{Encoder, Frame, Repeat};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    mem,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const FLUSH_INTERVAL_FRAMES: usize = 30;
+const LAST_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+type TimestampedColors = (Instant, Vec<(u8, u8, u8)>);
+
+enum Recording<W: Write> {
+    Pending(W),
+    Encoding(Encoder<W>),
+    Finished,
+}
+
+pub struct GifSink<W: Write> {
+    recording: Recording<W>,
+    pixel_size: u16,
+    duration: Option<Duration>,
+    started: Option<Instant>,
+    pending_frame: Option<TimestampedColors>,
+    frames_since_flush: usize,
+}
+
+impl GifSink<BufWriter<File>> {
+    pub fn create(path: &Path, pixel_size: u16, duration: Option<Duration>) -> io::Result<Self> {
+        Ok(GifSink::new(
+            BufWriter::new(File::create(path)?),
+            pixel_size,
+            duration,
+        ))
+    }
+}
+
+impl<W: Write> GifSink<W> {
+    pub fn new(writer: W, pixel_size: u16, duration: Option<Duration>) -> Self {
+        assert!(pixel_size > 0, "GIF pixel size must be at least 1");
+
+        Self {
+            recording: Recording::Pending(writer),
+            pixel_size,
+            duration,
+            started: None,
+            pending_frame: None,
+            frames_since_flush: 0,
+        }
+    }
+
+    fn record(&mut self, timestamp: Instant, colors: Vec<(u8, u8, u8)>) -> io::Result<()> {
+        if let Recording::Finished = self.recording {
+            return Ok(());
+        }
+
+        let started = *self.started.get_or_insert(timestamp);
+        if let Some(duration) = self.duration {
+            if timestamp.duration_since(started) >= duration {
+                self.finish_recording()?;
+                return Ok(());
+            }
+        }
+
+        if let Some((previous_timestamp, previous_colors)) =
+            self.pending_frame.replace((timestamp, colors))
+        {
+            self.encode_frame(
+                &previous_colors,
+                timestamp.duration_since(previous_timestamp),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_frame(&mut self, colors: &[(u8, u8, u8)], delay: Duration) -> io::Result<()> {
+        let pixel_size = usize::from(self.pixel_size);
+        let width: u16 = (colors.len() * pixel_size)
+            .try_into()
+            .map_err(|_| io::Error::other("Too many LEDs to fit in a GIF frame"))?;
+        let height = self.pixel_size;
+
+        self.recording = match mem::replace(&mut self.recording, Recording::Finished) {
+            Recording::Pending(writer) => {
+                let mut encoder =
+                    Encoder::new(writer, width, height, &[]).map_err(io::Error::other)?;
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(io::Error::other)?;
+                Recording::Encoding(encoder)
+            }
+            recording => recording,
+        };
+
+        let Recording::Encoding(encoder) = &mut self.recording else {
+            return Ok(());
+        };
+
+        let row: Vec<u8> = colors
+            .iter()
+            .flat_map(|&(r, g, b)| [r, g, b].repeat(pixel_size))
+            .collect();
+        let mut frame = Frame::from_rgb_speed(width, height, &row.repeat(pixel_size), 10);
+        frame.delay = (delay.as_millis() / 10).clamp(1, u16::MAX.into()) as u16;
+        encoder.write_frame(&frame).map_err(io::Error::other)?;
+
+        self.frames_since_flush += 1;
+        if self.frames_since_flush >= FLUSH_INTERVAL_FRAMES {
+            encoder.get_mut().flush()?;
+            self.frames_since_flush = 0;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish_recording(&mut self) -> io::Result<Option<W>> {
+        if let Some((_, colors)) = self.pending_frame.take() {
+            self.encode_frame(&colors, LAST_FRAME_DELAY)?;
+        }
+
+        match mem::replace(&mut self.recording, Recording::Finished) {
+            Recording::Pending(writer) => Ok(Some(writer)),
+            Recording::Encoding(encoder) => {
+                let mut writer = encoder.into_inner()?;
+                writer.flush()?;
+                Ok(Some(writer))
+            }
+            Recording::Finished => Ok(None),
+        }
+    }
+}
+
+impl<W: Write> OutputSink for GifSink<W> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.record(Instant::now(), decode_spi_data(spi_data))
+    }
+}
+
+impl<W: Write> Drop for GifSink<W> {
+    fn drop(&mut self) {
+        self.finish_recording().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::gif::GifSink;
+    use gif::DecodeOptions;
+    use std::time::{Duration, Instant};
+
+    fn decode_frames(data: &[u8]) -> (u16, u16, Vec<(u16, Vec<u8>)>) {
+        let mut options = DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(data).unwrap();
+        let (width, height) = (decoder.width(), decoder.height());
+
+        let mut frames = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames.push((frame.delay, frame.buffer.to_vec()));
+        }
+
+        (width, height, frames)
+    }
+
+    #[test]
+    fn it_renders_leds_as_squares_with_measured_delays() {
+        let mut sink = GifSink::new(Vec::new(), 2, None);
+        let start = Instant::now();
+
+        sink.record(start, vec![(255, 0, 0), (0, 0, 255)]).unwrap();
+        sink.record(
+            start + Duration::from_millis(50),
+            vec![(0, 255, 0), (0, 0, 0)],
+        )
+        .unwrap();
+        sink.record(
+            start + Duration::from_millis(250),
+            vec![(0, 0, 0), (0, 0, 0)],
+        )
+        .unwrap();
+
+        let data = sink.finish_recording().unwrap().unwrap();
+        let (width, height, frames) = decode_frames(&data);
+
+        assert_eq!((width, height), (4, 2));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            frames.iter().map(|(delay, _)| *delay).collect::<Vec<_>>(),
+            vec![5, 20, 10]
+        );
+
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let first_row = [red, red, blue, blue].concat();
+        assert_eq!(frames[0].1, [first_row.clone(), first_row].concat());
+    }
+
+    #[test]
+    fn it_stops_recording_after_the_duration() {
+        let mut sink = GifSink::new(Vec::new(), 1, Some(Duration::from_secs(1)));
+        let start = Instant::now();
+
+        for index in 0..20 {
+            sink.record(
+                start + Duration::from_millis(index * 100),
+                vec![(255, 255, 255)],
+            )
+            .unwrap();
+        }
+
+        assert!(sink.finish_recording().unwrap().is_none());
+    }
+
+    #[test]
+    fn it_writes_the_recording_when_the_duration_elapses() {
+        let mut sink = GifSink::new(Vec::new(), 1, Some(Duration::from_secs(1)));
+        let start = Instant::now();
+
+        sink.record(start, vec![(255, 255, 255)]).unwrap();
+        sink.record(start + Duration::from_millis(500), vec![(0, 0, 0)])
+            .unwrap();
+
+        let data = sink.finish_recording().unwrap().unwrap();
+        let (_, _, frames) = decode_frames(&data);
+        assert_eq!(frames.len(), 2);
+    }
+}
@@ -0,0 +1,425 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use flatbuffers::{
+    FlatBufferBuilder, Follow, ForwardsUOffset, InvalidFlatbuffer, Table, Verifiable, Verifier,
+    WIPOffset,
+};
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const IO_TIMEOUT: Duration = Duration::from_secs(1);
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// Field and union offsets from Hyperion's hyperion_request.fbs and hyperion_reply.fbs
+const COMMAND_IMAGE: u8 = 2;
+const COMMAND_CLEAR: u8 = 3;
+const COMMAND_REGISTER: u8 = 4;
+const IMAGE_TYPE_RAW_IMAGE: u8 = 1;
+
+const REQUEST_VT_COMMAND_TYPE: u16 = 4;
+const REQUEST_VT_COMMAND: u16 = 6;
+const RAW_IMAGE_VT_DATA: u16 = 4;
+const RAW_IMAGE_VT_WIDTH: u16 = 6;
+const RAW_IMAGE_VT_HEIGHT: u16 = 8;
+const IMAGE_VT_DATA_TYPE: u16 = 4;
+const IMAGE_VT_DATA: u16 = 6;
+const IMAGE_VT_DURATION: u16 = 8;
+const CLEAR_VT_PRIORITY: u16 = 4;
+const REGISTER_VT_ORIGIN: u16 = 4;
+const REGISTER_VT_PRIORITY: u16 = 6;
+const REPLY_VT_ERROR: u16 = 4;
+
+fn finish_request(
+    mut builder: FlatBufferBuilder,
+    command_type: u8,
+    command: WIPOffset<flatbuffers::UnionWIPOffset>,
+) -> Vec<u8> {
+    let start = builder.start_table();
+    builder.push_slot_always(REQUEST_VT_COMMAND, command);
+    builder.push_slot::<u8>(REQUEST_VT_COMMAND_TYPE, command_type, 0);
+    let request = builder.end_table(start);
+    builder.finish(request, None);
+
+    let data = builder.finished_data();
+    let mut message = Vec::with_capacity(data.len() + 4);
+    message.extend((data.len() as u32).to_be_bytes());
+    message.extend(data);
+    message
+}
+
+fn register_message(origin: &str, priority: i32) -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::new();
+    let origin = builder.create_string(origin);
+
+    let start = builder.start_table();
+    builder.push_slot_always(REGISTER_VT_ORIGIN, origin);
+    builder.push_slot::<i32>(REGISTER_VT_PRIORITY, priority, 0);
+    let register = builder.end_table(start);
+
+    finish_request(builder, COMMAND_REGISTER, register.as_union_value())
+}
+
+fn image_message(colors: &[(u8, u8, u8)]) -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::new();
+    let rgb: Vec<u8> = colors.iter().flat_map(|&(r, g, b)| [r, g, b]).collect();
+    let data = builder.create_vector(&rgb);
+
+    let start = builder.start_table();
+    builder.push_slot_always(RAW_IMAGE_VT_DATA, data);
+    builder.push_slot::<i32>(RAW_IMAGE_VT_WIDTH, colors.len() as i32, -1);
+    builder.push_slot::<i32>(RAW_IMAGE_VT_HEIGHT, 1, -1);
+    let raw_image = builder.end_table(start);
+
+    let start = builder.start_table();
+    builder.push_slot_always(IMAGE_VT_DATA, raw_image.as_union_value());
+    builder.push_slot::<i32>(IMAGE_VT_DURATION, -1, -1);
+    builder.push_slot::<u8>(IMAGE_VT_DATA_TYPE, IMAGE_TYPE_RAW_IMAGE, 0);
+    let image = builder.end_table(start);
+
+    finish_request(builder, COMMAND_IMAGE, image.as_union_value())
+}
+
+fn clear_message(priority: i32) -> Vec<u8> {
+    let mut builder = FlatBufferBuilder::new();
+
+    let start = builder.start_table();
+    builder.push_slot::<i32>(CLEAR_VT_PRIORITY, priority, 0);
+    let clear = builder.end_table(start);
+
+    finish_request(builder, COMMAND_CLEAR, clear.as_union_value())
+}
+
+struct Reply<'a> {
+    table: Table<'a>,
+}
+
+impl<'a> Follow<'a> for Reply<'a> {
+    type Inner = Reply<'a>;
+
+    unsafe fn follow(buf: &'a [u8], loc: usize) -> Self::Inner {
+        Reply {
+            table: Table::new(buf, loc),
+        }
+    }
+}
+
+impl Verifiable for Reply<'_> {
+    fn run_verifier(verifier: &mut Verifier, pos: usize) -> Result<(), InvalidFlatbuffer> {
+        verifier
+            .visit_table(pos)?
+            .visit_field::<ForwardsUOffset<&str>>("error", REPLY_VT_ERROR, false)?
+            .finish();
+        Ok(())
+    }
+}
+
+impl<'a> Reply<'a> {
+    fn error(&self) -> Option<&'a str> {
+        // SAFETY: the reply was checked by the verifier in `flatbuffers::root`
+        unsafe {
+            self.table
+                .get::<ForwardsUOffset<&str>>(REPLY_VT_ERROR, None)
+        }
+    }
+}
+
+fn parse_reply_error(data: &[u8]) -> io::Result<Option<String>> {
+    let reply = flatbuffers::root::<Reply>(data)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(reply.error().map(str::to_string))
+}
+
+pub struct HyperionSink {
+    target: String,
+    origin: String,
+    priority: i32,
+    stream: Option<TcpStream>,
+    read_buffer: Vec<u8>,
+    backoff: Duration,
+    next_attempt: Instant,
+}
+
+impl HyperionSink {
+    pub fn new(target: &str, origin: &str, priority: i32) -> Self {
+        Self {
+            target: target.to_string(),
+            origin: origin.to_string(),
+            priority,
+            stream: None,
+            read_buffer: Vec::new(),
+            backoff: MIN_BACKOFF,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let addr = self.target.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Unable to resolve {}", self.target),
+            )
+        })?;
+
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+
+        stream.write_all(&register_message(&self.origin, self.priority))?;
+        let reply = read_message(&mut stream)?;
+        if let Some(error) = parse_reply_error(&reply)? {
+            return Err(io::Error::other(format!(
+                "Hyperion rejected registration: {}",
+                error
+            )));
+        }
+
+        Ok(stream)
+    }
+
+    fn disconnect(&mut self, err: io::Error) {
+        warn!(
+            "Hyperion output to {} unavailable, retrying in {:?}: {}",
+            self.target, self.backoff, err
+        );
+        self.stream = None;
+        self.read_buffer.clear();
+        self.next_attempt = Instant::now() + self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+
+    fn drain_replies(&mut self) -> io::Result<()> {
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+
+        stream.set_nonblocking(true)?;
+        let mut chunk = [0; 1024];
+        let result = loop {
+            match stream.read(&mut chunk) {
+                Ok(0) => break Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(len) => self.read_buffer.extend(&chunk[..len]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        stream.set_nonblocking(false)?;
+        result?;
+
+        while self.read_buffer.len() >= 4 {
+            let len = u32::from_be_bytes(self.read_buffer[..4].try_into().unwrap()) as usize;
+            if self.read_buffer.len() < len + 4 {
+                break;
+            }
+
+            let reply: Vec<u8> = self.read_buffer.drain(..len + 4).skip(4).collect();
+            if let Some(error) = parse_reply_error(&reply)? {
+                warn!("Hyperion reported an error: {}", error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn read_message(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+
+    let mut message = vec![0; u32::from_be_bytes(len) as usize];
+    reader.read_exact(&mut message)?;
+    Ok(message)
+}
+
+impl OutputSink for HyperionSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        if self.stream.is_none() {
+            if Instant::now() < self.next_attempt {
+                return Ok(());
+            }
+
+            match self.connect() {
+                Ok(stream) => {
+                    self.stream = Some(stream);
+                    self.backoff = MIN_BACKOFF;
+                }
+                Err(err) => {
+                    self.disconnect(err);
+                    return Ok(());
+                }
+            }
+        }
+
+        let message = image_message(&decode_spi_data(spi_data));
+        let result = match self.stream.as_mut() {
+            Some(stream) => stream.write_all(&message),
+            None => Ok(()),
+        }
+        .and_then(|_| self.drain_replies());
+
+        if let Err(err) = result {
+            self.disconnect(err);
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for HyperionSink {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.as_mut() {
+            stream.write_all(&clear_message(self.priority)).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::hyperion::{
+        read_message, HyperionSink, COMMAND_CLEAR, COMMAND_IMAGE, COMMAND_REGISTER,
+    };
+    use crate::output::OutputSink;
+    use afterglow::led::LEDStrip;
+    use flatbuffers::FlatBufferBuilder;
+    use std::{
+        io::Write,
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    struct TableReader<'a> {
+        buf: &'a [u8],
+        pos: usize,
+        vtable: usize,
+    }
+
+    impl<'a> TableReader<'a> {
+        fn root(buf: &'a [u8]) -> Self {
+            TableReader::at(
+                buf,
+                u32::from_le_bytes(buf[0..4].try_into().unwrap()) as usize,
+            )
+        }
+
+        fn at(buf: &'a [u8], pos: usize) -> Self {
+            let soffset = i32::from_le_bytes(buf[pos..pos + 4].try_into().unwrap());
+            let vtable = (pos as i64 - i64::from(soffset)) as usize;
+            TableReader { buf, pos, vtable }
+        }
+
+        fn field(&self, vt: u16) -> Option<usize> {
+            let vtable_len =
+                u16::from_le_bytes(self.buf[self.vtable..self.vtable + 2].try_into().unwrap());
+            if vt >= vtable_len {
+                return None;
+            }
+
+            let offset_pos = self.vtable + usize::from(vt);
+            match u16::from_le_bytes(self.buf[offset_pos..offset_pos + 2].try_into().unwrap()) {
+                0 => None,
+                offset => Some(self.pos + usize::from(offset)),
+            }
+        }
+
+        fn u8(&self, vt: u16) -> Option<u8> {
+            self.field(vt).map(|pos| self.buf[pos])
+        }
+
+        fn i32(&self, vt: u16) -> Option<i32> {
+            self.field(vt)
+                .map(|pos| i32::from_le_bytes(self.buf[pos..pos + 4].try_into().unwrap()))
+        }
+
+        fn indirect(&self, vt: u16) -> usize {
+            let pos = self.field(vt).unwrap();
+            pos + u32::from_le_bytes(self.buf[pos..pos + 4].try_into().unwrap()) as usize
+        }
+
+        fn table(&self, vt: u16) -> TableReader<'a> {
+            TableReader::at(self.buf, self.indirect(vt))
+        }
+
+        fn bytes(&self, vt: u16) -> &'a [u8] {
+            let pos = self.indirect(vt);
+            let len = u32::from_le_bytes(self.buf[pos..pos + 4].try_into().unwrap()) as usize;
+            &self.buf[pos + 4..pos + 4 + len]
+        }
+    }
+
+    fn success_reply() -> Vec<u8> {
+        let mut builder = FlatBufferBuilder::new();
+        let start = builder.start_table();
+        let reply = builder.end_table(start);
+        builder.finish(reply, None);
+
+        let data = builder.finished_data();
+        [(data.len() as u32).to_be_bytes().to_vec(), data.to_vec()].concat()
+    }
+
+    #[test]
+    fn it_registers_sends_colors_and_clears_with_a_stub_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _): (TcpStream, _) = listener.accept().unwrap();
+            let mut messages = Vec::new();
+            while let Ok(message) = read_message(&mut stream) {
+                messages.push(message);
+                if stream.write_all(&success_reply()).is_err() {
+                    break;
+                }
+            }
+            messages
+        });
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x4b8040]);
+        let mut sink = HyperionSink::new(&target, "afterglow-test", 150);
+        sink.write(led_strip.get_spi_data()).unwrap();
+        drop(sink);
+
+        let messages = server.join().unwrap();
+        assert_eq!(messages.len(), 3);
+
+        let register = TableReader::root(&messages[0]);
+        assert_eq!(register.u8(4), Some(COMMAND_REGISTER));
+        let register = register.table(6);
+        assert_eq!(register.bytes(4), b"afterglow-test");
+        assert_eq!(register.i32(6), Some(150));
+
+        let image = TableReader::root(&messages[1]);
+        assert_eq!(image.u8(4), Some(COMMAND_IMAGE));
+        let image = image.table(6);
+        assert_eq!(image.u8(4), Some(1));
+        let raw_image = image.table(6);
+        assert_eq!(
+            raw_image.bytes(4),
+            &[0xff, 0x00, 0x00, 0x00, 0xff, 0x00, 0x4b, 0x80, 0x40]
+        );
+        assert_eq!(raw_image.i32(6), Some(3));
+        assert_eq!(raw_image.i32(8), Some(1));
+
+        let clear = TableReader::root(&messages[2]);
+        assert_eq!(clear.u8(4), Some(COMMAND_CLEAR));
+        assert_eq!(clear.table(6).i32(4), Some(150));
+    }
+
+    #[test]
+    fn it_backs_off_when_hyperion_is_unavailable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let led_strip = LEDStrip::new_with_data([0x4b8040]);
+        let mut sink = HyperionSink::new(&target, "afterglow", 150);
+        assert!(sink.write(led_strip.get_spi_data()).is_ok());
+        assert!(sink.write(led_strip.get_spi_data()).is_ok());
+        assert_eq!(sink.backoff, super::MIN_BACKOFF * 2);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use rppal::i2c::{self, I2c};
+use std::io;
+
+const I2C_WRITE_LIMIT: usize = 32;
+const MAX_I2C_ADDRESS: u16 = 0x77;
+
+pub trait I2cDevice {
+    fn set_slave_address(&mut self, address: u16) -> io::Result<()>;
+    fn write(&mut self, data: &[u8]) -> io::Result<()>;
+}
+
+fn to_io_error(err: i2c::Error) -> io::Error {
+    match err {
+        i2c::Error::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+impl I2cDevice for I2c {
+    fn set_slave_address(&mut self, address: u16) -> io::Result<()> {
+        I2c::set_slave_address(self, address).map_err(to_io_error)
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let written = I2c::write(self, data).map_err(to_io_error)?;
+        if written < data.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                format!("wrote {} of {} bytes", written, data.len()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+pub struct I2cOutputSink<D> {
+    i2c: D,
+    base_address: u16,
+    bytes_per_device: usize,
+}
+
+impl I2cOutputSink<I2c> {
+    pub fn open(bus: u8, base_address: u16, leds_per_device: usize) -> io::Result<Self> {
+        I2c::with_bus(bus)
+            .map(|i2c| I2cOutputSink::new(i2c, base_address, leds_per_device))
+            .map_err(to_io_error)
+    }
+}
+
+impl<D: I2cDevice> I2cOutputSink<D> {
+    pub fn new(i2c: D, base_address: u16, leds_per_device: usize) -> Self {
+        assert!(
+            leds_per_device > 0,
+            "I2C devices must accept at least 1 LED"
+        );
+
+        Self {
+            i2c,
+            base_address,
+            bytes_per_device: leds_per_device * 3,
+        }
+    }
+}
+
+impl<D: I2cDevice> OutputSink for I2cOutputSink<D> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let data: Vec<u8> = decode_spi_data(spi_data)
+            .into_iter()
+            .flat_map(|(r, g, b)| [r, g, b])
+            .collect();
+
+        for (device, device_data) in data.chunks(self.bytes_per_device).enumerate() {
+            let address = u16::try_from(device)
+                .ok()
+                .and_then(|device| self.base_address.checked_add(device))
+                .filter(|&address| address <= MAX_I2C_ADDRESS)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("no I2C address left for device {}", device),
+                    )
+                })?;
+
+            self.i2c.set_slave_address(address)?;
+            for chunk in device_data.chunks(I2C_WRITE_LIMIT) {
+                self.i2c.write(chunk)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::i2c::{I2cDevice, I2cOutputSink};
+    use crate::output::OutputSink;
+    use afterglow::led::LEDStrip;
+    use std::io;
+
+    #[derive(Default)]
+    struct MockI2c {
+        address: u16,
+        writes: Vec<(u16, Vec<u8>)>,
+    }
+
+    impl I2cDevice for MockI2c {
+        fn set_slave_address(&mut self, address: u16) -> io::Result<()> {
+            self.address = address;
+            Ok(())
+        }
+
+        fn write(&mut self, data: &[u8]) -> io::Result<()> {
+            self.writes.push((self.address, data.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_writes_rgb_bytes_to_a_single_device() {
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x4b8040]);
+        let mut sink = I2cOutputSink::new(MockI2c::default(), 0x40, 5);
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            sink.i2c.writes,
+            vec![(0x40, vec![0xff, 0x00, 0x00, 0x4b, 0x80, 0x40])]
+        );
+    }
+
+    #[test]
+    fn it_chunks_writes_to_the_i2c_write_limit() {
+        let led_strip: LEDStrip<12> = LEDStrip::new_with_data([0x010203; 12]);
+        let mut sink = I2cOutputSink::new(MockI2c::default(), 0x40, 12);
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        let lengths: Vec<(u16, usize)> = sink
+            .i2c
+            .writes
+            .iter()
+            .map(|(address, data)| (*address, data.len()))
+            .collect();
+        assert_eq!(lengths, vec![(0x40, 32), (0x40, 4)]);
+    }
+
+    #[test]
+    fn it_spreads_leds_across_sequential_devices() {
+        let led_strip = LEDStrip::new_with_data([0x000001, 0x000002, 0x000003, 0x000004, 0x000005]);
+        let mut sink = I2cOutputSink::new(MockI2c::default(), 0x40, 2);
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            sink.i2c.writes,
+            vec![
+                (0x40, vec![0x00, 0x00, 0x01, 0x00, 0x00, 0x02]),
+                (0x41, vec![0x00, 0x00, 0x03, 0x00, 0x00, 0x04]),
+                (0x42, vec![0x00, 0x00, 0x05]),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_fails_when_devices_run_past_the_last_address() {
+        let led_strip = LEDStrip::new_with_data([0x000001, 0x000002]);
+        let mut sink = I2cOutputSink::new(MockI2c::default(), 0x77, 1);
+
+        assert!(sink.write(led_strip.get_spi_data()).is_err());
+        assert_eq!(sink.i2c.writes, vec![(0x77, vec![0x00, 0x00, 0x01])]);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::output::OutputSink;
+use std::{
+    io,
+    sync::{Arc, Mutex, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+struct KeepAliveState<S> {
+    sink: S,
+    interval: Duration,
+    last_frame: Option<Vec<u8>>,
+    last_write: Instant,
+}
+
+impl<S: OutputSink> KeepAliveState<S> {
+    fn write(&mut self, timestamp: Instant, spi_data: &[u8]) -> io::Result<()> {
+        let result = self.sink.write(spi_data);
+        self.last_frame = Some(spi_data.to_vec());
+        self.last_write = timestamp;
+
+        result
+    }
+
+    fn refresh(&mut self, timestamp: Instant) -> io::Result<Duration> {
+        let due = self.last_write + self.interval;
+        if timestamp < due {
+            return Ok(due - timestamp);
+        }
+
+        self.last_write = timestamp;
+        if let Some(frame) = &self.last_frame {
+            self.sink.write(frame)?;
+        }
+
+        Ok(self.interval)
+    }
+}
+
+pub struct KeepAliveSink<S> {
+    state: Arc<Mutex<KeepAliveState<S>>>,
+}
+
+impl<S: OutputSink + Send + 'static> KeepAliveSink<S> {
+    pub fn new(sink: S, interval: Duration) -> Self {
+        assert!(!interval.is_zero(), "Keep-alive interval must be positive");
+
+        let state = Arc::new(Mutex::new(KeepAliveState {
+            sink,
+            interval,
+            last_frame: None,
+            last_write: Instant::now(),
+        }));
+
+        let weak_state = Arc::downgrade(&state);
+        thread::spawn(move || keep_alive(weak_state, interval));
+
+        Self { state }
+    }
+}
+
+fn keep_alive<S: OutputSink>(state: Weak<Mutex<KeepAliveState<S>>>, interval: Duration) {
+    let mut delay = interval;
+    loop {
+        thread::sleep(delay);
+
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        let mut state = state.lock().unwrap();
+        delay = state.refresh(Instant::now()).unwrap_or_else(|err| {
+            warn!("Failed to refresh LED data: {}", err);
+            interval
+        });
+    }
+}
+
+impl<S: OutputSink> OutputSink for KeepAliveSink<S> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.state.lock().unwrap().write(Instant::now(), spi_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::keep_alive::KeepAliveSink;
+    use crate::output::VecSink;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn it_refreshes_only_once_the_interval_has_passed() {
+        let sink = KeepAliveSink::new(VecSink::default(), Duration::from_secs(60));
+        let mut state = sink.state.lock().unwrap();
+        let start = Instant::now();
+
+        assert_eq!(
+            state.refresh(start + Duration::from_secs(60)).unwrap(),
+            Duration::from_secs(60)
+        );
+        assert!(state.sink.frames.is_empty());
+
+        state.write(start, &[0x00, 0x01]).unwrap();
+        assert_eq!(
+            state.refresh(start + Duration::from_secs(45)).unwrap(),
+            Duration::from_secs(15)
+        );
+        assert_eq!(
+            state.refresh(start + Duration::from_secs(60)).unwrap(),
+            Duration::from_secs(60)
+        );
+        assert_eq!(state.sink.frames, vec![vec![0x00, 0x01]; 2]);
+    }
+
+    #[test]
+    fn it_repeats_the_last_frame_while_the_source_is_stalled() {
+        let sink = KeepAliveSink::new(VecSink::default(), Duration::from_secs(60));
+        let mut state = sink.state.lock().unwrap();
+        let start = Instant::now();
+        state.write(start, &[0x00, 0x01]).unwrap();
+        state.write(start, &[0x02, 0x03]).unwrap();
+
+        // Steps a clock the way the timer thread does, waking up whenever
+        // the previous refresh said the next one is due
+        let mut timestamp = start;
+        for _ in 0..4 {
+            timestamp += state.refresh(timestamp).unwrap();
+        }
+
+        assert_eq!(timestamp, start + Duration::from_secs(240));
+        assert_eq!(
+            state.sink.frames,
+            [vec![vec![0x00, 0x01]], vec![vec![0x02, 0x03]; 4]].concat()
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::output::OutputSink;
+use std::{
+    io::{self, Write},
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedFrame {
+    pub elapsed: Duration,
+    pub spi_data: Vec<u8>,
+}
+
+pub struct MockSpiSink<W: Write> {
+    writer: W,
+    keep_frames: bool,
+    frames: Vec<RecordedFrame>,
+    frame_rate_bounds: Option<RangeInclusive<f32>>,
+    started: Option<Instant>,
+    last_write: Option<Instant>,
+}
+
+impl MockSpiSink<io::Sink> {
+    #[allow(dead_code)]
+    pub fn in_memory() -> Self {
+        Self {
+            keep_frames: true,
+            ..MockSpiSink::new(io::sink())
+        }
+    }
+}
+
+impl<W: Write> MockSpiSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            keep_frames: false,
+            frames: Vec::new(),
+            frame_rate_bounds: None,
+            started: None,
+            last_write: None,
+        }
+    }
+
+    pub fn with_frame_rate_bounds(mut self, bounds: RangeInclusive<f32>) -> Self {
+        self.frame_rate_bounds = Some(bounds);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn frames(&self) -> &[RecordedFrame] {
+        &self.frames
+    }
+
+    fn record(&mut self, timestamp: Instant, spi_data: &[u8]) -> io::Result<()> {
+        let elapsed = timestamp.duration_since(*self.started.get_or_insert(timestamp));
+        let interval = self
+            .last_write
+            .replace(timestamp)
+            .map(|last_write| timestamp.duration_since(last_write));
+
+        write!(self.writer, "{}", elapsed.as_micros())?;
+        for byte in spi_data {
+            write!(self.writer, " {:02x}", byte)?;
+        }
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+
+        if self.keep_frames {
+            self.frames.push(RecordedFrame {
+                elapsed,
+                spi_data: spi_data.to_vec(),
+            });
+        }
+
+        match (interval, &self.frame_rate_bounds) {
+            (Some(interval), Some(bounds)) => {
+                let fps = 1.0 / interval.as_secs_f32();
+                if bounds.contains(&fps) {
+                    Ok(())
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "frame rate {:.1} fps is outside of {:.1} - {:.1} fps",
+                            fps,
+                            bounds.start(),
+                            bounds.end()
+                        ),
+                    ))
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> OutputSink for MockSpiSink<W> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.record(Instant::now(), spi_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::mock::{MockSpiSink, RecordedFrame};
+    use crate::self_test::run_led_walk;
+    use afterglow::led::LEDStrip;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn it_records_frames_with_timestamps() {
+        let mut sink = MockSpiSink::in_memory();
+        let start = Instant::now();
+
+        sink.record(start, &[0x00, 0x01]).unwrap();
+        sink.record(start + Duration::from_millis(40), &[0x02])
+            .unwrap();
+
+        assert_eq!(
+            sink.frames(),
+            [
+                RecordedFrame {
+                    elapsed: Duration::ZERO,
+                    spi_data: vec![0x00, 0x01],
+                },
+                RecordedFrame {
+                    elapsed: Duration::from_millis(40),
+                    spi_data: vec![0x02],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_logs_frames_to_the_writer() {
+        let mut sink = MockSpiSink::new(Vec::new());
+        let start = Instant::now();
+
+        sink.record(start, &[0x00, 0xe1]).unwrap();
+        sink.record(start + Duration::from_micros(33_333), &[0xff])
+            .unwrap();
+
+        assert!(sink.frames().is_empty());
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "0 00 e1\n33333 ff\n"
+        );
+    }
+
+    #[test]
+    fn it_rejects_frames_outside_the_frame_rate_bounds() {
+        let mut sink = MockSpiSink::in_memory().with_frame_rate_bounds(20.0..=40.0);
+        let start = Instant::now();
+
+        assert!(sink.record(start, &[0x00]).is_ok());
+        assert!(sink
+            .record(start + Duration::from_millis(30), &[0x00])
+            .is_ok());
+        assert!(sink
+            .record(start + Duration::from_millis(40), &[0x00])
+            .is_err());
+        assert!(sink
+            .record(start + Duration::from_millis(200), &[0x00])
+            .is_err());
+        assert_eq!(sink.frames().len(), 4);
+    }
+
+    #[test]
+    fn it_captures_what_would_be_sent_to_the_strip() {
+        let mut led_strip: LEDStrip<2> = LEDStrip::new();
+        let mut sink = MockSpiSink::in_memory();
+
+        run_led_walk(&mut led_strip, &mut sink, Duration::ZERO).unwrap();
+
+        assert_eq!(sink.frames().len(), 3);
+        assert_eq!(
+            sink.frames()[0].spi_data,
+            [
+                0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0xff, 0xff,
+                0xff, 0xff
+            ]
+        );
+    }
+}
@@ -0,0 +1,170 @@
+mod adaptive;
+mod dmx;
+mod dry_run;
+mod frame_log;
+mod gif;
+mod hyperion;
+#[cfg(feature = "rpi")]
+mod i2c;
+mod keep_alive;
+mod mock;
+mod mqtt;
+mod osc;
+#[cfg_attr(not(feature = "rpi"), allow(dead_code))]
+mod retry;
+#[cfg(feature = "rpi")]
+mod spi;
+mod split;
+mod stdout;
+mod tcp;
+mod terminal;
+mod udp;
+mod websocket;
+
+pub use self::gif::GifSink;
+pub use adaptive::AdaptiveSink;
+pub use dmx::DmxUsbSink;
+pub use dry_run::DryRunSink;
+pub use frame_log::{replay_frames, FrameLogReader, FrameLogSink};
+pub use hyperion::HyperionSink;
+#[cfg(feature = "rpi")]
+pub use i2c::I2cOutputSink;
+pub use keep_alive::KeepAliveSink;
+pub use mock::MockSpiSink;
+pub use mqtt::MqttSink;
+pub use osc::OscSink;
+#[cfg(feature = "rpi")]
+pub use retry::{RetryPolicy, RetryingSink};
+#[cfg(feature = "rpi")]
+pub use spi::SpiSink;
+pub use split::SplitSink;
+pub use stdout::StdoutSink;
+pub use tcp::{TcpFrameSource, TcpSink};
+pub use terminal::TerminalSink;
+pub use udp::{UdpBroadcastSink, UdpFrameSource};
+pub use websocket::WsServer;
+
+use afterglow::led::AnyLedStrip;
+use std::io;
+
+pub trait OutputSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()>;
+
+    // Also hands over the strip the frame was encoded from, for sinks that
+    // report its colors before gamma and brightness corrections
+    fn write_strip(&mut self, _led_strip: &dyn AnyLedStrip, spi_data: &[u8]) -> io::Result<()> {
+        self.write(spi_data)
+    }
+}
+
+impl<S: OutputSink + ?Sized> OutputSink for Box<S> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        (**self).write(spi_data)
+    }
+
+    fn write_strip(&mut self, led_strip: &dyn AnyLedStrip, spi_data: &[u8]) -> io::Result<()> {
+        (**self).write_strip(led_strip, spi_data)
+    }
+}
+
+pub struct FanOutSink {
+    sinks: Vec<Box<dyn OutputSink>>,
+}
+
+impl FanOutSink {
+    pub fn new(sinks: Vec<Box<dyn OutputSink>>) -> Self {
+        Self { sinks }
+    }
+}
+
+impl OutputSink for FanOutSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let mut result = Ok(());
+        for sink in self.sinks.iter_mut() {
+            if let Err(err) = sink.write(spi_data) {
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+#[derive(Default)]
+pub struct VecSink {
+    pub frames: Vec<Vec<u8>>,
+}
+
+#[cfg(test)]
+impl OutputSink for VecSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.frames.push(spi_data.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::{FanOutSink, OutputSink};
+    use std::{cell::RefCell, io, rc::Rc};
+
+    struct RecordingSink {
+        frames: Rc<RefCell<Vec<Vec<u8>>>>,
+        fail: bool,
+    }
+
+    impl OutputSink for RecordingSink {
+        fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+            self.frames.borrow_mut().push(spi_data.to_vec());
+            if self.fail {
+                Err(io::Error::other("write failed"))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn it_fans_out_writes_to_every_sink() {
+        let first = Rc::new(RefCell::new(Vec::new()));
+        let second = Rc::new(RefCell::new(Vec::new()));
+        let mut sink = FanOutSink::new(vec![
+            Box::new(RecordingSink {
+                frames: Rc::clone(&first),
+                fail: false,
+            }),
+            Box::new(RecordingSink {
+                frames: Rc::clone(&second),
+                fail: false,
+            }),
+        ]);
+
+        sink.write(&[0x00, 0x01, 0x02]).unwrap();
+
+        assert_eq!(*first.borrow(), vec![vec![0x00, 0x01, 0x02]]);
+        assert_eq!(*second.borrow(), vec![vec![0x00, 0x01, 0x02]]);
+    }
+
+    #[test]
+    fn it_keeps_writing_after_a_sink_fails() {
+        let first = Rc::new(RefCell::new(Vec::new()));
+        let second = Rc::new(RefCell::new(Vec::new()));
+        let mut sink = FanOutSink::new(vec![
+            Box::new(RecordingSink {
+                frames: Rc::clone(&first),
+                fail: true,
+            }),
+            Box::new(RecordingSink {
+                frames: Rc::clone(&second),
+                fail: false,
+            }),
+        ]);
+
+        assert!(sink.write(&[0x00]).is_err());
+        assert_eq!(first.borrow().len(), 1);
+        assert_eq!(second.borrow().len(), 1);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::output::OutputSink;
+use afterglow::config::MqttSettings;
+use afterglow::led::decode_spi_data;
+use rumqttc::{Client, Connection, Event, MqttOptions, Packet, QoS, Transport};
+use std::{
+    fs, io, process, thread,
+    time::{Duration, Instant},
+};
+use tracing::{debug, warn};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+// Frames published while the broker is unreachable are dropped once this
+// many are queued, so a reconnect doesn't replay a backlog of stale colors
+const REQUEST_CAPACITY: usize = 10;
+
+fn leds_payload(colors: &[(u8, u8, u8)]) -> String {
+    let colors: Vec<String> = colors
+        .iter()
+        .map(|&(r, g, b)| format!("\"#{:02x}{:02x}{:02x}\"", r, g, b))
+        .collect();
+    format!("[{}]", colors.join(","))
+}
+
+fn quality_of_service(qos: u8) -> QoS {
+    match qos {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        _ => QoS::ExactlyOnce,
+    }
+}
+
+fn mqtt_options(settings: &MqttSettings) -> io::Result<MqttOptions> {
+    let mut options = MqttOptions::new(
+        format!("afterglow-{}", process::id()),
+        &settings.broker_url,
+        settings.port,
+    );
+    options.set_keep_alive(KEEP_ALIVE);
+
+    if let Some(tls) = &settings.tls {
+        let client_auth = match &tls.client_auth {
+            Some((cert, key)) => Some((fs::read(cert)?, fs::read(key)?)),
+            None => None,
+        };
+        options.set_transport(Transport::tls(fs::read(&tls.ca_cert)?, client_auth, None));
+    }
+
+    Ok(options)
+}
+
+// Drives the connection until the sink drops its client, backing off between
+// reconnects while the broker is unavailable
+fn run_connection(target: String, mut connection: Connection) {
+    let mut backoff = MIN_BACKOFF;
+    for notification in connection.iter() {
+        match notification {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => backoff = MIN_BACKOFF,
+            Ok(_) => {}
+            Err(err) => {
+                warn!(
+                    "MQTT output to {} unavailable, retrying in {:?}: {}",
+                    target, backoff, err
+                );
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+pub struct MqttSink {
+    client: Client,
+    topic_prefix: String,
+    qos: QoS,
+    started: Instant,
+    frame_count: u64,
+    status_start: Option<Instant>,
+    status_frames: u64,
+}
+
+impl MqttSink {
+    pub fn new(settings: &MqttSettings) -> io::Result<Self> {
+        let (client, connection) = Client::new(mqtt_options(settings)?, REQUEST_CAPACITY);
+        let target = format!("{}:{}", settings.broker_url, settings.port);
+        thread::spawn(move || run_connection(target, connection));
+
+        Ok(Self {
+            client,
+            topic_prefix: settings.topic_prefix.trim_end_matches('/').to_string(),
+            qos: quality_of_service(settings.qos),
+            started: Instant::now(),
+            frame_count: 0,
+            status_start: None,
+            status_frames: 0,
+        })
+    }
+
+    fn status_payload(&mut self, timestamp: Instant) -> Option<String> {
+        self.frame_count += 1;
+        self.status_frames += 1;
+        let status_start = *self.status_start.get_or_insert(timestamp);
+        let elapsed = timestamp.duration_since(status_start);
+        if elapsed < STATUS_INTERVAL {
+            return None;
+        }
+
+        let fps = self.status_frames as f64 / elapsed.as_secs_f64();
+        self.status_start = Some(timestamp);
+        self.status_frames = 0;
+        Some(format!(
+            "{{\"uptime\":{},\"fps\":{},\"frame_count\":{}}}",
+            timestamp.duration_since(self.started).as_secs(),
+            fps,
+            self.frame_count
+        ))
+    }
+
+    fn publish(&self, topic: &str, payload: String) {
+        let topic = format!("{}/{}", self.topic_prefix, topic);
+        if let Err(err) = self.client.try_publish(&topic, self.qos, false, payload) {
+            debug!("Dropping MQTT message to {}: {}", topic, err);
+        }
+    }
+}
+
+impl OutputSink for MqttSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.publish("leds", leds_payload(&decode_spi_data(spi_data)));
+        if let Some(status) = self.status_payload(Instant::now()) {
+            self.publish("status", status);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::mqtt::MqttSink;
+    use crate::output::OutputSink;
+    use afterglow::config::MqttSettings;
+    use afterglow::led::LEDStrip;
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    fn read_packet(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut header = [0; 1];
+        stream.read_exact(&mut header).unwrap();
+
+        let mut len = 0;
+        let mut shift = 0;
+        loop {
+            let mut byte = [0; 1];
+            stream.read_exact(&mut byte).unwrap();
+            len |= usize::from(byte[0] & 0x7f) << shift;
+            shift += 7;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+        }
+
+        let mut body = vec![0; len];
+        stream.read_exact(&mut body).unwrap();
+        (header[0], body)
+    }
+
+    fn publish_to_broker(qos: u8) -> (u8, (u8, Vec<u8>)) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let broker = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (connect, _) = read_packet(&mut stream);
+            stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+            let publish = read_packet(&mut stream);
+            (connect, publish)
+        });
+
+        let mut sink = MqttSink::new(&MqttSettings {
+            broker_url: addr.ip().to_string(),
+            port: addr.port(),
+            topic_prefix: "home/afterglow/".to_string(),
+            qos,
+            tls: None,
+        })
+        .unwrap();
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x4b8040]);
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        broker.join().unwrap()
+    }
+
+    #[test]
+    fn it_publishes_led_colors_to_the_broker() {
+        let (connect, (publish, body)) = publish_to_broker(0);
+        assert_eq!(connect, 0x10);
+        assert_eq!(publish, 0x30);
+        assert_eq!(
+            body,
+            [
+                &b"\x00\x13home/afterglow/leds"[..],
+                br##"["#ff0000","#4b8040"]"##
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn it_publishes_with_exactly_once_delivery() {
+        let (_, (publish, body)) = publish_to_broker(2);
+        assert_eq!(publish & 0x06, 0x04);
+        assert!(body.starts_with(b"\x00\x13home/afterglow/leds"));
+        assert!(body.ends_with(br##"["#ff0000","#4b8040"]"##));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+};
+use tracing::warn;
+
+const BUNDLE_TAG: &str = "#bundle";
+const IMMEDIATELY: u64 = 1;
+
+enum OscArg<'a> {
+    Float(f32),
+    Blob(&'a [u8]),
+}
+
+fn pad(data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(4), 0);
+}
+
+fn push_string(data: &mut Vec<u8>, s: &str) {
+    data.extend(s.as_bytes());
+    data.push(0);
+    pad(data);
+}
+
+fn encode_message(address: &str, args: &[OscArg]) -> Vec<u8> {
+    let mut data = Vec::new();
+    push_string(&mut data, address);
+
+    let type_tags: String = std::iter::once(',')
+        .chain(args.iter().map(|arg| match arg {
+            OscArg::Float(_) => 'f',
+            OscArg::Blob(_) => 'b',
+        }))
+        .collect();
+    push_string(&mut data, &type_tags);
+
+    for arg in args {
+        match arg {
+            OscArg::Float(value) => data.extend(value.to_be_bytes()),
+            OscArg::Blob(blob) => {
+                data.extend((blob.len() as i32).to_be_bytes());
+                data.extend(*blob);
+                pad(&mut data);
+            }
+        }
+    }
+
+    data
+}
+
+fn encode_bundle(messages: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    push_string(&mut data, BUNDLE_TAG);
+    data.extend(IMMEDIATELY.to_be_bytes());
+    for message in messages {
+        data.extend((message.len() as i32).to_be_bytes());
+        data.extend(message);
+    }
+
+    data
+}
+
+fn encode_frame(colors: &[(u8, u8, u8)], per_led: bool) -> Vec<u8> {
+    if !per_led {
+        let rgb: Vec<u8> = colors.iter().flat_map(|&(r, g, b)| [r, g, b]).collect();
+        return encode_bundle(&[encode_message("/afterglow/frame", &[OscArg::Blob(&rgb)])]);
+    }
+
+    let messages: Vec<Vec<u8>> = colors
+        .iter()
+        .enumerate()
+        .map(|(index, &(r, g, b))| {
+            encode_message(
+                &format!("/afterglow/led/{}", index),
+                &[
+                    OscArg::Float(f32::from(r) / 255.0),
+                    OscArg::Float(f32::from(g) / 255.0),
+                    OscArg::Float(f32::from(b) / 255.0),
+                ],
+            )
+        })
+        .collect();
+    encode_bundle(&messages)
+}
+
+pub struct OscSink {
+    target: SocketAddr,
+    socket: UdpSocket,
+    per_led: bool,
+}
+
+impl OscSink {
+    pub fn new(target: SocketAddr, per_led: bool) -> io::Result<Self> {
+        Ok(Self {
+            target,
+            socket: UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            per_led,
+        })
+    }
+}
+
+impl OutputSink for OscSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let packet = encode_frame(&decode_spi_data(spi_data), self.per_led);
+        if let Err(err) = self.socket.send_to(&packet, self.target) {
+            warn!("OSC output to {} unavailable: {}", self.target, err);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::osc::{encode_frame, encode_message, OscArg, OscSink};
+    use crate::output::OutputSink;
+    use afterglow::led::LEDStrip;
+    use std::net::{Ipv4Addr, UdpSocket};
+
+    #[test]
+    fn it_pads_strings_and_type_tags_to_four_bytes() {
+        assert_eq!(
+            encode_message("/led", &[OscArg::Float(1.0)]),
+            vec![
+                b'/', b'l', b'e', b'd', 0, 0, 0, 0, // Address
+                b',', b'f', 0, 0, // Type tags
+                0x3f, 0x80, 0x00, 0x00, // 1.0
+            ]
+        );
+        assert_eq!(
+            encode_message("/a", &[OscArg::Blob(&[1, 2, 3, 4, 5])]),
+            vec![
+                b'/', b'a', 0, 0, // Address
+                b',', b'b', 0, 0, // Type tags
+                0, 0, 0, 5, // Blob size
+                1, 2, 3, 4, 5, 0, 0, 0, // Blob data
+            ]
+        );
+    }
+
+    #[test]
+    fn it_encodes_a_frame_as_a_single_blob() {
+        assert_eq!(
+            encode_frame(&[(0xff, 0x00, 0x00), (0x4b, 0x80, 0x40)], false),
+            vec![
+                b'#', b'b', b'u', b'n', b'd', b'l', b'e', 0, // Bundle tag
+                0, 0, 0, 0, 0, 0, 0, 1, // Time tag
+                0, 0, 0, 36, // Message size
+                b'/', b'a', b'f', b't', b'e', b'r', b'g', b'l', // Address
+                b'o', b'w', b'/', b'f', b'r', b'a', b'm', b'e', //
+                0, 0, 0, 0, //
+                b',', b'b', 0, 0, // Type tags
+                0, 0, 0, 6, // Blob size
+                0xff, 0x00, 0x00, 0x4b, 0x80, 0x40, 0, 0, // Blob data
+            ]
+        );
+    }
+
+    #[test]
+    fn it_encodes_a_frame_as_one_message_per_led() {
+        assert_eq!(
+            encode_frame(&[(0xff, 0x00, 0x00)], true),
+            vec![
+                b'#', b'b', b'u', b'n', b'd', b'l', b'e', 0, // Bundle tag
+                0, 0, 0, 0, 0, 0, 0, 1, // Time tag
+                0, 0, 0, 40, // Message size
+                b'/', b'a', b'f', b't', b'e', b'r', b'g', b'l', // Address
+                b'o', b'w', b'/', b'l', b'e', b'd', b'/', b'0', //
+                0, 0, 0, 0, //
+                b',', b'f', b'f', b'f', 0, 0, 0, 0, // Type tags
+                0x3f, 0x80, 0x00, 0x00, // Red
+                0x00, 0x00, 0x00, 0x00, // Green
+                0x00, 0x00, 0x00, 0x00, // Blue
+            ]
+        );
+    }
+
+    #[test]
+    fn it_sends_bundles_over_udp() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut sink = OscSink::new(receiver.local_addr().unwrap(), false).unwrap();
+
+        let led_strip = LEDStrip::new_with_data([0x4b8040]);
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        let mut buffer = [0; 128];
+        let len = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..8], b"#bundle\0");
+        assert_eq!(&buffer[len - 4..len], [0x4b, 0x80, 0x40, 0x00]);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::output::OutputSink;
+use std::{io, thread, time::Duration};
+use tracing::{error, warn};
+
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub retry_delay: Duration,
+    pub reopen_after: u32,
+}
+
+pub struct RetryingSink<S, F> {
+    label: String,
+    sink: Option<S>,
+    open: F,
+    policy: RetryPolicy,
+    consecutive_failures: u32,
+}
+
+impl<S: OutputSink, F: FnMut() -> io::Result<S>> RetryingSink<S, F> {
+    pub fn new(label: &str, mut open: F, policy: RetryPolicy) -> io::Result<Self> {
+        let sink = open()?;
+        Ok(Self {
+            label: label.to_string(),
+            sink: Some(sink),
+            open,
+            policy,
+            consecutive_failures: 0,
+        })
+    }
+
+    fn reopen(&mut self) {
+        warn!(
+            "{}: {} consecutive failed frames, reopening device",
+            self.label, self.consecutive_failures
+        );
+        self.sink = None;
+        self.consecutive_failures = 0;
+
+        match (self.open)() {
+            Ok(sink) => self.sink = Some(sink),
+            Err(err) => error!("{}: failed to reopen device: {}", self.label, err),
+        }
+    }
+
+    fn write_with_retries(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let Some(sink) = self.sink.as_mut() else {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "device is not open",
+            ));
+        };
+
+        let attempts = self.policy.max_retries + 1;
+        let mut attempt = 1;
+        loop {
+            let err = match sink.write(spi_data) {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+
+            warn!(
+                "{}: write of {} bytes failed (attempt {}/{}, errno {:?}): {}",
+                self.label,
+                spi_data.len(),
+                attempt,
+                attempts,
+                err.raw_os_error(),
+                err
+            );
+            if attempt == attempts {
+                return Err(err);
+            }
+
+            attempt += 1;
+            thread::sleep(self.policy.retry_delay);
+        }
+    }
+}
+
+impl<S: OutputSink, F: FnMut() -> io::Result<S>> OutputSink for RetryingSink<S, F> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        if self.write_with_retries(spi_data).is_ok() {
+            self.consecutive_failures = 0;
+            return Ok(());
+        }
+
+        self.consecutive_failures += 1;
+        if self.consecutive_failures > self.policy.reopen_after {
+            self.reopen();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::retry::{RetryPolicy, RetryingSink};
+    use crate::output::OutputSink;
+    use std::{cell::RefCell, collections::VecDeque, io, rc::Rc, time::Duration};
+
+    #[derive(Default)]
+    struct Faults {
+        results: VecDeque<bool>,
+        attempts: usize,
+        writes: Vec<Vec<u8>>,
+        opens: usize,
+    }
+
+    struct FaultyWriter {
+        faults: Rc<RefCell<Faults>>,
+    }
+
+    impl OutputSink for FaultyWriter {
+        fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+            let mut faults = self.faults.borrow_mut();
+            faults.attempts += 1;
+            if faults.results.pop_front().unwrap_or(true) {
+                faults.writes.push(spi_data.to_vec());
+                Ok(())
+            } else {
+                Err(io::Error::from_raw_os_error(5))
+            }
+        }
+    }
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 2,
+        retry_delay: Duration::ZERO,
+        reopen_after: 1,
+    };
+
+    fn faulty_sink(results: &[bool]) -> (Rc<RefCell<Faults>>, impl OutputSink) {
+        let faults = Rc::new(RefCell::new(Faults {
+            results: results.iter().copied().collect(),
+            ..Faults::default()
+        }));
+
+        let open_faults = Rc::clone(&faults);
+        let sink = RetryingSink::new(
+            "test",
+            move || {
+                open_faults.borrow_mut().opens += 1;
+                Ok(FaultyWriter {
+                    faults: Rc::clone(&open_faults),
+                })
+            },
+            POLICY,
+        )
+        .unwrap();
+
+        (faults, sink)
+    }
+
+    #[test]
+    fn it_retries_a_failed_write() {
+        let (faults, mut sink) = faulty_sink(&[false, false, true]);
+
+        assert!(sink.write(&[0x00, 0x01]).is_ok());
+
+        let faults = faults.borrow();
+        assert_eq!(faults.attempts, 3);
+        assert_eq!(faults.writes, vec![vec![0x00, 0x01]]);
+        assert_eq!(faults.opens, 1);
+    }
+
+    #[test]
+    fn it_reopens_after_consecutive_failed_frames() {
+        let (faults, mut sink) = faulty_sink(&[false; 6]);
+
+        assert!(sink.write(&[0x00]).is_ok());
+        assert_eq!(faults.borrow().attempts, 3);
+        assert_eq!(faults.borrow().opens, 1);
+
+        assert!(sink.write(&[0x00]).is_ok());
+        assert_eq!(faults.borrow().attempts, 6);
+        assert_eq!(faults.borrow().opens, 2);
+
+        assert!(sink.write(&[0x02]).is_ok());
+        assert_eq!(faults.borrow().attempts, 7);
+        assert_eq!(faults.borrow().writes, vec![vec![0x02]]);
+    }
+
+    #[test]
+    fn it_resets_the_failure_count_after_a_successful_frame() {
+        let (faults, mut sink) = faulty_sink(&[false, false, false, true, false, false, false]);
+
+        for _ in 0..3 {
+            assert!(sink.write(&[0x00]).is_ok());
+        }
+
+        assert_eq!(faults.borrow().opens, 1);
+    }
+
+    #[test]
+    fn it_keeps_going_when_the_device_cannot_be_reopened() {
+        let opens = Rc::new(RefCell::new(0));
+        let open_count = Rc::clone(&opens);
+        let mut sink = RetryingSink::new(
+            "test",
+            move || {
+                *open_count.borrow_mut() += 1;
+                if *open_count.borrow() == 1 {
+                    Ok(FaultyWriter {
+                        faults: Rc::new(RefCell::new(Faults {
+                            results: [false; 6].into_iter().collect(),
+                            ..Faults::default()
+                        })),
+                    })
+                } else {
+                    Err(io::Error::from_raw_os_error(19))
+                }
+            },
+            POLICY,
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            assert!(sink.write(&[0x00]).is_ok());
+        }
+        assert_eq!(*opens.borrow(), 2);
+
+        for _ in 0..2 {
+            assert!(sink.write(&[0x00]).is_ok());
+        }
+        assert_eq!(*opens.borrow(), 3);
+    }
+}
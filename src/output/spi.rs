@@ -0,0 +1,39 @@
+use crate::output::OutputSink;
+use afterglow::spi_settings::SpiSettings;
+use rppal::spi::{self, Spi};
+use std::io;
+
+fn to_io_error(err: spi::Error) -> io::Error {
+    match err {
+        spi::Error::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+pub struct SpiSink {
+    spi: Spi,
+}
+
+impl SpiSink {
+    pub fn new(spi: Spi) -> Self {
+        Self { spi }
+    }
+
+    pub fn open(settings: SpiSettings) -> io::Result<Self> {
+        Spi::new(
+            settings.bus.into(),
+            settings.slave_select.into(),
+            settings.clock_speed,
+            settings.mode.into(),
+        )
+        .map(SpiSink::new)
+        .map_err(to_io_error)
+    }
+}
+
+impl OutputSink for SpiSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        self.spi.write(spi_data).map_err(to_io_error)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+use crate::output::OutputSink;
+use afterglow::led::slice_spi_data;
+use std::{io, ops::Range, thread};
+use tracing::warn;
+
+pub struct SplitSink {
+    strips: Vec<(Range<usize>, Box<dyn OutputSink + Send>)>,
+}
+
+impl SplitSink {
+    pub fn new(strips: Vec<(Range<usize>, Box<dyn OutputSink + Send>)>) -> Self {
+        Self { strips }
+    }
+}
+
+impl OutputSink for SplitSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        thread::scope(|scope| {
+            for (leds, sink) in &mut self.strips {
+                scope.spawn(move || {
+                    if let Err(err) = sink.write(&slice_spi_data(spi_data, leds.clone())) {
+                        warn!("Failed to write LEDs {:?}: {}", leds, err);
+                    }
+                });
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::split::SplitSink;
+    use crate::output::OutputSink;
+    use afterglow::led::{decode_spi_data, LEDStrip};
+    use afterglow::segment_map::{
+        average_segment_colors, build_segment_map, Orientation, DEFAULT_EDGE_FRACTION,
+    };
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    type Frames = Arc<Mutex<Vec<Vec<u8>>>>;
+
+    struct MockSpi {
+        frames: Frames,
+        fail: bool,
+    }
+
+    impl OutputSink for MockSpi {
+        fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+            if self.fail {
+                return Err(io::Error::other("bus unavailable"));
+            }
+            self.frames.lock().unwrap().push(spi_data.to_vec());
+            Ok(())
+        }
+    }
+
+    fn mock_spi(fail: bool) -> (Frames, Box<MockSpi>) {
+        let frames = Arc::new(Mutex::new(Vec::new()));
+        let sink = Box::new(MockSpi {
+            frames: Arc::clone(&frames),
+            fail,
+        });
+        (frames, sink)
+    }
+
+    #[test]
+    fn it_writes_each_index_range_to_its_own_strip() {
+        let (ring, ring_sink) = mock_spi(false);
+        let (bar, bar_sink) = mock_spi(false);
+        let mut sink = SplitSink::new(vec![(0..3, ring_sink), (3..5, bar_sink)]);
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x4b8040, 0xffffff]);
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        let ring = ring.lock().unwrap();
+        let bar = bar.lock().unwrap();
+        assert_eq!(ring.len(), 1);
+        assert_eq!(bar.len(), 1);
+        assert_eq!(
+            decode_spi_data(&ring[0]),
+            vec![(255, 0, 0), (0, 255, 0), (0, 0, 255)]
+        );
+        assert_eq!(
+            decode_spi_data(&bar[0]),
+            vec![(75, 128, 64), (255, 255, 255)]
+        );
+        assert_eq!(
+            bar[0],
+            LEDStrip::new_with_data([0x4b8040, 0xffffff])
+                .get_spi_data()
+                .clone()
+        );
+    }
+
+    #[test]
+    fn it_keeps_writing_other_strips_when_a_bus_fails() {
+        let (_, ring_sink) = mock_spi(true);
+        let (bar, bar_sink) = mock_spi(false);
+        let mut sink = SplitSink::new(vec![(0..2, ring_sink), (2..4, bar_sink)]);
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x0000ff, 0x4b8040]);
+        assert!(sink.write(led_strip.get_spi_data()).is_ok());
+        assert!(sink.write(led_strip.get_spi_data()).is_ok());
+
+        assert_eq!(bar.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn it_dispatches_a_combined_segment_map_to_each_strip() {
+        const NUM_LEDS: usize = 12;
+        let segment_map = build_segment_map(
+            NUM_LEDS,
+            9,
+            7,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let rgb: Vec<u8> = segment_map
+            .iter()
+            .flat_map(|segment| match segment {
+                Some(segment) => [*segment as u8 * 20, 0x40, 0x80],
+                None => [0xff, 0xff, 0xff],
+            })
+            .collect();
+
+        let segment_colors = average_segment_colors(&rgb, &segment_map, NUM_LEDS).unwrap();
+        let mut led_strip: LEDStrip<NUM_LEDS> = LEDStrip::new();
+        for (index, color) in segment_colors.into_iter().enumerate() {
+            led_strip.set_led(index, color);
+        }
+
+        let (left, left_sink) = mock_spi(false);
+        let (right, right_sink) = mock_spi(false);
+        let mut sink = SplitSink::new(vec![(0..6, left_sink), (6..12, right_sink)]);
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        let expected = |segments: std::ops::Range<u8>| -> Vec<(u8, u8, u8)> {
+            segments.map(|segment| (segment * 20, 0x40, 0x80)).collect()
+        };
+        assert_eq!(decode_spi_data(&left.lock().unwrap()[0]), expected(0..6));
+        assert_eq!(decode_spi_data(&right.lock().unwrap()[0]), expected(6..12));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::io::{self, Stdout, Write};
+
+pub struct StdoutSink<W: Write> {
+    writer: W,
+}
+
+impl StdoutSink<Stdout> {
+    pub fn stdout() -> Self {
+        StdoutSink::new(io::stdout())
+    }
+}
+
+impl<W: Write> StdoutSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> OutputSink for StdoutSink<W> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        for (index, (r, g, b)) in decode_spi_data(spi_data).into_iter().enumerate() {
+            writeln!(self.writer, "LED[{}]: #{:02x}{:02x}{:02x}", index, r, g, b)?;
+        }
+        writeln!(self.writer)?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::stdout::StdoutSink;
+    use crate::output::OutputSink;
+    use afterglow::led::{ChipProfile, LEDStrip};
+
+    #[test]
+    fn it_prints_each_led_as_hex() {
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x4b8040, 0x000000]);
+        let mut sink = StdoutSink::new(Vec::new());
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "LED[0]: #ff0000\nLED[1]: #4b8040\nLED[2]: #000000\n\n"
+        );
+    }
+
+    #[test]
+    fn it_prints_lpd8806_frames() {
+        let mut led_strip = LEDStrip::new_with_data([0xfe0000, 0x4a8040]);
+        led_strip.set_chip_profile(ChipProfile::Lpd8806);
+        let mut sink = StdoutSink::new(Vec::new());
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "LED[0]: #fe0000\nLED[1]: #4a8040\n\n"
+        );
+    }
+}
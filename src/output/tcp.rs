@@ -0,0 +1,310 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+const FRAME_MAGIC: u32 = 0x41474c57;
+const HEADER_LEN: usize = 14;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TcpFrame {
+    pub timestamp_ms: u64,
+    pub colors: Vec<(u8, u8, u8)>,
+}
+
+fn encode_frame(frame: &TcpFrame) -> io::Result<Vec<u8>> {
+    let led_count: u16 = frame
+        .colors
+        .len()
+        .try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Too many LEDs in frame"))?;
+
+    let mut data = Vec::with_capacity(HEADER_LEN + frame.colors.len() * 3);
+    data.extend(FRAME_MAGIC.to_be_bytes());
+    data.extend(led_count.to_be_bytes());
+    data.extend(frame.timestamp_ms.to_be_bytes());
+    for &(r, g, b) in frame.colors.iter() {
+        data.extend([r, g, b]);
+    }
+
+    Ok(data)
+}
+
+fn read_frame(reader: &mut impl Read) -> io::Result<TcpFrame> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    let magic = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    if magic != FRAME_MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid frame magic {:#010x}", magic),
+        ));
+    }
+    let led_count = u16::from_be_bytes(header[4..6].try_into().unwrap());
+    let timestamp_ms = u64::from_be_bytes(header[6..14].try_into().unwrap());
+
+    let mut rgb = vec![0; usize::from(led_count) * 3];
+    reader.read_exact(&mut rgb)?;
+
+    Ok(TcpFrame {
+        timestamp_ms,
+        colors: rgb
+            .chunks_exact(3)
+            .map(|color| (color[0], color[1], color[2]))
+            .collect(),
+    })
+}
+
+pub struct TcpSink {
+    target: String,
+    stream: Option<TcpStream>,
+    next_attempt: Instant,
+}
+
+impl TcpSink {
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            stream: None,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last_err = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Unable to resolve {}", self.target),
+        );
+        for addr in self.target.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = err,
+            }
+        }
+
+        Err(last_err)
+    }
+
+    fn disconnect(&mut self, err: io::Error) {
+        warn!("TCP output to {} unavailable: {}", self.target, err);
+        self.stream = None;
+        self.next_attempt = Instant::now() + RECONNECT_DELAY;
+    }
+}
+
+impl OutputSink for TcpSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        if self.stream.is_none() {
+            if Instant::now() < self.next_attempt {
+                return Ok(());
+            }
+
+            match self.connect() {
+                Ok(stream) => self.stream = Some(stream),
+                Err(err) => {
+                    self.disconnect(err);
+                    return Ok(());
+                }
+            }
+        }
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let frame = encode_frame(&TcpFrame {
+            timestamp_ms,
+            colors: decode_spi_data(spi_data),
+        })?;
+
+        if let Some(stream) = self.stream.as_mut() {
+            if let Err(err) = stream.write_all(&frame) {
+                self.disconnect(err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct TcpFrameSource {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+}
+
+impl TcpFrameSource {
+    pub fn bind(addr: &str) -> io::Result<Self> {
+        let listener = if addr.starts_with(':') {
+            TcpListener::bind(format!("0.0.0.0{}", addr))?
+        } else {
+            TcpListener::bind(addr)?
+        };
+
+        Ok(Self {
+            listener,
+            stream: None,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn next_frame(&mut self) -> io::Result<TcpFrame> {
+        loop {
+            let stream = match self.stream.as_mut() {
+                Some(stream) => stream,
+                None => {
+                    let (stream, _) = self.listener.accept()?;
+                    self.stream.insert(stream)
+                }
+            };
+
+            match read_frame(stream) {
+                Ok(frame) => return Ok(frame),
+                Err(err) => {
+                    if err.kind() != io::ErrorKind::UnexpectedEof {
+                        warn!("Dropping TCP frame connection: {}", err);
+                    }
+                    self.stream = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::tcp::{encode_frame, read_frame, TcpFrame, TcpFrameSource, TcpSink};
+    use crate::output::OutputSink;
+    use afterglow::led::LEDStrip;
+    use std::{
+        io::{self, Read, Write},
+        net::{TcpListener, TcpStream},
+    };
+
+    struct TrickleReader<R: Read>(R);
+
+    impl<R: Read> Read for TrickleReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn it_encodes_frames_with_a_length_prefixed_header() {
+        let frame = TcpFrame {
+            timestamp_ms: 0x0102030405060708,
+            colors: vec![(0xff, 0x00, 0x00), (0x4b, 0x80, 0x40)],
+        };
+
+        assert_eq!(
+            encode_frame(&frame).unwrap(),
+            vec![
+                0x41, 0x47, 0x4c, 0x57, // Magic
+                0x00, 0x02, // LED count
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, // Timestamp
+                0xff, 0x00, 0x00, // LED 0
+                0x4b, 0x80, 0x40, // LED 1
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reads_frames_across_partial_reads() {
+        let frame = TcpFrame {
+            timestamp_ms: 1234,
+            colors: vec![(1, 2, 3), (4, 5, 6), (7, 8, 9)],
+        };
+        let data = [encode_frame(&frame).unwrap(), encode_frame(&frame).unwrap()].concat();
+        let mut reader = TrickleReader(data.as_slice());
+
+        assert_eq!(read_frame(&mut reader).unwrap(), frame);
+        assert_eq!(read_frame(&mut reader).unwrap(), frame);
+        assert_eq!(
+            read_frame(&mut reader).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn it_rejects_frames_with_a_bad_magic() {
+        let mut data = encode_frame(&TcpFrame {
+            timestamp_ms: 0,
+            colors: vec![(1, 2, 3)],
+        })
+        .unwrap();
+        data[0] = 0x00;
+
+        assert_eq!(
+            read_frame(&mut data.as_slice()).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn it_streams_frames_over_localhost() {
+        let mut source = TcpFrameSource::bind("127.0.0.1:0").unwrap();
+        let target = source.local_addr().unwrap().to_string();
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x00ff00, 0x4b8040]);
+        let mut sink = TcpSink::new(&target);
+        sink.write(led_strip.get_spi_data()).unwrap();
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        for _ in 0..2 {
+            let frame = source.next_frame().unwrap();
+            assert_eq!(frame.colors, vec![(255, 0, 0), (0, 255, 0), (75, 128, 64)]);
+            assert!(frame.timestamp_ms > 0);
+        }
+
+        drop(sink);
+        let led_strip = LEDStrip::new_with_data([0x0000ff]);
+        let mut sink = TcpSink::new(&target);
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(source.next_frame().unwrap().colors, vec![(0, 0, 255)]);
+    }
+
+    #[test]
+    fn it_recovers_from_a_corrupt_connection() {
+        let mut source = TcpFrameSource::bind("127.0.0.1:0").unwrap();
+        let target = source.local_addr().unwrap();
+
+        let mut stream = TcpStream::connect(target).unwrap();
+        stream.write_all(&[0xde, 0xad, 0xbe, 0xef]).unwrap();
+        stream.write_all(&[0x00; 32]).unwrap();
+        drop(stream);
+
+        let led_strip = LEDStrip::new_with_data([0x4b8040]);
+        let mut sink = TcpSink::new(&target.to_string());
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(source.next_frame().unwrap().colors, vec![(75, 128, 64)]);
+    }
+
+    #[test]
+    fn it_does_not_fail_when_the_receiver_is_unavailable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let led_strip = LEDStrip::new_with_data([0x4b8040]);
+        let mut sink = TcpSink::new(&target);
+        assert!(sink.write(led_strip.get_spi_data()).is_ok());
+        assert!(sink.write(led_strip.get_spi_data()).is_ok());
+    }
+}
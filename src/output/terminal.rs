@@ -0,0 +1,155 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::{
+    env,
+    io::{self, IsTerminal, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+}
+
+impl ColorMode {
+    fn detect() -> Self {
+        match env::var("COLORTERM") {
+            Ok(value) if value == "truecolor" || value == "24bit" => ColorMode::TrueColor,
+            _ => ColorMode::Ansi256,
+        }
+    }
+}
+
+fn ansi_256_index(r: u8, g: u8, b: u8) -> u8 {
+    let cube_level = |channel: u8| match channel {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (channel - 35) / 40,
+    };
+
+    16 + 36 * cube_level(r) + 6 * cube_level(g) + cube_level(b)
+}
+
+pub struct TerminalSink<W: Write> {
+    writer: W,
+    color_mode: ColorMode,
+    redraw_in_place: bool,
+    last_draw: Option<Instant>,
+}
+
+impl TerminalSink<Stdout> {
+    pub fn stdout() -> Self {
+        let stdout = io::stdout();
+        let is_terminal = stdout.is_terminal();
+        TerminalSink::new(stdout, ColorMode::detect(), is_terminal)
+    }
+}
+
+impl<W: Write> TerminalSink<W> {
+    pub fn new(writer: W, color_mode: ColorMode, redraw_in_place: bool) -> Self {
+        Self {
+            writer,
+            color_mode,
+            redraw_in_place,
+            last_draw: None,
+        }
+    }
+
+    fn render(&self, spi_data: &[u8]) -> String {
+        let mut line = String::new();
+        if self.redraw_in_place {
+            line.push('\r');
+        }
+
+        for (r, g, b) in decode_spi_data(spi_data) {
+            match self.color_mode {
+                ColorMode::TrueColor => line.push_str(&format!("\x1b[48;2;{};{};{}m ", r, g, b)),
+                ColorMode::Ansi256 => {
+                    line.push_str(&format!("\x1b[48;5;{}m ", ansi_256_index(r, g, b)))
+                }
+            }
+        }
+        line.push_str("\x1b[0m");
+
+        if !self.redraw_in_place {
+            line.push('\n');
+        }
+
+        line
+    }
+}
+
+impl<W: Write> OutputSink for TerminalSink<W> {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let now = Instant::now();
+        if let Some(last_draw) = self.last_draw {
+            if now.duration_since(last_draw) < REFRESH_INTERVAL {
+                return Ok(());
+            }
+        }
+        self.last_draw = Some(now);
+
+        let line = self.render(spi_data);
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::terminal::{ansi_256_index, ColorMode, TerminalSink};
+    use crate::output::OutputSink;
+    use afterglow::led::LEDStrip;
+
+    #[test]
+    fn it_renders_truecolor_blocks_in_place() {
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x4b8040]);
+        let mut sink = TerminalSink::new(Vec::new(), ColorMode::TrueColor, true);
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "\r\x1b[48;2;255;0;0m \x1b[48;2;75;128;64m \x1b[0m"
+        );
+    }
+
+    #[test]
+    fn it_renders_256_color_blocks_on_separate_lines() {
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x000000]);
+        let mut sink = TerminalSink::new(Vec::new(), ColorMode::Ansi256, false);
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "\x1b[48;5;196m \x1b[48;5;16m \x1b[0m\n"
+        );
+    }
+
+    #[test]
+    fn it_approximates_colors_with_the_256_color_cube() {
+        assert_eq!(ansi_256_index(0, 0, 0), 16);
+        assert_eq!(ansi_256_index(255, 255, 255), 231);
+        assert_eq!(ansi_256_index(0, 255, 0), 46);
+        assert_eq!(ansi_256_index(0, 0, 255), 21);
+        assert_eq!(ansi_256_index(95, 135, 175), 67);
+    }
+
+    #[test]
+    fn it_caps_the_refresh_rate() {
+        let led_strip = LEDStrip::new_with_data([0xff0000]);
+        let mut sink = TerminalSink::new(Vec::new(), ColorMode::TrueColor, true);
+
+        sink.write(led_strip.get_spi_data()).unwrap();
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(sink.writer).unwrap(),
+            "\r\x1b[48;2;255;0;0m \x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::output::OutputSink;
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+};
+use tracing::warn;
+
+const DATAGRAM_MAGIC: u32 = 0x41475550;
+const HEADER_LEN: usize = 6;
+const MAX_DATAGRAM_LEN: usize = 65507;
+
+fn encode_datagram(spi_data: &[u8]) -> io::Result<Vec<u8>> {
+    let len: u16 = spi_data
+        .len()
+        .try_into()
+        .ok()
+        .filter(|&len| usize::from(len) + HEADER_LEN <= MAX_DATAGRAM_LEN)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Too much LED data for UDP"))?;
+
+    let mut data = Vec::with_capacity(HEADER_LEN + spi_data.len());
+    data.extend(DATAGRAM_MAGIC.to_be_bytes());
+    data.extend(len.to_be_bytes());
+    data.extend(spi_data);
+
+    Ok(data)
+}
+
+fn decode_datagram(datagram: &[u8]) -> io::Result<&[u8]> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let Some((header, payload)) = datagram.split_at_checked(HEADER_LEN) else {
+        return Err(invalid(format!(
+            "Datagram of {} bytes is too short",
+            datagram.len()
+        )));
+    };
+
+    let magic = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    if magic != DATAGRAM_MAGIC {
+        return Err(invalid(format!("Invalid datagram magic {:#010x}", magic)));
+    }
+    let len = usize::from(u16::from_be_bytes(header[4..6].try_into().unwrap()));
+    if payload.len() != len {
+        return Err(invalid(format!(
+            "Datagram declares {} bytes but carries {}",
+            len,
+            payload.len()
+        )));
+    }
+
+    Ok(payload)
+}
+
+pub struct UdpBroadcastSink {
+    addr: SocketAddr,
+    socket: UdpSocket,
+}
+
+impl UdpBroadcastSink {
+    pub fn new(addr: SocketAddr) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+
+        Ok(Self { addr, socket })
+    }
+}
+
+impl OutputSink for UdpBroadcastSink {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let datagram = encode_datagram(spi_data)?;
+        if let Err(err) = self.socket.send_to(&datagram, self.addr) {
+            warn!("UDP output to {} unavailable: {}", self.addr, err);
+        }
+
+        Ok(())
+    }
+}
+
+pub struct UdpFrameSource {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+}
+
+impl UdpFrameSource {
+    pub fn bind(port: u16) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?,
+            buffer: vec![0; MAX_DATAGRAM_LEN],
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    pub fn next_frame(&mut self) -> io::Result<Vec<u8>> {
+        let (len, _) = self.socket.recv_from(&mut self.buffer)?;
+        decode_datagram(&self.buffer[..len]).map(|spi_data| spi_data.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::udp::{decode_datagram, encode_datagram, UdpBroadcastSink, UdpFrameSource};
+    use crate::output::OutputSink;
+    use afterglow::led::LEDStrip;
+    use std::{
+        io,
+        net::{Ipv4Addr, SocketAddr},
+    };
+
+    #[test]
+    fn it_frames_datagrams_with_a_magic_and_length() {
+        assert_eq!(
+            encode_datagram(&[0x00, 0xe1, 0xff]).unwrap(),
+            vec![
+                0x41, 0x47, 0x55, 0x50, // Magic
+                0x00, 0x03, // Length
+                0x00, 0xe1, 0xff, // SPI data
+            ]
+        );
+        assert!(encode_datagram(&[0x00; 65502]).is_err());
+    }
+
+    #[test]
+    fn it_rejects_malformed_datagrams() {
+        let datagram = encode_datagram(&[0x00, 0xe1, 0xff]).unwrap();
+        assert_eq!(decode_datagram(&datagram).unwrap(), [0x00, 0xe1, 0xff]);
+
+        let mut bad_magic = datagram.clone();
+        bad_magic[0] = 0x00;
+        for datagram in [&bad_magic[..], &datagram[..4], &datagram[..8]] {
+            assert_eq!(
+                decode_datagram(datagram).unwrap_err().kind(),
+                io::ErrorKind::InvalidData
+            );
+        }
+    }
+
+    #[test]
+    fn it_sends_spi_data_over_localhost() {
+        let mut source = UdpFrameSource::bind(0).unwrap();
+        let port = source.local_addr().unwrap().port();
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x4b8040]);
+        let mut sink =
+            UdpBroadcastSink::new(SocketAddr::from((Ipv4Addr::LOCALHOST, port))).unwrap();
+        sink.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(&source.next_frame().unwrap(), led_strip.get_spi_data());
+    }
+}
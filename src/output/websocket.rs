@@ -0,0 +1,114 @@
+use crate::output::OutputSink;
+use afterglow::led::decode_spi_data;
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing::warn;
+use tungstenite::{Message, WebSocket};
+
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+type Clients = Arc<Mutex<Vec<WebSocket<TcpStream>>>>;
+
+fn encode_frame(colors: &[(u8, u8, u8)]) -> String {
+    let colors: Vec<String> = colors
+        .iter()
+        .map(|&(r, g, b)| format!("\"#{:02x}{:02x}{:02x}\"", r, g, b))
+        .collect();
+    format!("[{}]", colors.join(","))
+}
+
+fn accept(stream: TcpStream) -> io::Result<WebSocket<TcpStream>> {
+    stream.set_nodelay(true)?;
+    let websocket = tungstenite::accept(stream).map_err(io::Error::other)?;
+    websocket.get_ref().set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    Ok(websocket)
+}
+
+pub struct WsServer {
+    local_addr: SocketAddr,
+    clients: Clients,
+}
+
+impl WsServer {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let clients: Clients = Arc::default();
+
+        let accepted = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream.and_then(accept) {
+                    Ok(websocket) => accepted.lock().unwrap().push(websocket),
+                    Err(err) => warn!("Failed to accept WebSocket client: {}", err),
+                }
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            clients,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl OutputSink for WsServer {
+    fn write(&mut self, spi_data: &[u8]) -> io::Result<()> {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() {
+            return Ok(());
+        }
+
+        let frame = encode_frame(&decode_spi_data(spi_data));
+        // Clients that disconnected or fell too far behind are dropped
+        clients.retain_mut(|websocket| websocket.send(Message::text(frame.clone())).is_ok());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::websocket::{encode_frame, WsServer};
+    use crate::output::OutputSink;
+    use afterglow::led::LEDStrip;
+    use std::{thread, time::Duration};
+    use tungstenite::Message;
+
+    #[test]
+    fn it_encodes_a_frame_as_a_json_array() {
+        assert_eq!(
+            encode_frame(&[(0xff, 0x00, 0x00), (0x4b, 0x80, 0x40)]),
+            "[\"#ff0000\",\"#4b8040\"]"
+        );
+        assert_eq!(encode_frame(&[]), "[]");
+    }
+
+    #[test]
+    fn it_pushes_frames_to_connected_clients() {
+        let mut server = WsServer::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let (mut client, _) =
+            tungstenite::connect(format!("ws://{}", server.local_addr())).unwrap();
+        while server.clients.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x4b8040]);
+        server.write(led_strip.get_spi_data()).unwrap();
+
+        assert_eq!(
+            client.read().unwrap(),
+            Message::text("[\"#ff0000\",\"#4b8040\"]")
+        );
+    }
+}
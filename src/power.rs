@@ -0,0 +1,262 @@
+use crate::led::LEDStrip;
+
+const MILLIAMPS_PER_CHANNEL: f32 = 20.0;
+const ABL_KP: f32 = 0.1;
+const ABL_KI: f32 = 0.002;
+const ABL_AVERAGE_WEIGHT: f32 = 0.2;
+const ABL_INTEGRAL_LIMIT: f32 = 1.0;
+const ABL_MIN_MULTIPLIER: f32 = 0.05;
+
+pub fn estimate_milliamps<const N: usize>(led_strip: &LEDStrip<N>) -> f32 {
+    let channel_total: u32 = (0..N)
+        .map(|index| {
+            let (r, g, b) = led_strip.get_led(index);
+            u32::from(r) + u32::from(g) + u32::from(b)
+        })
+        .sum();
+
+    channel_total as f32 * MILLIAMPS_PER_CHANNEL / 255.0
+}
+
+pub fn limit_power<const N: usize>(led_strip: &mut LEDStrip<N>, max_milliamps: u32) {
+    let mut colors: Vec<u32> = (0..N)
+        .map(|index| {
+            let (r, g, b) = led_strip.get_led(index);
+            (u32::from(r) << 16) | (u32::from(g) << 8) | u32::from(b)
+        })
+        .collect();
+
+    if PowerBudget::new(max_milliamps, N).scale(&mut colors) {
+        for (index, color) in colors.into_iter().enumerate() {
+            led_strip.set_led(index, color);
+        }
+    }
+}
+
+pub struct PowerBudget {
+    max_milliamps: u32,
+    num_leds: usize,
+}
+
+impl PowerBudget {
+    pub fn new(max_milliamps: u32, num_leds: usize) -> Self {
+        Self {
+            max_milliamps,
+            num_leds,
+        }
+    }
+
+    pub fn scale(&self, colors: &mut [u32]) -> bool {
+        assert_eq!(
+            colors.len(),
+            self.num_leds,
+            "Power budget is for {} LEDs",
+            self.num_leds
+        );
+
+        let channel_total: u32 = colors
+            .iter()
+            .map(|color| {
+                let [_, r, g, b] = color.to_be_bytes();
+                u32::from(r) + u32::from(g) + u32::from(b)
+            })
+            .sum();
+        let estimated_milliamps = channel_total as f32 * MILLIAMPS_PER_CHANNEL / 255.0;
+        if estimated_milliamps <= self.max_milliamps as f32 {
+            return false;
+        }
+
+        let scale = self.max_milliamps as f32 / estimated_milliamps;
+        let scale_channel = |channel: u8| (f32::from(channel) * scale) as u32;
+        for color in colors.iter_mut() {
+            let [_, r, g, b] = color.to_be_bytes();
+            *color = (scale_channel(r) << 16) | (scale_channel(g) << 8) | scale_channel(b);
+        }
+        true
+    }
+}
+
+pub struct AutoBrightnessLimiter {
+    target_milliamps: f32,
+    average_milliamps: Option<f32>,
+    integral: f32,
+    multiplier: f32,
+}
+
+impl AutoBrightnessLimiter {
+    pub fn new(target_milliamps: u32) -> Self {
+        assert!(target_milliamps > 0, "ABL target must be above 0 mA");
+
+        Self {
+            target_milliamps: target_milliamps as f32,
+            average_milliamps: None,
+            integral: 0.0,
+            multiplier: 1.0,
+        }
+    }
+
+    fn update(&mut self, milliamps: f32) {
+        let average_milliamps = match self.average_milliamps {
+            Some(average) => average + ABL_AVERAGE_WEIGHT * (milliamps - average),
+            None => milliamps,
+        };
+        self.average_milliamps = Some(average_milliamps);
+
+        let error = (self.target_milliamps - average_milliamps) / self.target_milliamps;
+        let integral = (self.integral + error).clamp(-ABL_INTEGRAL_LIMIT, ABL_INTEGRAL_LIMIT);
+        let multiplier = self.multiplier + ABL_KP * error + ABL_KI * integral;
+
+        self.multiplier = multiplier.clamp(ABL_MIN_MULTIPLIER, 1.0);
+        // Stop integrating while the multiplier is pinned so it can react
+        // as soon as the draw crosses the target
+        if self.multiplier == multiplier {
+            self.integral = integral;
+        }
+    }
+
+    pub fn apply<const N: usize>(&mut self, led_strip: &mut LEDStrip<N>) {
+        self.update(estimate_milliamps(led_strip) * self.multiplier);
+        if self.multiplier < 1.0 {
+            led_strip.scale_brightness(self.multiplier);
+        }
+    }
+}
+
+// The ABL step followed by the hard current cap, in the order every frame
+// from the capture loop goes through them
+pub struct PowerLimiter {
+    abl: Option<AutoBrightnessLimiter>,
+    max_milliamps: Option<u32>,
+}
+
+impl PowerLimiter {
+    pub fn new(abl_target_milliamps: Option<u32>, max_milliamps: Option<u32>) -> Self {
+        Self {
+            abl: abl_target_milliamps.map(AutoBrightnessLimiter::new),
+            max_milliamps,
+        }
+    }
+
+    pub fn apply<const N: usize>(&mut self, led_strip: &mut LEDStrip<N>) {
+        if let Some(abl) = &mut self.abl {
+            abl.apply(led_strip);
+        }
+        if let Some(max_milliamps) = self.max_milliamps {
+            limit_power(led_strip, max_milliamps);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::led::LEDStrip;
+    use crate::power::{
+        estimate_milliamps, limit_power, AutoBrightnessLimiter, PowerBudget, PowerLimiter,
+    };
+
+    #[test]
+    fn it_estimates_current_draw() {
+        let led_strip = LEDStrip::new_with_data([0xffffff; 10]);
+        assert_eq!(estimate_milliamps(&led_strip), 600.0);
+
+        let led_strip = LEDStrip::new_with_data([0xff0000, 0x000000]);
+        assert_eq!(estimate_milliamps(&led_strip), 20.0);
+    }
+
+    #[test]
+    fn it_scales_a_full_white_strip_to_fit_the_budget() {
+        let mut led_strip = LEDStrip::new_with_data([0xffffff; 10]);
+
+        limit_power(&mut led_strip, 300);
+
+        assert!(estimate_milliamps(&led_strip) <= 300.0);
+        for index in 0..10 {
+            assert_eq!(led_strip.get_led(index), (127, 127, 127));
+        }
+    }
+
+    #[test]
+    fn it_preserves_color_ratios_when_scaling() {
+        let mut led_strip = LEDStrip::new_with_data([0xff8000; 10]);
+
+        limit_power(&mut led_strip, 150);
+
+        assert!(estimate_milliamps(&led_strip) <= 150.0);
+        assert_eq!(led_strip.get_led(0), (127, 63, 0));
+    }
+
+    #[test]
+    fn it_leaves_a_dim_strip_unchanged() {
+        let mut led_strip = LEDStrip::new_with_data([0x101010; 10]);
+        let spi_data = led_strip.get_spi_data().clone();
+
+        limit_power(&mut led_strip, 300);
+
+        assert_eq!(led_strip.get_spi_data(), &spi_data);
+    }
+
+    #[test]
+    fn it_scales_colors_down_to_the_budget() {
+        let mut colors = [0xffffff; 60];
+
+        assert!(PowerBudget::new(1000, 60).scale(&mut colors));
+
+        let expected = (255.0 * 1000.0 / 3600.0) as u32;
+        assert_eq!(expected, 70);
+        assert!(colors
+            .iter()
+            .all(|&color| color == (expected << 16) | (expected << 8) | expected));
+    }
+
+    #[test]
+    fn it_leaves_colors_within_the_budget_unchanged() {
+        let mut colors = [0x101010, 0xff0000];
+
+        assert!(!PowerBudget::new(1000, 2).scale(&mut colors));
+        assert_eq!(colors, [0x101010, 0xff0000]);
+    }
+
+    #[test]
+    fn it_settles_the_average_draw_on_the_abl_target() {
+        let mut abl = AutoBrightnessLimiter::new(1000);
+
+        for _ in 0..200 {
+            let mut led_strip = LEDStrip::new_with_data([0xffffff; 60]);
+            abl.apply(&mut led_strip);
+        }
+
+        let average_milliamps = abl.average_milliamps.unwrap();
+        assert!(
+            (950.0..=1050.0).contains(&average_milliamps),
+            "{}",
+            average_milliamps
+        );
+        assert!((0.25..=0.3).contains(&abl.multiplier), "{}", abl.multiplier);
+    }
+
+    #[test]
+    fn it_leaves_strips_under_the_abl_target_at_full_brightness() {
+        let mut abl = AutoBrightnessLimiter::new(1000);
+
+        for _ in 0..50 {
+            let mut led_strip = LEDStrip::new_with_data([0x404040; 60]);
+            let spi_data = led_strip.get_spi_data().clone();
+            abl.apply(&mut led_strip);
+            assert_eq!(led_strip.get_spi_data(), &spi_data);
+        }
+        assert_eq!(abl.multiplier, 1.0);
+    }
+
+    #[test]
+    fn it_caps_the_draw_after_the_abl_step() {
+        let mut limiter = PowerLimiter::new(Some(1000), Some(300));
+        let mut led_strip = LEDStrip::new_with_data([0xffffff; 10]);
+
+        limiter.apply(&mut led_strip);
+        assert!(estimate_milliamps(&led_strip) <= 300.0);
+
+        let mut led_strip = LEDStrip::new_with_data([0xffffff; 10]);
+        PowerLimiter::new(None, None).apply(&mut led_strip);
+        assert_eq!(estimate_milliamps(&led_strip), 600.0);
+    }
+}
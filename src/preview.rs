@@ -0,0 +1,221 @@
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+use std::io::Write;
+
+pub fn render_segment_colors(segment_map: &[Option<usize>], segment_colors: &[u32]) -> Vec<u32> {
+    segment_map
+        .iter()
+        .map(|segment| match *segment {
+            Some(segment) => segment_colors[segment],
+            None => 0,
+        })
+        .collect()
+}
+
+pub fn draw_led_ring(
+    buffer: &mut [u32],
+    led_values: &[u32],
+    segment_map: &[Option<usize>],
+    width: usize,
+    (center_x, center_y): (usize, usize),
+    inner_radius: f64,
+    thickness: f64,
+) {
+    let outer_radius = inner_radius + thickness;
+
+    for (index, (pixel, segment)) in buffer.iter_mut().zip(segment_map).enumerate() {
+        let Some(segment) = *segment else {
+            continue;
+        };
+
+        let dx = center_x as f64 - (index % width) as f64;
+        let dy = (index / width) as f64 - center_y as f64;
+        if dx.hypot(dy) < outer_radius {
+            *pixel = led_values[segment];
+        }
+    }
+}
+
+pub fn draw_circle(
+    buffer: &mut [u32],
+    width: usize,
+    (center_x, center_y): (usize, usize),
+    radius: f64,
+    color: u32,
+) {
+    for (index, pixel) in buffer.iter_mut().enumerate() {
+        let dx = center_x as f64 - (index % width) as f64;
+        let dy = (index / width) as f64 - center_y as f64;
+        if (dx.hypot(dy) - radius).abs() < 0.5 {
+            *pixel = color;
+        }
+    }
+}
+
+pub fn draw_rect(
+    buffer: &mut [u32],
+    width: usize,
+    (x, y, rect_width, rect_height): (u32, u32, u32, u32),
+    color: u32,
+) {
+    let (left, top) = (x as usize, y as usize);
+    let right = left + rect_width as usize - 1;
+    let bottom = top + rect_height as usize - 1;
+
+    for (index, pixel) in buffer.iter_mut().enumerate() {
+        let (px, py) = (index % width, index / width);
+        let on_edge = (px == left || px == right) && (top..=bottom).contains(&py)
+            || (py == top || py == bottom) && (left..=right).contains(&px);
+        if on_edge {
+            *pixel = color;
+        }
+    }
+}
+
+pub fn write_png<W: Write>(
+    writer: W,
+    pixels: &[u32],
+    width: u32,
+    height: u32,
+) -> Result<(), EncodingError> {
+    let mut encoder = Encoder::new(writer, width, height);
+    encoder.set_color(ColorType::Rgb);
+    encoder.set_depth(BitDepth::Eight);
+
+    let data: Vec<u8> = pixels
+        .iter()
+        .flat_map(|&color| [(color >> 16) as u8, (color >> 8) as u8, color as u8])
+        .collect();
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&data)?;
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preview::{draw_circle, draw_led_ring, draw_rect, render_segment_colors, write_png};
+    use afterglow::segment_map::{
+        build_segment_map, inner_radius, Orientation, DEFAULT_EDGE_FRACTION,
+    };
+    use png::Decoder;
+
+    #[test]
+    fn it_renders_segment_colors_over_the_mapped_pixels() {
+        let segment_map = vec![Some(0), Some(0), None, Some(2)];
+
+        assert_eq!(
+            render_segment_colors(&segment_map, &[0xff0000, 0x00ff00, 0x0000ff]),
+            vec![0xff0000, 0xff0000, 0x000000, 0x0000ff]
+        );
+    }
+
+    #[test]
+    fn it_draws_each_segment_arc_in_its_led_color() {
+        let (width, height) = (40, 30);
+        let segment_map = build_segment_map(
+            12,
+            width,
+            height,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let led_values: Vec<u32> = (1..=12).map(|index| index * 0x111111).collect();
+        let source = vec![0xabcdef; (width * height) as usize];
+
+        let mut buffer = source.clone();
+        draw_led_ring(
+            &mut buffer,
+            &led_values,
+            &segment_map,
+            width as usize,
+            (20, 15),
+            inner_radius(width, height, DEFAULT_EDGE_FRACTION),
+            3.0,
+        );
+
+        let mut arc_pixels = 0;
+        for (index, segment) in segment_map.iter().enumerate() {
+            let (x, y) = ((index as u32 % width) as f64, (index as u32 / width) as f64);
+            let distance = (20.0 - x).hypot(y - 15.0);
+            match segment {
+                Some(segment) if distance < 10.0 => {
+                    assert_eq!(buffer[index], led_values[*segment]);
+                    if *segment == 0 {
+                        arc_pixels += 1;
+                    }
+                }
+                _ => assert_eq!(buffer[index], source[index]),
+            }
+        }
+        assert!(arc_pixels > 0);
+    }
+
+    #[test]
+    fn it_outlines_a_circle_around_the_frame_center() {
+        let mut buffer = vec![0x000000; 9 * 7];
+        draw_circle(&mut buffer, 9, (4, 3), 2.0, 0xffffff);
+
+        for (x, y) in [(2, 3), (6, 3), (4, 1), (4, 5), (5, 5)] {
+            assert_eq!(buffer[y * 9 + x], 0xffffff);
+        }
+        for (x, y) in [(4, 3), (5, 4), (6, 5), (0, 0), (8, 3)] {
+            assert_eq!(buffer[y * 9 + x], 0x000000);
+        }
+
+        let mut buffer = vec![0x000000; 9 * 7];
+        draw_circle(&mut buffer, 9, (4, 3), f64::INFINITY, 0xffffff);
+        assert!(buffer.iter().all(|&pixel| pixel == 0x000000));
+
+        let mut buffer = vec![0x000000; 9 * 7];
+        draw_circle(&mut buffer, 9, (2, 2), 1.0, 0xffffff);
+        for (x, y) in [(1, 2), (3, 2), (2, 1), (2, 3)] {
+            assert_eq!(buffer[y * 9 + x], 0xffffff);
+        }
+        assert_eq!(buffer[2 * 9 + 2], 0x000000);
+    }
+
+    #[test]
+    fn it_outlines_a_rectangle() {
+        let mut buffer = vec![0x000000; 9 * 7];
+        draw_rect(&mut buffer, 9, (2, 1, 5, 4), 0xffffff);
+
+        for (x, y) in [(2, 1), (6, 1), (4, 1), (2, 3), (6, 4), (4, 4)] {
+            assert_eq!(buffer[y * 9 + x], 0xffffff, "({}, {})", x, y);
+        }
+        for (x, y) in [(1, 1), (7, 1), (4, 2), (4, 0), (4, 5), (3, 3)] {
+            assert_eq!(buffer[y * 9 + x], 0x000000, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn it_writes_the_segment_map_as_a_png() {
+        let segment_map = build_segment_map(
+            12,
+            9,
+            7,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let segment_colors: Vec<u32> = (0..12).map(|index| index * 0x111111).collect();
+
+        let mut png = Vec::new();
+        write_png(
+            &mut png,
+            &render_segment_colors(&segment_map, &segment_colors),
+            9,
+            7,
+        )
+        .unwrap();
+
+        let mut reader = Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (9, 7));
+        assert_eq!(info.buffer_size(), 9 * 7 * 3);
+
+        let segment = segment_map[0].unwrap() as u8;
+        assert_eq!(&data[0..3], [segment * 0x11; 3]);
+    }
+}
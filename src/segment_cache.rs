@@ -0,0 +1,85 @@
+use afterglow::segment_map::SegmentMap;
+use std::{
+    env, fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_home.join("afterglow"))
+}
+
+fn cache_path(dir: &Path, params: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    params.hash(&mut hasher);
+    dir.join(format!("segmap-{:016x}.bin", hasher.finish()))
+}
+
+// Anything wrong with the cached map only costs a rebuild, since the file
+// also records the parameters it was built from
+pub fn load_or_build<E>(
+    dir: &Path,
+    params: &str,
+    build: impl FnOnce() -> Result<SegmentMap, E>,
+) -> Result<SegmentMap, E> {
+    let path = cache_path(dir, params);
+    match SegmentMap::load(&path, params) {
+        Ok(segment_map) => return Ok(segment_map),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => info!("Rebuilding cached segment map {}: {}", path.display(), err),
+    }
+
+    let segment_map = build()?;
+    if let Err(err) = fs::create_dir_all(dir).and_then(|_| segment_map.save(&path, params)) {
+        warn!("Failed to cache segment map to {}: {}", path.display(), err);
+    }
+    Ok(segment_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::segment_cache::{cache_path, load_or_build};
+    use afterglow::segment_map::SegmentMap;
+    use std::{convert::Infallible, env, fs, process};
+
+    #[test]
+    fn it_rebuilds_the_cached_map_when_parameters_change() {
+        let dir = env::temp_dir().join(format!("afterglow-segmap-{}", process::id()));
+        let segment_map = SegmentMap::new(vec![Some(0), None, Some(1), Some(1)], 2, 2);
+        let build = || Ok::<_, Infallible>(segment_map.clone());
+        let cached = || -> Result<SegmentMap, Infallible> { panic!("Should load the cached map") };
+
+        assert_eq!(
+            load_or_build(&dir, "36 leds", build),
+            Ok(segment_map.clone())
+        );
+        assert_eq!(
+            load_or_build(&dir, "36 leds", cached),
+            Ok(segment_map.clone())
+        );
+
+        let rebuilt = SegmentMap::new(vec![Some(0); 4], 2, 1);
+        assert_eq!(
+            load_or_build(&dir, "24 leds", || Ok::<_, Infallible>(rebuilt.clone())),
+            Ok(rebuilt)
+        );
+        assert_eq!(
+            load_or_build(&dir, "36 leds", cached),
+            Ok(segment_map.clone())
+        );
+
+        fs::write(cache_path(&dir, "36 leds"), b"AGSM\x01garbage").unwrap();
+        assert_eq!(
+            load_or_build(&dir, "36 leds", build),
+            Ok(segment_map.clone())
+        );
+        assert_eq!(load_or_build(&dir, "36 leds", cached), Ok(segment_map));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,3341 @@
+use crate::color::{hsv_to_rgb, linear_to_srgb, srgb_to_linear};
+use clap::ValueEnum;
+use png::{BitDepth, ColorType, Encoder};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+use std::{
+    error::Error,
+    f64::consts::{PI, TAU},
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter},
+    ops::Range,
+    path::Path,
+    str::FromStr,
+};
+use tracing::instrument;
+#[cfg(feature = "simd")]
+use wide::{u16x8, u32x8, u8x16};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Rotation {
+    #[default]
+    #[value(name = "0")]
+    Rotate0,
+    #[value(name = "90")]
+    Rotate90,
+    #[value(name = "180")]
+    Rotate180,
+    #[value(name = "270")]
+    Rotate270,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Orientation {
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub rotation: Rotation,
+    pub rotation_degrees: f64,
+    pub clockwise: bool,
+    pub elliptical: bool,
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Self {
+            flip_horizontal: false,
+            flip_vertical: false,
+            rotation: Rotation::default(),
+            rotation_degrees: 0.0,
+            clockwise: true,
+            elliptical: false,
+        }
+    }
+}
+
+impl Orientation {
+    // Flips are applied to the finished map with mirror_segment_map, since
+    // negating offsets from the center pixel is off by one on even sizes
+    fn transform(&self, dx: i32, dy: i32) -> (i32, i32) {
+        match self.rotation {
+            Rotation::Rotate0 => (dx, dy),
+            Rotation::Rotate90 => (-dy, dx),
+            Rotation::Rotate180 => (-dx, -dy),
+            Rotation::Rotate270 => (dy, -dx),
+        }
+    }
+
+    fn swaps_axes(&self) -> bool {
+        matches!(self.rotation, Rotation::Rotate90 | Rotation::Rotate270)
+    }
+}
+
+pub const DEFAULT_EDGE_FRACTION: f64 = 0.5;
+
+pub fn inner_radius(width: u32, height: u32, edge_fraction: f64) -> f64 {
+    let half_size = (width / 2).min(height / 2);
+    (f64::from(half_size) * edge_fraction).floor()
+}
+
+pub fn outer_radius(width: u32, height: u32, outer_fraction: Option<f64>) -> f64 {
+    let half_size = (width / 2).min(height / 2);
+    outer_fraction.map_or(f64::INFINITY, |outer_fraction| {
+        (f64::from(half_size) * outer_fraction).floor()
+    })
+}
+
+#[instrument(level = "debug")]
+pub fn build_segment_map(
+    num_leds: usize,
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+    edge_fraction: f64,
+    outer_fraction: Option<f64>,
+) -> Vec<Option<usize>> {
+    let theta_scalar = (num_leds as f64) / TAU;
+
+    build_angular_segment_map(
+        num_leds,
+        width,
+        height,
+        orientation,
+        edge_fraction,
+        outer_fraction,
+        |theta| Some(((theta * theta_scalar).floor() as usize).min(num_leds - 1)),
+    )
+}
+
+#[instrument(level = "debug")]
+pub fn build_weighted_segment_map(
+    boundaries: &[f64],
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+    edge_fraction: f64,
+    outer_fraction: Option<f64>,
+) -> Vec<Option<usize>> {
+    assert!(!boundaries.is_empty(), "At least one segment is required");
+    assert!(
+        boundaries.is_sorted() && boundaries.iter().all(|angle| (0.0..TAU).contains(angle)),
+        "Segment boundaries must be sorted angles in [0, TAU)"
+    );
+
+    build_angular_segment_map(
+        boundaries.len(),
+        width,
+        height,
+        orientation,
+        edge_fraction,
+        outer_fraction,
+        |theta| {
+            // Angles before the first boundary wrap around into the last segment
+            Some(
+                boundaries
+                    .partition_point(|&boundary| boundary <= theta)
+                    .checked_sub(1)
+                    .unwrap_or(boundaries.len() - 1),
+            )
+        },
+    )
+}
+
+// One band of a multi-ring layout. Fractions are of the distance from the
+// frame center to its nearest edge, like --edge-fraction, and the start
+// angle in degrees is added to the orientation's rotation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RingZone {
+    pub num_leds: usize,
+    pub inner_fraction: f64,
+    pub outer_fraction: f64,
+    pub start_angle: f64,
+    pub clockwise: bool,
+}
+
+impl FromStr for RingZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected LEDS,INNER,OUTER[,START[,cw|ccw]], got: {}", s);
+
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [num_leds, inner_fraction, outer_fraction, ref rest @ ..] = parts[..] else {
+            return Err(invalid());
+        };
+        let (start_angle, clockwise) = match rest {
+            [] => (0.0, true),
+            [start_angle] => (start_angle.parse().map_err(|_| invalid())?, true),
+            [start_angle, "cw"] => (start_angle.parse().map_err(|_| invalid())?, true),
+            [start_angle, "ccw"] => (start_angle.parse().map_err(|_| invalid())?, false),
+            _ => return Err(invalid()),
+        };
+
+        Ok(RingZone {
+            num_leds: num_leds.parse().map_err(|_| invalid())?,
+            inner_fraction: inner_fraction.parse().map_err(|_| invalid())?,
+            outer_fraction: outer_fraction.parse().map_err(|_| invalid())?,
+            start_angle,
+            clockwise,
+        })
+    }
+}
+
+// Each zone's LEDs follow the previous zone's on one logical strip
+pub fn zone_ranges(zones: &[RingZone]) -> Vec<Range<usize>> {
+    zones
+        .iter()
+        .scan(0, |offset, zone| {
+            let start = *offset;
+            *offset += zone.num_leds;
+            Some(start..*offset)
+        })
+        .collect()
+}
+
+#[instrument(level = "debug")]
+pub fn build_zoned_segment_map(
+    zones: &[RingZone],
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+) -> Vec<Option<usize>> {
+    let mut segment_table = vec![None; (width * height).try_into().unwrap()];
+    let ranges = zone_ranges(zones);
+
+    // Outer zones are mapped last so pixels on a shared band edge belong to
+    // the zone starting there
+    let mut order: Vec<usize> = (0..zones.len()).collect();
+    order.sort_by(|&a, &b| zones[a].inner_fraction.total_cmp(&zones[b].inner_fraction));
+    for index in order {
+        let zone = zones[index];
+        let zone_map = build_segment_map(
+            zone.num_leds,
+            width,
+            height,
+            Orientation {
+                rotation_degrees: orientation.rotation_degrees + zone.start_angle,
+                clockwise: zone.clockwise,
+                ..orientation
+            },
+            zone.inner_fraction,
+            Some(zone.outer_fraction),
+        );
+        for (pixel, segment) in segment_table.iter_mut().zip(zone_map) {
+            if let Some(segment) = segment {
+                *pixel = Some(ranges[index].start + segment);
+            }
+        }
+    }
+
+    segment_table
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LedRange {
+    Span { start: f64, end: f64 },
+    Centered { center: f64, width: f64 },
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct LayoutFile {
+    leds: Vec<LedRange>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LayoutError {
+    Parse(String),
+    InvalidRange(usize),
+    Overlap(usize, usize),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Parse(err) => write!(f, "invalid LED layout: {}", err),
+            LayoutError::InvalidRange(led) => write!(
+                f,
+                "LED {} must cover more than 0 and at most 360 degrees",
+                led
+            ),
+            LayoutError::Overlap(first, second) => {
+                write!(f, "LEDs {} and {} have overlapping ranges", first, second)
+            }
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedLayout {
+    ranges: Vec<(f64, f64)>,
+}
+
+impl LedLayout {
+    pub fn new(ranges: Vec<(f64, f64)>) -> Result<Self, LayoutError> {
+        for (led, &(_, width)) in ranges.iter().enumerate() {
+            if width.is_nan() || width <= 0.0 || width > TAU {
+                return Err(LayoutError::InvalidRange(led));
+            }
+        }
+
+        let ranges: Vec<(f64, f64)> = ranges
+            .into_iter()
+            .map(|(start, width)| (start.rem_euclid(TAU), width))
+            .collect();
+        for (first, &(first_start, first_width)) in ranges.iter().enumerate() {
+            for (second, &(second_start, second_width)) in ranges.iter().enumerate().skip(first + 1)
+            {
+                if (second_start - first_start).rem_euclid(TAU) < first_width
+                    || (first_start - second_start).rem_euclid(TAU) < second_width
+                {
+                    return Err(LayoutError::Overlap(first, second));
+                }
+            }
+        }
+
+        Ok(Self { ranges })
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_toml(s: &str) -> Result<Self, LayoutError> {
+        let file: LayoutFile =
+            toml::from_str(s).map_err(|err| LayoutError::Parse(err.to_string()))?;
+
+        Self::new(
+            file.leds
+                .into_iter()
+                .map(|range| match range {
+                    LedRange::Span { start, end } => {
+                        // Ranges ending before their start wrap around past 360 degrees
+                        let width = (end - start).rem_euclid(360.0);
+                        let width = if width == 0.0 && end != start {
+                            360.0
+                        } else {
+                            width
+                        };
+                        (start.to_radians(), width.to_radians())
+                    }
+                    LedRange::Centered { center, width } => {
+                        ((center - width / 2.0).to_radians(), width.to_radians())
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: &Path) -> Result<Self, LayoutError> {
+        let s = fs::read_to_string(path)
+            .map_err(|err| LayoutError::Parse(format!("{}: {}", path.display(), err)))?;
+        Self::from_toml(&s)
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn load(path: &Path) -> Result<Self, LayoutError> {
+        Err(LayoutError::Parse(format!(
+            "{}: reading LED layouts needs the serde feature",
+            path.display()
+        )))
+    }
+
+    pub fn num_leds(&self) -> usize {
+        self.ranges.len()
+    }
+
+    fn led_at(&self, theta: f64) -> Option<usize> {
+        self.ranges
+            .iter()
+            .position(|&(start, width)| (theta - start).rem_euclid(TAU) < width)
+    }
+}
+
+#[instrument(level = "debug", skip(layout))]
+pub fn build_segment_map_from_layout(
+    layout: &LedLayout,
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+    edge_fraction: f64,
+    outer_fraction: Option<f64>,
+) -> Vec<Option<usize>> {
+    assert!(layout.num_leds() > 0, "At least one segment is required");
+
+    build_angular_segment_map(
+        layout.num_leds(),
+        width,
+        height,
+        orientation,
+        edge_fraction,
+        outer_fraction,
+        |theta| layout.led_at(theta),
+    )
+}
+
+fn build_angular_segment_map(
+    num_leds: usize,
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+    edge_fraction: f64,
+    outer_fraction: Option<f64>,
+    segment_at: impl Fn(f64) -> Option<usize>,
+) -> Vec<Option<usize>> {
+    let mut segment_table: Vec<Option<usize>> =
+        Vec::with_capacity((width * height).try_into().unwrap());
+
+    let width = width as i32;
+    let height = height as i32;
+    let half_width = width / 2;
+    let half_height = height / 2;
+
+    // Ellipses map an annulus in coordinates normalized to the frame size,
+    // leaving the corners outside the ellipse unmapped
+    let (scale_x, scale_y, edges) = if orientation.elliptical {
+        let (half_width, half_height) = (f64::from(half_width), f64::from(half_height));
+        let (scale_x, scale_y) = if orientation.swaps_axes() {
+            (half_height, half_width)
+        } else {
+            (half_width, half_height)
+        };
+        (
+            scale_x,
+            scale_y,
+            edge_fraction..=outer_fraction.unwrap_or(1.0),
+        )
+    } else {
+        let (width, height) = (width as u32, height as u32);
+        let edge = inner_radius(width, height, edge_fraction);
+        (1.0, 1.0, edge..=outer_radius(width, height, outer_fraction))
+    };
+
+    let theta_offset = orientation.rotation_degrees.rem_euclid(360.0).to_radians();
+
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = orientation.transform(half_width - x, y - half_height);
+            let (dx, dy) = (dx as f64 / scale_x, dy as f64 / scale_y);
+            segment_table.push(if edges.contains(&dx.hypot(dy)) {
+                // Wraps into (0, TAU] so the center row ray where the last
+                // segment meets the first stays in the last at any rotation
+                let theta = TAU - (PI - dy.atan2(dx) - theta_offset).rem_euclid(TAU);
+                segment_at(theta).map(|segment| {
+                    if orientation.clockwise {
+                        segment
+                    } else {
+                        num_leds - 1 - segment
+                    }
+                })
+            } else {
+                None
+            });
+        }
+    }
+
+    mirror_segment_map(
+        segment_table,
+        width as u32,
+        orientation.flip_horizontal,
+        orientation.flip_vertical,
+    )
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SegmentLayout {
+    #[default]
+    Circle,
+    Ellipse,
+    Border,
+    Bottom,
+    Matrix,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Corner {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EdgeCounts {
+    pub top: usize,
+    pub right: usize,
+    pub bottom: usize,
+    pub left: usize,
+}
+
+impl EdgeCounts {
+    pub fn total(&self) -> usize {
+        self.top + self.right + self.bottom + self.left
+    }
+}
+
+impl FromStr for EdgeCounts {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected TOP,RIGHT,BOTTOM,LEFT LED counts, got: {}", s);
+
+        let counts: Vec<usize> = s
+            .split(',')
+            .map(|count| count.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let [top, right, bottom, left] = counts[..] else {
+            return Err(invalid());
+        };
+
+        Ok(EdgeCounts {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+}
+
+pub const DEFAULT_BORDER_THICKNESS: f64 = 0.1;
+
+#[derive(Clone, Copy)]
+enum Edge {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+const CLOCKWISE_EDGES: [Edge; 4] = [Edge::Top, Edge::Right, Edge::Bottom, Edge::Left];
+
+impl Edge {
+    fn count(self, counts: EdgeCounts) -> usize {
+        match self {
+            Edge::Top => counts.top,
+            Edge::Right => counts.right,
+            Edge::Bottom => counts.bottom,
+            Edge::Left => counts.left,
+        }
+    }
+
+    fn position(self, x: f64, y: f64, width: f64, height: f64) -> f64 {
+        match self {
+            Edge::Top => x / width,
+            Edge::Right => y / height,
+            Edge::Bottom => 1.0 - x / width,
+            Edge::Left => 1.0 - y / height,
+        }
+    }
+
+    fn point(self, position: f64, width: f64, height: f64, inset: f64) -> (f64, f64) {
+        match self {
+            Edge::Top => (position * width, inset),
+            Edge::Right => (width - inset, position * height),
+            Edge::Bottom => ((1.0 - position) * width, height - inset),
+            Edge::Left => (inset, (1.0 - position) * height),
+        }
+    }
+}
+
+#[instrument(level = "debug")]
+pub fn build_border_segment_map(
+    counts: EdgeCounts,
+    start: Corner,
+    width: u32,
+    height: u32,
+    thickness_fraction: f64,
+) -> Vec<Option<usize>> {
+    let mut segment_table: Vec<Option<usize>> =
+        Vec::with_capacity((width * height).try_into().unwrap());
+
+    let thickness = ((f64::from(width.min(height)) * thickness_fraction).round() as u32).max(1);
+
+    let mut offsets = [0; 4];
+    let mut offset = 0;
+    for index in 0..CLOCKWISE_EDGES.len() {
+        let edge = (start as usize + index) % CLOCKWISE_EDGES.len();
+        offsets[edge] = offset;
+        offset += CLOCKWISE_EDGES[edge].count(counts);
+    }
+
+    let inset = f64::from(thickness) / 2.0;
+    let (frame_width, frame_height) = (f64::from(width), f64::from(height));
+
+    for y in 0..height {
+        for x in 0..width {
+            let in_edge = [
+                y < thickness,
+                x >= width.saturating_sub(thickness),
+                y >= height.saturating_sub(thickness),
+                x < thickness,
+            ];
+            let (px, py) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+
+            let nearest = CLOCKWISE_EDGES
+                .iter()
+                .zip(offsets)
+                .zip(in_edge)
+                .filter(|&((&edge, _), in_edge)| in_edge && edge.count(counts) > 0)
+                .map(|((&edge, offset), _)| {
+                    let count = edge.count(counts);
+                    let position = edge.position(px, py, frame_width, frame_height);
+                    let led = ((position * count as f64).floor() as usize).min(count - 1);
+
+                    let center = (led as f64 + 0.5) / count as f64;
+                    let (cx, cy) = edge.point(center, frame_width, frame_height, inset);
+                    ((cx - px).hypot(cy - py), offset + led)
+                })
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+            segment_table.push(nearest.map(|(_, segment)| segment));
+        }
+    }
+
+    segment_table
+}
+
+pub fn mirror_segment_map(
+    segment_map: Vec<Option<usize>>,
+    width: u32,
+    mirror_x: bool,
+    mirror_y: bool,
+) -> Vec<Option<usize>> {
+    if !mirror_x && !mirror_y {
+        return segment_map;
+    }
+
+    let width = width as usize;
+    let height = segment_map.len() / width;
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let x = if mirror_x { width - 1 - x } else { x };
+            let y = if mirror_y { height - 1 - y } else { y };
+            segment_map[y * width + x]
+        })
+        .collect()
+}
+
+pub const DEFAULT_BAND_FRACTION: f64 = 0.25;
+
+#[instrument(level = "debug")]
+pub fn build_bottom_segment_map(
+    num_leds: usize,
+    width: u32,
+    height: u32,
+    band_fraction: f64,
+) -> Vec<Option<usize>> {
+    let band_height = ((f64::from(height) * band_fraction).round() as u32).clamp(1, height);
+    let band_top = height - band_height;
+
+    (0..height)
+        .flat_map(|y| {
+            (0..width).map(move |x| (y >= band_top).then(|| x as usize * num_leds / width as usize))
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatrixSize {
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl FromStr for MatrixSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected a COLSxROWS matrix size, got: {}", s);
+
+        let (cols, rows) = s.split_once('x').ok_or_else(invalid)?;
+        let cols: usize = cols.trim().parse().map_err(|_| invalid())?;
+        let rows: usize = rows.trim().parse().map_err(|_| invalid())?;
+        if cols == 0 || rows == 0 {
+            return Err(invalid());
+        }
+
+        Ok(MatrixSize { cols, rows })
+    }
+}
+
+#[instrument(level = "debug")]
+pub fn build_matrix_segment_map(
+    cols: usize,
+    rows: usize,
+    serpentine: bool,
+    width: u32,
+    height: u32,
+) -> Vec<Option<usize>> {
+    assert!(cols > 0 && rows > 0, "Matrix must have at least one LED");
+
+    (0..height as usize)
+        .flat_map(|y| {
+            let row = y * rows / height as usize;
+            (0..width as usize).map(move |x| {
+                let col = x * cols / width as usize;
+                // Serpentine wiring runs every other row from right to left
+                let col = if serpentine && row % 2 == 1 {
+                    cols - 1 - col
+                } else {
+                    col
+                };
+                Some(row * cols + col)
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crop {
+    pub top: f64,
+    pub right: f64,
+    pub bottom: f64,
+    pub left: f64,
+}
+
+impl Crop {
+    // Returns the x, y, width and height of the region left after cropping
+    pub fn region(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let inset = |size: u32, fraction: f64| (f64::from(size) * fraction).round() as u32;
+
+        let x = inset(width, self.left).min(width - 1);
+        let y = inset(height, self.top).min(height - 1);
+        let cropped_width = (width - inset(width, self.right)).saturating_sub(x).max(1);
+        let cropped_height = (height - inset(height, self.bottom))
+            .saturating_sub(y)
+            .max(1);
+        (x, y, cropped_width, cropped_height)
+    }
+}
+
+impl FromStr for Crop {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "expected an inset or TOP,RIGHT,BOTTOM,LEFT insets as fractions of the frame, got: {}",
+                s
+            )
+        };
+
+        let insets: Vec<f64> = s
+            .split(',')
+            .map(|inset| inset.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let crop = match insets[..] {
+            [inset] => Crop {
+                top: inset,
+                right: inset,
+                bottom: inset,
+                left: inset,
+            },
+            [top, right, bottom, left] => Crop {
+                top,
+                right,
+                bottom,
+                left,
+            },
+            _ => return Err(invalid()),
+        };
+
+        if insets.iter().any(|inset| !(0.0..1.0).contains(inset))
+            || crop.left + crop.right >= 1.0
+            || crop.top + crop.bottom >= 1.0
+        {
+            return Err(format!("crop {} leaves nothing of the frame", s));
+        }
+
+        Ok(crop)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CropRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropRect {
+    // Clamps the rectangle to the frame, keeping at least one pixel
+    pub fn region(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let x = self.x.min(width - 1);
+        let y = self.y.min(height - 1);
+        (
+            x,
+            y,
+            self.width.clamp(1, width - x),
+            self.height.clamp(1, height - y),
+        )
+    }
+}
+
+impl FromStr for CropRect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected X,Y,WIDTH,HEIGHT in pixels, got: {}", s);
+
+        let values: Vec<u32> = s
+            .split(',')
+            .map(|value| value.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+        let [x, y, width, height] = values[..] else {
+            return Err(invalid());
+        };
+        if width == 0 || height == 0 {
+            return Err(format!("crop rectangle {} is empty", s));
+        }
+
+        Ok(CropRect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+pub fn uncrop_segment_map(
+    cropped: &[Option<usize>],
+    (x, y, cropped_width, cropped_height): (u32, u32, u32, u32),
+    width: u32,
+    height: u32,
+) -> Vec<Option<usize>> {
+    assert_eq!(
+        cropped.len(),
+        (cropped_width * cropped_height) as usize,
+        "Cropped segment map does not match the crop region"
+    );
+
+    let mut segment_table = vec![None; (width * height) as usize];
+    for (row, segments) in cropped.chunks_exact(cropped_width as usize).enumerate() {
+        let start = (y as usize + row) * width as usize + x as usize;
+        segment_table[start..start + segments.len()].copy_from_slice(segments);
+    }
+
+    segment_table
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FrameSizeError {
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for FrameSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} bytes of RGB data to match the segment map, got {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl Error for FrameSizeError {}
+
+fn mean_square_color((r, g, b): (u64, u64, u64), count: u64) -> u32 {
+    if count == 0 {
+        return 0;
+    }
+
+    let r = ((r / count) as f64).sqrt() as u32;
+    let g = ((g / count) as f64).sqrt() as u32;
+    let b = ((b / count) as f64).sqrt() as u32;
+    (r << 16) | (g << 8) | b
+}
+
+pub fn accumulate_pixels(
+    rgb: &[u8],
+    segment_map: &[Option<usize>],
+    sums: &mut [(u64, u64, u64)],
+    counts: &mut [u64],
+) {
+    for (pixel, segment) in rgb.chunks_exact(3).zip(segment_map) {
+        if let Some(segment) = *segment {
+            sums[segment].0 += u64::from(pixel[0]).pow(2);
+            sums[segment].1 += u64::from(pixel[1]).pow(2);
+            sums[segment].2 += u64::from(pixel[2]).pow(2);
+            counts[segment] += 1;
+        }
+    }
+}
+
+// Scans only the mapped pixels from SegmentMap::to_flat_lookup, leaving
+// nothing to branch on inside the loop
+pub fn accumulate_flat_lookup(
+    rgb: &[u8],
+    (pixel_indices, segment_indices): (&[u32], &[u32]),
+    sums: &mut [(u64, u64, u64)],
+    counts: &mut [u64],
+) {
+    for (&pixel, &segment) in pixel_indices.iter().zip(segment_indices) {
+        let (offset, segment) = (pixel as usize * 3, segment as usize);
+        sums[segment].0 += u64::from(rgb[offset]).pow(2);
+        sums[segment].1 += u64::from(rgb[offset + 1]).pow(2);
+        sums[segment].2 += u64::from(rgb[offset + 2]).pow(2);
+        counts[segment] += 1;
+    }
+}
+
+// 16 pixels fill three u8x16 vectors exactly
+#[cfg(feature = "simd")]
+const SIMD_BLOCK_LENGTH: usize = 48;
+
+// Squares are at most 255^2 and each u32 lane takes one per block, so this
+// many blocks fit before the lanes have to be flushed into the u64 totals
+#[cfg(feature = "simd")]
+const SIMD_FLUSH_INTERVAL: usize = (u32::MAX / (255 * 255)) as usize;
+
+#[cfg(feature = "simd")]
+fn sum_squares_simd(pixels: &[u8]) -> (u64, u64, u64) {
+    // Byte n of a block widens into lane n % 8 of accumulator n / 8, so
+    // every lane always holds the same channel, n % 3
+    let mut lanes = [u32x8::default(); SIMD_BLOCK_LENGTH / 8];
+    let mut totals = [0u64; 3];
+    let flush = |lanes: &mut [u32x8; SIMD_BLOCK_LENGTH / 8], totals: &mut [u64; 3]| {
+        for (index, lane) in lanes.iter_mut().enumerate() {
+            for (offset, value) in lane.to_array().into_iter().enumerate() {
+                totals[(index * 8 + offset) % 3] += u64::from(value);
+            }
+            *lane = u32x8::default();
+        }
+    };
+
+    let blocks = pixels.chunks_exact(SIMD_BLOCK_LENGTH);
+    let remainder = blocks.remainder();
+    for (index, block) in blocks.enumerate() {
+        for (lanes, bytes) in lanes.chunks_exact_mut(2).zip(block.chunks_exact(16)) {
+            let bytes = u8x16::new(bytes.try_into().unwrap());
+            let low = u16x8::from_u8x16_low(bytes);
+            let high = u16x8::from_u8x16_high(bytes);
+            lanes[0] += low.mul_widen(low);
+            lanes[1] += high.mul_widen(high);
+        }
+        if index % SIMD_FLUSH_INTERVAL == SIMD_FLUSH_INTERVAL - 1 {
+            flush(&mut lanes, &mut totals);
+        }
+    }
+    flush(&mut lanes, &mut totals);
+
+    for pixel in remainder.chunks_exact(3) {
+        for (total, &value) in totals.iter_mut().zip(pixel) {
+            *total += u64::from(value).pow(2);
+        }
+    }
+
+    (totals[0], totals[1], totals[2])
+}
+
+// Segment maps assign long horizontal runs of pixels to the same segment,
+// so each run is summed as one contiguous slice
+#[cfg(feature = "simd")]
+pub fn accumulate_pixels_simd(
+    rgb: &[u8],
+    segment_map: &[Option<usize>],
+    sums: &mut [(u64, u64, u64)],
+    counts: &mut [u64],
+) {
+    let mut offset = 0;
+    for run in segment_map.chunk_by(|a, b| a == b) {
+        let pixels = &rgb[offset * 3..(offset + run.len()) * 3];
+        offset += run.len();
+        let Some(segment) = run[0] else {
+            continue;
+        };
+
+        let (r, g, b) = sum_squares_simd(pixels);
+        sums[segment].0 += r;
+        sums[segment].1 += g;
+        sums[segment].2 += b;
+        counts[segment] += run.len() as u64;
+    }
+}
+
+#[instrument(level = "trace", skip(rgb, segment_map))]
+pub fn average_segment_colors(
+    rgb: &[u8],
+    segment_map: &[Option<usize>],
+    num_leds: usize,
+) -> Result<Vec<u32>, FrameSizeError> {
+    if rgb.len() != segment_map.len() * 3 {
+        return Err(FrameSizeError {
+            expected: segment_map.len() * 3,
+            actual: rgb.len(),
+        });
+    }
+
+    let mut led_values: Vec<(u64, u64, u64)> = vec![(0, 0, 0); num_leds];
+    let mut counts: Vec<u64> = vec![0; num_leds];
+    #[cfg(feature = "simd")]
+    accumulate_pixels_simd(rgb, segment_map, &mut led_values, &mut counts);
+    #[cfg(not(feature = "simd"))]
+    accumulate_pixels(rgb, segment_map, &mut led_values, &mut counts);
+
+    Ok(led_values
+        .into_iter()
+        .zip(counts)
+        .map(|(sum, count)| mean_square_color(sum, count))
+        .collect())
+}
+
+// 3x5 glyphs for 0-9, one row per byte with the leftmost pixel in bit 2
+const LABEL_DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+// Stepping hues by the golden angle keeps neighboring segments far apart on
+// the color wheel however many LEDs there are
+fn palette_color(segment: usize) -> u32 {
+    hsv_to_rgb(segment as f32 * 137.508, 0.75, 1.0)
+}
+
+// Draws the label in white on a black box so it reads over any segment color
+fn stamp_label(
+    pixels: &mut [u32],
+    width: usize,
+    (center_x, center_y): (usize, usize),
+    label: usize,
+    scale: usize,
+) {
+    let digits: Vec<usize> = label
+        .to_string()
+        .bytes()
+        .map(|digit| usize::from(digit - b'0'))
+        .collect();
+    let height = pixels.len() / width;
+    let box_width = (digits.len() * 4 + 1) * scale;
+    let box_height = 7 * scale;
+    let left = center_x.saturating_sub(box_width / 2);
+    let top = center_y.saturating_sub(box_height / 2);
+
+    for y in top..(top + box_height).min(height) {
+        for x in left..(left + box_width).min(width) {
+            let (col, row) = ((x - left) / scale, (y - top) / scale);
+            let lit = (1..6).contains(&row)
+                && col % 4 != 0
+                && col / 4 < digits.len()
+                && LABEL_DIGITS[digits[col / 4]][row - 1] & (0b100 >> (col % 4 - 1)) != 0;
+            pixels[y * width + x] = if lit { 0xffffff } else { 0x000000 };
+        }
+    }
+}
+
+const SEGMENT_MAP_MAGIC: &[u8; 4] = b"AGSM";
+const SEGMENT_MAP_VERSION: u8 = 1;
+const UNMAPPED_RUN: u32 = u32::MAX;
+
+fn invalid_segment_map(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(data: &mut &[u8]) -> io::Result<u32> {
+    let (bytes, rest) = data
+        .split_first_chunk()
+        .ok_or_else(|| invalid_segment_map("segment map file is truncated"))?;
+    *data = rest;
+    Ok(u32::from_le_bytes(*bytes))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentMap {
+    pixels: Vec<Option<usize>>,
+    width: usize,
+    num_leds: usize,
+}
+
+impl SegmentMap {
+    pub fn new(pixels: Vec<Option<usize>>, width: u32, num_leds: usize) -> Self {
+        let width = width as usize;
+        assert!(
+            width > 0 && pixels.len().is_multiple_of(width),
+            "Segment map must be a whole number of rows"
+        );
+        assert!(
+            pixels.iter().flatten().all(|&segment| segment < num_leds),
+            "Segment map references more than {} LEDs",
+            num_leds
+        );
+
+        Self {
+            pixels,
+            width,
+            num_leds,
+        }
+    }
+
+    pub fn segment_of(&self, x: u32, y: u32) -> Option<usize> {
+        let (x, y) = (x as usize, y as usize);
+        assert!(x < self.width && y < self.height(), "pixel out of bounds");
+
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn num_leds(&self) -> usize {
+        self.num_leds
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.pixels.len() / self.width
+    }
+
+    pub fn pixels(&self) -> &[Option<usize>] {
+        &self.pixels
+    }
+
+    // Splits the mapped pixels into parallel pixel and segment index lists in
+    // row-major order, dropping the unmapped ones
+    pub fn to_flat_lookup(&self) -> (Vec<u32>, Vec<u32>) {
+        self.pixels
+            .iter()
+            .enumerate()
+            .filter_map(|(pixel, segment)| segment.map(|segment| (pixel as u32, segment as u32)))
+            .unzip()
+    }
+
+    // Stores the map as runs of identical segments along with the parameters
+    // it was built from, which load checks before trusting the rest
+    pub fn to_bytes(&self, params: &str) -> Vec<u8> {
+        let runs: Vec<&[Option<usize>]> = self.pixels.chunk_by(|a, b| a == b).collect();
+
+        let mut data = Vec::with_capacity(params.len() + runs.len() * 8 + 25);
+        data.extend(SEGMENT_MAP_MAGIC);
+        data.push(SEGMENT_MAP_VERSION);
+        data.extend((params.len() as u32).to_le_bytes());
+        data.extend(params.as_bytes());
+        data.extend((self.width as u32).to_le_bytes());
+        data.extend((self.height() as u32).to_le_bytes());
+        data.extend((self.num_leds as u32).to_le_bytes());
+        data.extend((runs.len() as u32).to_le_bytes());
+        for run in runs {
+            let segment = run[0].map_or(UNMAPPED_RUN, |segment| segment as u32);
+            data.extend((run.len() as u32).to_le_bytes());
+            data.extend(segment.to_le_bytes());
+        }
+        data
+    }
+
+    pub fn from_bytes(mut data: &[u8], params: &str) -> io::Result<Self> {
+        let header = data
+            .split_first_chunk::<5>()
+            .filter(|(header, _)| header[..4] == SEGMENT_MAP_MAGIC[..]);
+        let Some((header, rest)) = header else {
+            return Err(invalid_segment_map("not a segment map file"));
+        };
+        if header[4] != SEGMENT_MAP_VERSION {
+            return Err(invalid_segment_map("unsupported segment map version"));
+        }
+        data = rest;
+
+        let params_len = read_u32(&mut data)? as usize;
+        if data.get(..params_len) != Some(params.as_bytes()) {
+            return Err(invalid_segment_map(
+                "segment map was built with other parameters",
+            ));
+        }
+        data = &data[params_len..];
+
+        let width = read_u32(&mut data)?;
+        let num_pixels = (width as usize)
+            .checked_mul(read_u32(&mut data)? as usize)
+            .ok_or_else(|| invalid_segment_map("segment map file is corrupt"))?;
+        let num_leds = read_u32(&mut data)? as usize;
+        let num_runs = read_u32(&mut data)?;
+        let mut pixels = Vec::new();
+        for _ in 0..num_runs {
+            let len = read_u32(&mut data)? as usize;
+            let segment = match read_u32(&mut data)? {
+                UNMAPPED_RUN => None,
+                segment if (segment as usize) < num_leds => Some(segment as usize),
+                _ => return Err(invalid_segment_map("segment map references unknown LEDs")),
+            };
+            if len > num_pixels - pixels.len() {
+                return Err(invalid_segment_map("segment map file is corrupt"));
+            }
+            pixels.resize(pixels.len() + len, segment);
+        }
+        if !data.is_empty() || width == 0 || pixels.len() != num_pixels {
+            return Err(invalid_segment_map("segment map file is corrupt"));
+        }
+
+        Ok(Self::new(pixels, width, num_leds))
+    }
+
+    pub fn save(&self, path: &Path, params: &str) -> io::Result<()> {
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, self.to_bytes(params))?;
+        fs::rename(temp_path, path)
+    }
+
+    pub fn load(path: &Path, params: &str) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(path)?, params)
+    }
+
+    // Colors each segment from a repeating palette with unmapped pixels left
+    // black, optionally labeling segments with their LED index
+    pub fn render(&self, labels: bool) -> Vec<u32> {
+        let mut pixels: Vec<u32> = self
+            .pixels
+            .iter()
+            .map(|segment| segment.map_or(0x000000, palette_color))
+            .collect();
+
+        if labels {
+            let mut sums = vec![(0, 0, 0); self.num_leds];
+            for (pixel, segment) in self.pixels.iter().enumerate() {
+                if let Some(segment) = *segment {
+                    sums[segment].0 += pixel % self.width;
+                    sums[segment].1 += pixel / self.width;
+                    sums[segment].2 += 1;
+                }
+            }
+
+            let scale = (self.width.min(self.height()) / 120).max(1);
+            for (segment, (x, y, count)) in sums.into_iter().enumerate() {
+                if let (Some(x), Some(y)) = (x.checked_div(count), y.checked_div(count)) {
+                    stamp_label(&mut pixels, self.width, (x, y), segment, scale);
+                }
+            }
+        }
+
+        pixels
+    }
+
+    pub fn render_png(&self, path: &Path, labels: bool) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = Encoder::new(file, self.width as u32, self.height() as u32);
+        encoder.set_color(ColorType::Rgb);
+        encoder.set_depth(BitDepth::Eight);
+
+        let data: Vec<u8> = self
+            .render(labels)
+            .into_iter()
+            .flat_map(|color| [(color >> 16) as u8, (color >> 8) as u8, color as u8])
+            .collect();
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+
+    pub fn subsample(&self, factor: usize) -> SubsampledSegmentMap<'_> {
+        assert!(factor > 0, "Subsample factor must be positive");
+
+        SubsampledSegmentMap {
+            segment_map: self,
+            factor,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SegmentMapError {
+    NoLeds,
+    EmptyResolution(u32, u32),
+    BoundaryCount(usize, usize),
+    InvalidBoundaries,
+    CropOutOfBounds((u32, u32, u32, u32)),
+    ZoneCount(usize, usize),
+    InvalidZones,
+    Layout(LayoutError),
+    LedCount(&'static str, usize, usize),
+}
+
+impl fmt::Display for SegmentMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SegmentMapError::NoLeds => write!(f, "at least one LED is required"),
+            SegmentMapError::EmptyResolution(width, height) => {
+                write!(f, "resolution {}x{} has no pixels", width, height)
+            }
+            SegmentMapError::BoundaryCount(expected, actual) => write!(
+                f,
+                "expected a segment boundary for each of the {} LEDs, got {}",
+                expected, actual
+            ),
+            SegmentMapError::InvalidBoundaries => {
+                write!(f, "segment boundaries must be sorted angles in [0, 360)")
+            }
+            SegmentMapError::CropOutOfBounds((x, y, width, height)) => write!(
+                f,
+                "crop {}x{}+{}+{} is empty or outside the frame",
+                width, height, x, y
+            ),
+            SegmentMapError::ZoneCount(expected, actual) => write!(
+                f,
+                "ring zones cover {} LEDs, expected the {} LEDs being driven",
+                actual, expected
+            ),
+            SegmentMapError::InvalidZones => write!(
+                f,
+                "ring zones need at least one LED and non-overlapping bands with 0 <= INNER < OUTER"
+            ),
+            SegmentMapError::Layout(err) => write!(f, "{}", err),
+            SegmentMapError::LedCount(layout, expected, actual) => write!(
+                f,
+                "{} layout covers {} LEDs, expected the {} LEDs being driven",
+                layout, actual, expected
+            ),
+        }
+    }
+}
+
+impl Error for SegmentMapError {}
+
+#[derive(Clone, Debug)]
+pub struct SegmentMapBuilder {
+    num_leds: usize,
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+    edge_fraction: f64,
+    outer_fraction: Option<f64>,
+    boundaries: Option<Vec<f64>>,
+    zones: Vec<RingZone>,
+    crop: Option<(u32, u32, u32, u32)>,
+}
+
+impl Default for SegmentMapBuilder {
+    fn default() -> Self {
+        Self {
+            num_leds: 0,
+            width: 0,
+            height: 0,
+            orientation: Orientation::default(),
+            edge_fraction: DEFAULT_EDGE_FRACTION,
+            outer_fraction: None,
+            boundaries: None,
+            zones: Vec::new(),
+            crop: None,
+        }
+    }
+}
+
+impl SegmentMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn num_leds(mut self, num_leds: usize) -> Self {
+        self.num_leds = num_leds;
+        self
+    }
+
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn start_angle(mut self, degrees: f64) -> Self {
+        self.orientation.rotation_degrees = degrees;
+        self
+    }
+
+    pub fn inner_radius(mut self, edge_fraction: f64) -> Self {
+        self.edge_fraction = edge_fraction;
+        self
+    }
+
+    pub fn outer_radius(mut self, outer_fraction: Option<f64>) -> Self {
+        self.outer_fraction = outer_fraction;
+        self
+    }
+
+    // Segment start angles in radians, replacing the evenly spaced segments
+    pub fn boundaries(mut self, boundaries: Vec<f64>) -> Self {
+        self.boundaries = Some(boundaries);
+        self
+    }
+
+    // Concentric rings replacing the single ring between the inner and outer
+    // radius
+    pub fn zones(mut self, zones: Vec<RingZone>) -> Self {
+        self.zones = zones;
+        self
+    }
+
+    pub fn crop(mut self, region: Option<(u32, u32, u32, u32)>) -> Self {
+        self.crop = region;
+        self
+    }
+
+    fn validate(&self) -> Result<(), SegmentMapError> {
+        if self.num_leds == 0 {
+            return Err(SegmentMapError::NoLeds);
+        }
+        if self.width == 0 || self.height == 0 {
+            return Err(SegmentMapError::EmptyResolution(self.width, self.height));
+        }
+        if let Some(region @ (x, y, width, height)) = self.crop {
+            if width == 0
+                || height == 0
+                || x.saturating_add(width) > self.width
+                || y.saturating_add(height) > self.height
+            {
+                return Err(SegmentMapError::CropOutOfBounds(region));
+            }
+        }
+        if let Some(boundaries) = &self.boundaries {
+            if boundaries.len() != self.num_leds {
+                return Err(SegmentMapError::BoundaryCount(
+                    self.num_leds,
+                    boundaries.len(),
+                ));
+            }
+            if !boundaries.is_sorted() || !boundaries.iter().all(|angle| (0.0..TAU).contains(angle))
+            {
+                return Err(SegmentMapError::InvalidBoundaries);
+            }
+        }
+        if !self.zones.is_empty() {
+            let total = self.zones.iter().map(|zone| zone.num_leds).sum();
+            if total != self.num_leds {
+                return Err(SegmentMapError::ZoneCount(self.num_leds, total));
+            }
+
+            let mut bands: Vec<(f64, f64)> = self
+                .zones
+                .iter()
+                .map(|zone| (zone.inner_fraction, zone.outer_fraction))
+                .collect();
+            bands.sort_by(|a, b| a.0.total_cmp(&b.0));
+            if self.zones.iter().any(|zone| zone.num_leds == 0)
+                || !bands
+                    .iter()
+                    .all(|&(inner, outer)| 0.0 <= inner && inner < outer)
+                || bands.windows(2).any(|pair| pair[0].1 > pair[1].0)
+            {
+                return Err(SegmentMapError::InvalidZones);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn build(&self) -> Result<SegmentMap, SegmentMapError> {
+        if !self.zones.is_empty() {
+            return self.build_with(|width, height| {
+                build_zoned_segment_map(&self.zones, width, height, self.orientation)
+            });
+        }
+
+        self.build_with(|width, height| match &self.boundaries {
+            Some(boundaries) => build_weighted_segment_map(
+                boundaries,
+                width,
+                height,
+                self.orientation,
+                self.edge_fraction,
+                self.outer_fraction,
+            ),
+            None => build_segment_map(
+                self.num_leds,
+                width,
+                height,
+                self.orientation,
+                self.edge_fraction,
+                self.outer_fraction,
+            ),
+        })
+    }
+
+    // Builds a map for layouts the builder has no settings for, passing the
+    // cropped resolution to the layout and placing its output in the frame
+    pub fn build_with(
+        &self,
+        layout: impl FnOnce(u32, u32) -> Vec<Option<usize>>,
+    ) -> Result<SegmentMap, SegmentMapError> {
+        self.validate()?;
+
+        let pixels = match self.crop {
+            Some(region @ (_, _, width, height)) => {
+                uncrop_segment_map(&layout(width, height), region, self.width, self.height)
+            }
+            None => layout(self.width, self.height),
+        };
+        Ok(SegmentMap::new(pixels, self.width, self.num_leds))
+    }
+}
+
+pub struct SubsampledSegmentMap<'a> {
+    segment_map: &'a SegmentMap,
+    factor: usize,
+}
+
+impl SubsampledSegmentMap<'_> {
+    pub fn segment_of(&self, x: u32, y: u32) -> Option<usize> {
+        if !(x as usize).is_multiple_of(self.factor) || !(y as usize).is_multiple_of(self.factor) {
+            return None;
+        }
+
+        self.segment_map.segment_of(x, y)
+    }
+
+    pub fn num_leds(&self) -> usize {
+        self.segment_map.num_leds()
+    }
+
+    // Yields the index and segment of each sampled pixel, stepping over the
+    // skipped rows and columns instead of filtering every pixel
+    pub fn segments(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let width = self.segment_map.width;
+        (0..self.segment_map.height())
+            .step_by(self.factor)
+            .flat_map(move |y| {
+                (0..width).step_by(self.factor).filter_map(move |x| {
+                    let pixel = y * width + x;
+                    self.segment_map.pixels[pixel].map(|segment| (pixel, segment))
+                })
+            })
+    }
+}
+
+// Weighs each pixel by its distance from the center of the frame,
+// normalized to the corners and raised to the given power
+fn radial_weight(width: usize, height: usize, power: f64) -> impl Fn(usize) -> f64 {
+    let half_width = (width / 2) as f64;
+    let half_height = (height / 2) as f64;
+    let max_distance = half_width.hypot(half_height).max(1.0);
+
+    move |pixel| {
+        let dx = half_width - (pixel % width) as f64;
+        let dy = (pixel / width) as f64 - half_height;
+        (dx.hypot(dy) / max_distance).powf(power)
+    }
+}
+
+pub struct SegmentIndex {
+    segments: Vec<Vec<usize>>,
+    // Consecutive pixels of each segment, so they can be summed as
+    // contiguous slices
+    #[cfg(feature = "simd")]
+    runs: Vec<Vec<Range<usize>>>,
+    weights: Option<Vec<Vec<f64>>>,
+    linear_light: Option<Box<[f64; 256]>>,
+    num_pixels: usize,
+}
+
+impl SegmentIndex {
+    pub fn new(segment_map: &SubsampledSegmentMap) -> Self {
+        let mut segments = vec![Vec::new(); segment_map.num_leds()];
+        for (pixel, segment) in segment_map.segments() {
+            segments[segment].push(pixel);
+        }
+
+        Self {
+            #[cfg(feature = "simd")]
+            runs: segments
+                .iter()
+                .map(|pixels| {
+                    pixels
+                        .chunk_by(|&a, &b| b == a + 1)
+                        .map(|run| run[0]..run[run.len() - 1] + 1)
+                        .collect()
+                })
+                .collect(),
+            segments,
+            weights: None,
+            linear_light: None,
+            num_pixels: segment_map.segment_map.pixels().len(),
+        }
+    }
+
+    pub fn with_radial_weights(mut self, segment_map: &SegmentMap, power: f64) -> Self {
+        let radial_weight = radial_weight(segment_map.width(), segment_map.height(), power);
+        self.weights = Some(
+            self.segments
+                .iter()
+                .map(|pixels| pixels.iter().map(|&pixel| radial_weight(pixel)).collect())
+                .collect(),
+        );
+        self
+    }
+
+    pub fn with_linear_light(mut self) -> Self {
+        self.linear_light = Some(Box::new(std::array::from_fn(|channel| {
+            f64::from(srgb_to_linear(channel as u8))
+        })));
+        self
+    }
+
+    fn channel_value(&self, channel: u8) -> f64 {
+        match &self.linear_light {
+            Some(linear_light) => linear_light[usize::from(channel)],
+            None => f64::from(channel),
+        }
+    }
+
+    fn encode_channel(&self, value: f64) -> u32 {
+        match self.linear_light {
+            Some(_) => linear_to_srgb(value as f32).into(),
+            None => value as u32,
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    fn sum_squares(&self, rgb: &[u8], segment: usize) -> (u64, u64, u64) {
+        self.runs[segment]
+            .iter()
+            .map(|run| sum_squares_simd(&rgb[run.start * 3..run.end * 3]))
+            .fold((0, 0, 0), |(r, g, b), (run_r, run_g, run_b)| {
+                (r + run_r, g + run_g, b + run_b)
+            })
+    }
+
+    #[cfg(not(feature = "simd"))]
+    fn sum_squares(&self, rgb: &[u8], segment: usize) -> (u64, u64, u64) {
+        self.segments[segment]
+            .iter()
+            .fold((0, 0, 0), |(r, g, b), &pixel| {
+                let offset = pixel * 3;
+                (
+                    r + u64::from(rgb[offset]).pow(2),
+                    g + u64::from(rgb[offset + 1]).pow(2),
+                    b + u64::from(rgb[offset + 2]).pow(2),
+                )
+            })
+    }
+
+    fn segment_color(&self, rgb: &[u8], segment: usize) -> u32 {
+        let pixels = &self.segments[segment];
+        if self.weights.is_none() && self.linear_light.is_none() {
+            return mean_square_color(self.sum_squares(rgb, segment), pixels.len() as u64);
+        }
+
+        let weights = self.weights.as_ref().map(|weights| &weights[segment]);
+        let weight = |index: usize| weights.map_or(1.0, |weights| weights[index]);
+        let channel = |offset: usize| self.channel_value(rgb[offset]).powi(2);
+
+        let sum = pixels
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0, 0.0), |(r, g, b), (index, &pixel)| {
+                let (weight, offset) = (weight(index), pixel * 3);
+                (
+                    r + weight * channel(offset),
+                    g + weight * channel(offset + 1),
+                    b + weight * channel(offset + 2),
+                )
+            });
+        let total_weight = match weights {
+            Some(weights) => weights.iter().sum(),
+            None => pixels.len() as f64,
+        };
+        self.weighted_mean_square_color(sum, total_weight)
+    }
+
+    fn weighted_mean_square_color(&self, (r, g, b): (f64, f64, f64), total_weight: f64) -> u32 {
+        if total_weight <= 0.0 {
+            return 0;
+        }
+
+        let r = self.encode_channel((r / total_weight).sqrt());
+        let g = self.encode_channel((g / total_weight).sqrt());
+        let b = self.encode_channel((b / total_weight).sqrt());
+        (r << 16) | (g << 8) | b
+    }
+}
+
+// Below this many pixels handing segments out to other threads costs more
+// than it saves
+#[cfg(feature = "rayon")]
+const PARALLEL_MIN_PIXELS: usize = 1280 * 720;
+
+fn check_frame_size(rgb: &[u8], segment_index: &SegmentIndex) -> Result<(), FrameSizeError> {
+    if rgb.len() != segment_index.num_pixels * 3 {
+        return Err(FrameSizeError {
+            expected: segment_index.num_pixels * 3,
+            actual: rgb.len(),
+        });
+    }
+
+    Ok(())
+}
+
+#[instrument(level = "trace", skip_all)]
+pub fn average_indexed_segment_colors(
+    rgb: &[u8],
+    segment_index: &SegmentIndex,
+) -> Result<Vec<u32>, FrameSizeError> {
+    #[cfg(feature = "rayon")]
+    if segment_index.num_pixels >= PARALLEL_MIN_PIXELS {
+        return average_indexed_segment_colors_parallel(rgb, segment_index);
+    }
+
+    average_indexed_segment_colors_serial(rgb, segment_index)
+}
+
+pub fn average_indexed_segment_colors_serial(
+    rgb: &[u8],
+    segment_index: &SegmentIndex,
+) -> Result<Vec<u32>, FrameSizeError> {
+    check_frame_size(rgb, segment_index)?;
+
+    Ok((0..segment_index.segments.len())
+        .map(|segment| segment_index.segment_color(rgb, segment))
+        .collect())
+}
+
+// Each segment owns a disjoint set of pixels, so segments are averaged on
+// separate threads without sharing any accumulators
+#[cfg(feature = "rayon")]
+pub fn average_indexed_segment_colors_parallel(
+    rgb: &[u8],
+    segment_index: &SegmentIndex,
+) -> Result<Vec<u32>, FrameSizeError> {
+    check_frame_size(rgb, segment_index)?;
+
+    Ok((0..segment_index.segments.len())
+        .into_par_iter()
+        .map(|segment| segment_index.segment_color(rgb, segment))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "simd")]
+    use crate::segment_map::accumulate_pixels_simd;
+    use crate::segment_map::{
+        accumulate_flat_lookup, accumulate_pixels, average_indexed_segment_colors,
+        average_segment_colors, build_border_segment_map, build_bottom_segment_map,
+        build_matrix_segment_map, build_segment_map, build_segment_map_from_layout,
+        build_weighted_segment_map, mirror_segment_map, uncrop_segment_map, zone_ranges, Corner,
+        Crop, CropRect, EdgeCounts, FrameSizeError, LayoutError, LedLayout, MatrixSize,
+        Orientation, RingZone, Rotation, SegmentIndex, SegmentMap, SegmentMapBuilder,
+        SegmentMapError, DEFAULT_EDGE_FRACTION,
+    };
+    #[cfg(feature = "rayon")]
+    use crate::segment_map::{
+        average_indexed_segment_colors_parallel, average_indexed_segment_colors_serial,
+    };
+    use png::Decoder;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+    use std::{env, fs, process};
+
+    const NUM_LEDS: usize = 12;
+    const WIDTH: u32 = 9;
+    const HEIGHT: u32 = 7;
+
+    fn segment_at(segment_map: &[Option<usize>], x: u32, y: u32) -> Option<usize> {
+        segment_map[(y * WIDTH + x) as usize]
+    }
+
+    #[test]
+    fn it_keeps_the_default_mapping_without_transforms() {
+        let segment_map = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        assert_eq!(segment_map.len(), (WIDTH * HEIGHT) as usize);
+        assert_eq!(segment_at(&segment_map, 4, 3), None);
+        assert_eq!(segment_at(&segment_map, 8, 2), Some(0));
+        assert_eq!(segment_at(&segment_map, 8, 3), Some(NUM_LEDS - 1));
+        assert_eq!(segment_at(&segment_map, 0, 3), Some(6));
+    }
+
+    #[test]
+    fn it_widens_the_unmapped_center_with_the_edge_fraction() {
+        let narrow = build_segment_map(NUM_LEDS, WIDTH, HEIGHT, Orientation::default(), 0.0, None);
+        let wide = build_segment_map(NUM_LEDS, WIDTH, HEIGHT, Orientation::default(), 1.0, None);
+
+        assert!(narrow.iter().all(Option::is_some));
+        assert_eq!(segment_at(&wide, 4, 3), None);
+        assert_eq!(segment_at(&wide, 6, 3), None);
+        assert_eq!(segment_at(&wide, 8, 2), Some(0));
+    }
+
+    #[test]
+    fn it_limits_the_mapping_to_the_outer_radius() {
+        let (width, height) = (41, 41);
+        let segment_at =
+            |segment_map: &[Option<usize>], x: u32| segment_map[(20 * width + x) as usize];
+
+        let unlimited = build_segment_map(
+            NUM_LEDS,
+            width,
+            height,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        assert!(unlimited[0].is_some());
+        assert!(segment_at(&unlimited, 0).is_some());
+
+        let limited = build_segment_map(
+            NUM_LEDS,
+            width,
+            height,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            Some(0.75),
+        );
+        assert_eq!(limited[0], None);
+        assert_eq!(segment_at(&limited, 29), None);
+        assert_eq!(segment_at(&limited, 30), Some(NUM_LEDS - 1));
+        assert_eq!(segment_at(&limited, 35), Some(NUM_LEDS - 1));
+        assert_eq!(segment_at(&limited, 36), None);
+        assert_eq!(segment_at(&limited, 5), Some(6));
+        assert_eq!(segment_at(&limited, 4), None);
+    }
+
+    #[test]
+    fn it_flips_the_mapping_horizontally() {
+        let original = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let flipped = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                flip_horizontal: true,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                assert_eq!(
+                    segment_at(&flipped, x, y),
+                    segment_at(&original, WIDTH - 1 - x, y)
+                );
+            }
+        }
+
+        assert_eq!(segment_at(&original, 8, 2), Some(0));
+        assert_eq!(segment_at(&flipped, 0, 2), Some(0));
+    }
+
+    #[test]
+    fn it_flips_even_sized_mappings_like_mirror_segment_map() {
+        let (width, height) = (10, 8);
+        let original = build_segment_map(
+            NUM_LEDS,
+            width,
+            height,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        for (flip_horizontal, flip_vertical) in [(true, false), (false, true), (true, true)] {
+            let flipped = build_segment_map(
+                NUM_LEDS,
+                width,
+                height,
+                Orientation {
+                    flip_horizontal,
+                    flip_vertical,
+                    ..Orientation::default()
+                },
+                DEFAULT_EDGE_FRACTION,
+                None,
+            );
+            assert_eq!(
+                flipped,
+                mirror_segment_map(original.clone(), width, flip_horizontal, flip_vertical)
+            );
+        }
+    }
+
+    #[test]
+    fn it_flips_the_mapping_vertically() {
+        let original = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let flipped = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                flip_vertical: true,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                assert_eq!(
+                    segment_at(&flipped, x, y),
+                    segment_at(&original, x, HEIGHT - 1 - y)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn it_rotates_the_mapping_by_quarter_turns() {
+        let original = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let rotated = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                rotation: Rotation::Rotate90,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        assert_eq!(segment_at(&original, 8, 2), Some(0));
+        assert_eq!(segment_at(&rotated, 8, 2), Some(3));
+        assert_eq!(segment_at(&original, 0, 3), Some(6));
+        assert_eq!(segment_at(&rotated, 0, 3), Some(9));
+    }
+
+    #[test]
+    fn it_rotates_the_mapping_by_arbitrary_angles() {
+        let build = |rotation_degrees| {
+            build_segment_map(
+                NUM_LEDS,
+                WIDTH,
+                HEIGHT,
+                Orientation {
+                    rotation_degrees,
+                    ..Orientation::default()
+                },
+                DEFAULT_EDGE_FRACTION,
+                None,
+            )
+        };
+
+        let half_turn = build(180.0);
+        assert_eq!(segment_at(&half_turn, 8, 2), Some(NUM_LEDS / 2));
+        assert_eq!(segment_at(&half_turn, 0, 3), Some(NUM_LEDS - 1));
+        assert_eq!(segment_at(&half_turn, 4, 3), None);
+
+        assert_eq!(segment_at(&build(-180.0), 8, 2), Some(NUM_LEDS / 2));
+        assert_eq!(segment_at(&build(15.0), 8, 2), Some(0));
+        assert_eq!(segment_at(&build(20.0), 8, 2), Some(1));
+        assert_eq!(segment_at(&build(90.0), 8, 2), Some(NUM_LEDS / 4));
+        assert_eq!(segment_at(&build(90.0), 0, 3), Some(9));
+        assert_eq!(build(360.0), build(0.0));
+        assert_eq!(build(-360.0), build(0.0));
+
+        for (rotation_degrees, rotation) in [
+            (90.0, Rotation::Rotate90),
+            (180.0, Rotation::Rotate180),
+            (270.0, Rotation::Rotate270),
+        ] {
+            assert_eq!(
+                build(rotation_degrees),
+                build_segment_map(
+                    NUM_LEDS,
+                    WIDTH,
+                    HEIGHT,
+                    Orientation {
+                        rotation,
+                        ..Orientation::default()
+                    },
+                    DEFAULT_EDGE_FRACTION,
+                    None,
+                )
+            );
+        }
+    }
+
+    fn segment_pixel_counts(segment_map: &[Option<usize>], num_leds: usize) -> Vec<usize> {
+        segment_map
+            .iter()
+            .flatten()
+            .fold(vec![0; num_leds], |mut counts, &segment| {
+                counts[segment] += 1;
+                counts
+            })
+    }
+
+    fn count_spread(counts: &[usize]) -> f64 {
+        let min = *counts.iter().min().unwrap() as f64;
+        let max = *counts.iter().max().unwrap() as f64;
+        max / min
+    }
+
+    #[test]
+    fn it_balances_segment_sizes_with_an_elliptical_mapping() {
+        let circle = build_segment_map(
+            36,
+            1280,
+            720,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let ellipse = build_segment_map(
+            36,
+            1280,
+            720,
+            Orientation {
+                elliptical: true,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        assert!(count_spread(&segment_pixel_counts(&circle, 36)) > 1.2);
+        assert!(count_spread(&segment_pixel_counts(&ellipse, 36)) <= 1.2);
+    }
+
+    #[test]
+    fn it_leaves_the_center_and_corners_unmapped_with_an_elliptical_mapping() {
+        let segment_map = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                elliptical: true,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        assert_eq!(segment_at(&segment_map, 4, 3), None);
+        assert_eq!(segment_at(&segment_map, 5, 3), None);
+        assert_eq!(segment_at(&segment_map, 0, 0), None);
+        assert_eq!(segment_at(&segment_map, 8, 6), None);
+        assert_eq!(segment_at(&segment_map, 8, 2), None);
+        assert_eq!(segment_at(&segment_map, 7, 2), Some(0));
+        assert_eq!(segment_at(&segment_map, 0, 3), Some(6));
+        assert_eq!(segment_at(&segment_map, 3, 1), Some(3));
+    }
+
+    #[test]
+    fn it_reverses_the_mapping_direction() {
+        let clockwise = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let counter_clockwise = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                clockwise: false,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        for (segment, reversed) in clockwise.iter().zip(&counter_clockwise) {
+            assert_eq!(*reversed, segment.map(|segment| NUM_LEDS - 1 - segment));
+        }
+        assert_eq!(segment_at(&counter_clockwise, 8, 2), Some(NUM_LEDS - 1));
+    }
+
+    #[test]
+    fn it_composes_flips_and_rotations() {
+        let rotated = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                rotation: Rotation::Rotate180,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let flipped = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                flip_horizontal: true,
+                flip_vertical: true,
+                rotation: Rotation::Rotate0,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        assert_eq!(rotated, flipped);
+
+        let identity = build_segment_map(
+            NUM_LEDS,
+            WIDTH,
+            HEIGHT,
+            Orientation {
+                flip_horizontal: true,
+                flip_vertical: true,
+                rotation: Rotation::Rotate180,
+                ..Orientation::default()
+            },
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        assert_eq!(
+            identity,
+            build_segment_map(
+                NUM_LEDS,
+                WIDTH,
+                HEIGHT,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn it_maps_segments_of_different_widths() {
+        let segment_map = build_weighted_segment_map(
+            &[0.0, FRAC_PI_2, PI],
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        assert_eq!(segment_map.len(), (WIDTH * HEIGHT) as usize);
+
+        assert_eq!(segment_at(&segment_map, 8, 2), Some(0));
+        assert_eq!(segment_at(&segment_map, 8, 0), Some(0));
+        assert_eq!(segment_at(&segment_map, 4, 0), Some(1));
+        assert_eq!(segment_at(&segment_map, 0, 2), Some(1));
+        assert_eq!(segment_at(&segment_map, 0, 3), Some(2));
+        assert_eq!(segment_at(&segment_map, 4, 6), Some(2));
+        assert_eq!(segment_at(&segment_map, 8, 5), Some(2));
+        assert_eq!(segment_at(&segment_map, 4, 3), None);
+    }
+
+    #[test]
+    fn it_matches_the_uniform_mapping_with_evenly_spaced_boundaries() {
+        assert_eq!(
+            build_weighted_segment_map(
+                &[0.0, FRAC_PI_2, PI, 3.0 * FRAC_PI_2],
+                WIDTH,
+                HEIGHT,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            build_segment_map(
+                4,
+                WIDTH,
+                HEIGHT,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Segment boundaries must be sorted angles in [0, TAU)")]
+    fn it_rejects_unsorted_segment_boundaries() {
+        build_weighted_segment_map(
+            &[PI, 0.0],
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_parses_led_layouts() {
+        let layout = LedLayout::from_toml(
+            "[[leds]]\nstart = 350.0\nend = 10.0\n\n[[leds]]\ncenter = 90.0\nwidth = 30.0\n",
+        )
+        .unwrap();
+
+        assert_eq!(layout.num_leds(), 2);
+        assert_eq!(layout.led_at(0.0), Some(0));
+        assert_eq!(layout.led_at(355f64.to_radians()), Some(0));
+        assert_eq!(layout.led_at(FRAC_PI_2), Some(1));
+        assert_eq!(layout.led_at(PI), None);
+        assert!(matches!(
+            LedLayout::from_toml("[[leds]]\nstart = 0.0\n"),
+            Err(LayoutError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn it_leaves_gaps_between_led_ranges_unmapped() {
+        let layout =
+            LedLayout::new(vec![(FRAC_PI_4, FRAC_PI_2), (5.0 * FRAC_PI_4, FRAC_PI_2)]).unwrap();
+        let quadrants = build_weighted_segment_map(
+            &[FRAC_PI_4, 3.0 * FRAC_PI_4, 5.0 * FRAC_PI_4, 7.0 * FRAC_PI_4],
+            WIDTH,
+            HEIGHT,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+
+        assert_eq!(
+            build_segment_map_from_layout(
+                &layout,
+                WIDTH,
+                HEIGHT,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            quadrants
+                .iter()
+                .map(|segment| match segment {
+                    Some(0) => Some(0),
+                    Some(2) => Some(1),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn it_rejects_overlapping_led_ranges() {
+        assert_eq!(
+            LedLayout::new(vec![(0.0, FRAC_PI_2), (PI, PI), (FRAC_PI_2, FRAC_PI_2)]),
+            Ok(LedLayout {
+                ranges: vec![(0.0, FRAC_PI_2), (PI, PI), (FRAC_PI_2, FRAC_PI_2)]
+            })
+        );
+        assert_eq!(
+            LedLayout::new(vec![(0.0, FRAC_PI_2), (PI, PI), (1.0, 0.1)]),
+            Err(LayoutError::Overlap(0, 2))
+        );
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            LedLayout::from_toml(
+                "[[leds]]\nstart = 300.0\nend = 30.0\n\n[[leds]]\nstart = 20.0\nend = 40.0\n"
+            ),
+            Err(LayoutError::Overlap(0, 1))
+        );
+        assert_eq!(
+            LedLayout::new(vec![(0.0, 0.0)]),
+            Err(LayoutError::InvalidRange(0))
+        );
+    }
+
+    const BORDER_WIDTH: u32 = 12;
+    const BORDER_HEIGHT: u32 = 6;
+    const BORDER_COUNTS: EdgeCounts = EdgeCounts {
+        top: 3,
+        right: 2,
+        bottom: 3,
+        left: 2,
+    };
+
+    fn border_segment_at(segment_map: &[Option<usize>], x: u32, y: u32) -> Option<usize> {
+        segment_map[(y * BORDER_WIDTH + x) as usize]
+    }
+
+    #[test]
+    fn it_parses_edge_counts() {
+        assert_eq!("3,2,3,2".parse(), Ok(BORDER_COUNTS));
+        assert_eq!(
+            "10, 6, 0, 6".parse(),
+            Ok(EdgeCounts {
+                top: 10,
+                right: 6,
+                bottom: 0,
+                left: 6,
+            })
+        );
+        assert!("3,2,3".parse::<EdgeCounts>().is_err());
+        assert!("3,2,3,-2".parse::<EdgeCounts>().is_err());
+    }
+
+    #[test]
+    fn it_maps_the_border_clockwise_from_the_top_left() {
+        let segment_map = build_border_segment_map(
+            BORDER_COUNTS,
+            Corner::TopLeft,
+            BORDER_WIDTH,
+            BORDER_HEIGHT,
+            1.0 / 3.0,
+        );
+        assert_eq!(segment_map.len(), (BORDER_WIDTH * BORDER_HEIGHT) as usize);
+
+        assert_eq!(border_segment_at(&segment_map, 6, 0), Some(1));
+        assert_eq!(border_segment_at(&segment_map, 11, 2), Some(3));
+        assert_eq!(border_segment_at(&segment_map, 11, 3), Some(4));
+        assert_eq!(border_segment_at(&segment_map, 6, 5), Some(6));
+        assert_eq!(border_segment_at(&segment_map, 0, 3), Some(8));
+        assert_eq!(border_segment_at(&segment_map, 0, 2), Some(9));
+
+        assert_eq!(border_segment_at(&segment_map, 0, 0), Some(9));
+        assert_eq!(border_segment_at(&segment_map, 11, 0), Some(3));
+        assert_eq!(border_segment_at(&segment_map, 11, 5), Some(4));
+        assert_eq!(border_segment_at(&segment_map, 0, 5), Some(8));
+
+        for (x, y) in [(2, 2), (6, 3), (9, 3)] {
+            assert_eq!(border_segment_at(&segment_map, x, y), None);
+        }
+    }
+
+    #[test]
+    fn it_starts_the_border_at_the_configured_corner() {
+        let segment_map = build_border_segment_map(
+            BORDER_COUNTS,
+            Corner::BottomRight,
+            BORDER_WIDTH,
+            BORDER_HEIGHT,
+            1.0 / 3.0,
+        );
+
+        assert_eq!(border_segment_at(&segment_map, 6, 5), Some(1));
+        assert_eq!(border_segment_at(&segment_map, 0, 0), Some(4));
+        assert_eq!(border_segment_at(&segment_map, 6, 0), Some(6));
+        assert_eq!(border_segment_at(&segment_map, 11, 5), Some(9));
+    }
+
+    #[test]
+    fn it_maps_the_bottom_band_from_left_to_right() {
+        let segment_map = build_bottom_segment_map(4, 16, 8, 0.25);
+        let segment_at = |x: usize, y: usize| segment_map[y * 16 + x];
+
+        assert_eq!(segment_at(0, 7), Some(0));
+        assert_eq!(segment_at(0, 6), Some(0));
+        assert_eq!(segment_at(15, 7), Some(3));
+        assert_eq!(segment_at(15, 6), Some(3));
+        assert_eq!(segment_at(3, 7), Some(0));
+        assert_eq!(segment_at(4, 7), Some(1));
+        assert_eq!(segment_at(8, 6), Some(2));
+
+        for (x, y) in [(0, 0), (8, 3), (15, 5)] {
+            assert_eq!(segment_at(x, y), None);
+        }
+        assert_eq!(
+            build_bottom_segment_map(3, 4, 2, 0.0),
+            vec![None, None, None, None, Some(0), Some(0), Some(1), Some(2)]
+        );
+    }
+
+    #[test]
+    fn it_mirrors_the_border_mapping() {
+        let segment_map = build_border_segment_map(
+            BORDER_COUNTS,
+            Corner::TopLeft,
+            BORDER_WIDTH,
+            BORDER_HEIGHT,
+            0.34,
+        );
+
+        for (mirror_x, mirror_y) in [(true, false), (false, true), (true, true)] {
+            let mirrored =
+                mirror_segment_map(segment_map.clone(), BORDER_WIDTH, mirror_x, mirror_y);
+            for y in 0..BORDER_HEIGHT {
+                for x in 0..BORDER_WIDTH {
+                    let source_x = if mirror_x { BORDER_WIDTH - 1 - x } else { x };
+                    let source_y = if mirror_y { BORDER_HEIGHT - 1 - y } else { y };
+                    assert_eq!(
+                        border_segment_at(&mirrored, x, y),
+                        border_segment_at(&segment_map, source_x, source_y),
+                        "({}, {})",
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+
+        // Mirroring left to right runs the LEDs counterclockwise from the top right
+        let mirrored = mirror_segment_map(segment_map.clone(), BORDER_WIDTH, true, false);
+        assert_eq!(border_segment_at(&mirrored, 9, 0), Some(0));
+        assert_eq!(border_segment_at(&mirrored, 2, 0), Some(2));
+        assert_eq!(
+            mirror_segment_map(segment_map.clone(), BORDER_WIDTH, false, false),
+            segment_map
+        );
+    }
+
+    #[test]
+    fn it_mirrors_the_circle_mapping_on_odd_sized_frames() {
+        let build = |flip_horizontal| {
+            build_segment_map(
+                NUM_LEDS,
+                WIDTH,
+                HEIGHT,
+                Orientation {
+                    flip_horizontal,
+                    ..Orientation::default()
+                },
+                DEFAULT_EDGE_FRACTION,
+                None,
+            )
+        };
+
+        assert_eq!(
+            build(true),
+            mirror_segment_map(build(false), WIDTH, true, false)
+        );
+    }
+
+    #[test]
+    fn it_parses_matrix_sizes() {
+        assert_eq!("8x8".parse(), Ok(MatrixSize { cols: 8, rows: 8 }));
+        assert_eq!("16 x 4".parse(), Ok(MatrixSize { cols: 16, rows: 4 }));
+        assert!("8".parse::<MatrixSize>().is_err());
+        assert!("0x8".parse::<MatrixSize>().is_err());
+        assert!("8x-1".parse::<MatrixSize>().is_err());
+    }
+
+    #[test]
+    fn it_maps_matrix_cells_row_by_row() {
+        assert_eq!(
+            build_matrix_segment_map(2, 2, false, 4, 2),
+            vec![
+                Some(0),
+                Some(0),
+                Some(1),
+                Some(1),
+                Some(2),
+                Some(2),
+                Some(3),
+                Some(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reverses_odd_rows_of_serpentine_matrices() {
+        let segment_map = build_matrix_segment_map(3, 3, true, 6, 6);
+        let segment_at = |x: usize, y: usize| segment_map[y * 6 + x];
+
+        assert_eq!(segment_at(0, 0), Some(0));
+        assert_eq!(segment_at(5, 0), Some(2));
+        assert_eq!(segment_at(0, 2), Some(5));
+        assert_eq!(segment_at(5, 2), Some(3));
+        assert_eq!(segment_at(2, 3), Some(4));
+        assert_eq!(segment_at(0, 5), Some(6));
+        assert_eq!(segment_at(5, 5), Some(8));
+    }
+
+    #[test]
+    fn it_covers_the_frame_edges_when_cells_do_not_divide_evenly() {
+        let segment_map = build_matrix_segment_map(3, 2, false, 8, 5);
+        let segment_at = |x: usize, y: usize| segment_map[y * 8 + x];
+
+        assert!(segment_map.iter().all(Option::is_some));
+        assert_eq!(segment_at(2, 0), Some(0));
+        assert_eq!(segment_at(3, 0), Some(1));
+        assert_eq!(segment_at(5, 0), Some(1));
+        assert_eq!(segment_at(6, 0), Some(2));
+        assert_eq!(segment_at(7, 0), Some(2));
+        assert_eq!(segment_at(7, 2), Some(2));
+        assert_eq!(segment_at(7, 3), Some(5));
+        assert_eq!(segment_at(0, 4), Some(3));
+    }
+
+    #[test]
+    fn it_parses_crops() {
+        assert_eq!(
+            "0.1".parse(),
+            Ok(Crop {
+                top: 0.1,
+                right: 0.1,
+                bottom: 0.1,
+                left: 0.1,
+            })
+        );
+        assert_eq!(
+            "0, 0.2, 0.1, 0.3".parse(),
+            Ok(Crop {
+                top: 0.0,
+                right: 0.2,
+                bottom: 0.1,
+                left: 0.3,
+            })
+        );
+        assert!("0.1,0.1".parse::<Crop>().is_err());
+        assert!("0.5,0,0.5,0".parse::<Crop>().is_err());
+        assert!("-0.1".parse::<Crop>().is_err());
+    }
+
+    #[test]
+    fn it_excludes_the_cropped_border_from_every_segment() {
+        let (width, height) = (40, 30);
+        let crop: Crop = "0.1".parse().unwrap();
+        let region = crop.region(width, height);
+        assert_eq!(region, (4, 3, 32, 24));
+
+        let cropped = build_segment_map(
+            NUM_LEDS,
+            region.2,
+            region.3,
+            Orientation::default(),
+            0.0,
+            None,
+        );
+        let segment_map = uncrop_segment_map(&cropped, region, width, height);
+        assert_eq!(segment_map.len(), (width * height) as usize);
+
+        for y in 0..height {
+            for x in 0..width {
+                let segment = segment_map[(y * width + x) as usize];
+                let inside = (4..36).contains(&x) && (3..27).contains(&y);
+                if inside {
+                    assert_eq!(segment, cropped[((y - 3) * 32 + x - 4) as usize]);
+                } else {
+                    assert_eq!(segment, None, "({}, {})", x, y);
+                }
+            }
+        }
+        assert!((0..NUM_LEDS).all(|led| segment_map.contains(&Some(led))));
+    }
+
+    #[test]
+    fn it_parses_and_clamps_crop_rectangles() {
+        let crop_rect: CropRect = "120, 0, 1600, 1080".parse().unwrap();
+        assert_eq!(
+            crop_rect,
+            CropRect {
+                x: 120,
+                y: 0,
+                width: 1600,
+                height: 1080,
+            }
+        );
+        assert_eq!(crop_rect.region(1920, 1080), (120, 0, 1600, 1080));
+        assert_eq!(crop_rect.region(640, 480), (120, 0, 520, 480));
+        assert!("0,0,0,10".parse::<CropRect>().is_err());
+        assert!("0,0,10".parse::<CropRect>().is_err());
+        assert!("0.1,0,10,10".parse::<CropRect>().is_err());
+    }
+
+    #[test]
+    fn it_moves_the_annulus_with_the_crop_rectangle() {
+        let (width, height) = (40, 30);
+        let region = CropRect {
+            x: 20,
+            y: 10,
+            width: 20,
+            height: 20,
+        }
+        .region(width, height);
+        let cropped = build_segment_map(
+            NUM_LEDS,
+            region.2,
+            region.3,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let segment_map = uncrop_segment_map(&cropped, region, width, height);
+        let segment_at = |x: u32, y: u32| segment_map[(y * width + x) as usize];
+
+        for (x, y) in [(0, 0), (19, 15), (10, 20), (39, 9)] {
+            assert_eq!(segment_at(x, y), None, "({}, {})", x, y);
+        }
+        assert_eq!(segment_at(30, 20), None);
+        assert_eq!(segment_at(20, 20), Some(6));
+        assert!(segment_at(39, 29).is_some());
+    }
+
+    #[test]
+    fn it_averages_pixels_into_segment_colors() {
+        let segment_map = vec![Some(0), Some(0), None, Some(2)];
+        let rgb = [
+            0xff, 0x00, 0x00, // Segment 0
+            0x00, 0x00, 0x00, // Segment 0
+            0xff, 0xff, 0xff, // Unmapped
+            0x4b, 0x80, 0x40, // Segment 2
+        ];
+
+        assert_eq!(
+            average_segment_colors(&rgb, &segment_map, 3),
+            Ok(vec![0xb40000, 0x000000, 0x4b8040])
+        );
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn it_accumulates_pixels_like_the_scalar_loop_with_simd() {
+        let segment_map = build_segment_map(
+            NUM_LEDS,
+            64,
+            48,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let rgb: Vec<u8> = (0..64 * 48 * 3)
+            .map(|index| (index * 7 % 251) as u8)
+            .collect();
+
+        let mut scalar = (vec![(0, 0, 0); NUM_LEDS], vec![0; NUM_LEDS]);
+        accumulate_pixels(&rgb, &segment_map, &mut scalar.0, &mut scalar.1);
+        let mut simd = (vec![(0, 0, 0); NUM_LEDS], vec![0; NUM_LEDS]);
+        accumulate_pixels_simd(&rgb, &segment_map, &mut simd.0, &mut simd.1);
+        assert_eq!(simd, scalar);
+
+        let segment_map = SegmentMap::new(segment_map, 64, NUM_LEDS);
+        for factor in [1, 2] {
+            let segment_index = SegmentIndex::new(&segment_map.subsample(factor));
+            for (segment, pixels) in segment_index.segments.iter().enumerate() {
+                let scalar = pixels.iter().fold((0, 0, 0), |(r, g, b), &pixel| {
+                    let offset = pixel * 3;
+                    (
+                        r + u64::from(rgb[offset]).pow(2),
+                        g + u64::from(rgb[offset + 1]).pow(2),
+                        b + u64::from(rgb[offset + 2]).pow(2),
+                    )
+                });
+                assert_eq!(segment_index.sum_squares(&rgb, segment), scalar);
+            }
+        }
+
+        // Long enough for the u32 lanes to be flushed mid-run
+        let long_run = vec![0xff; 100_000 * 3];
+        let mut sums = vec![(0, 0, 0)];
+        let mut counts = vec![0];
+        accumulate_pixels_simd(&long_run, &[Some(0); 100_000], &mut sums, &mut counts);
+        assert_eq!(sums, vec![(6_502_500_000, 6_502_500_000, 6_502_500_000)]);
+        assert_eq!(counts, vec![100_000]);
+    }
+
+    #[test]
+    fn it_rejects_frames_that_do_not_match_the_segment_map() {
+        let segment_map = vec![Some(0), Some(0), None, Some(2)];
+
+        let err = average_segment_colors(&[0xff; 11], &segment_map, 3).unwrap_err();
+        assert_eq!(
+            err,
+            FrameSizeError {
+                expected: 12,
+                actual: 11
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "expected 12 bytes of RGB data to match the segment map, got 11"
+        );
+        assert!(average_segment_colors(&[0xff; 15], &segment_map, 3).is_err());
+        assert_eq!(
+            average_indexed_segment_colors(
+                &[0xff; 11],
+                &SegmentIndex::new(&SegmentMap::new(segment_map, 4, 3).subsample(1))
+            ),
+            Err(err)
+        );
+    }
+
+    #[test]
+    fn it_averages_indexed_segments_like_the_pixel_table() {
+        let (width, height) = (64, 48);
+        let segment_map = build_segment_map(
+            12,
+            width,
+            height,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            Some(0.9),
+        );
+        let rgb: Vec<u8> = (0..width * height * 3)
+            .map(|index| (index * 7 % 251) as u8)
+            .collect();
+
+        let segment_index =
+            SegmentIndex::new(&SegmentMap::new(segment_map.clone(), width, 12).subsample(1));
+        assert_eq!(
+            average_indexed_segment_colors(&rgb, &segment_index),
+            average_segment_colors(&rgb, &segment_map, 12)
+        );
+        assert_eq!(
+            average_indexed_segment_colors(
+                &[0x4b, 0x80, 0x40],
+                &SegmentIndex::new(&SegmentMap::new(vec![None], 1, 1).subsample(1))
+            ),
+            Ok(vec![0x000000])
+        );
+    }
+
+    #[test]
+    fn it_samples_every_nth_pixel_of_indexed_segments() {
+        let (width, height) = (64, 48);
+        let segment_map = build_segment_map(
+            12,
+            width,
+            height,
+            Orientation::default(),
+            DEFAULT_EDGE_FRACTION,
+            None,
+        );
+        let rgb: Vec<u8> = (0..width * height * 3)
+            .map(|index| (index * 7 % 251) as u8)
+            .collect();
+
+        let (mut sampled_map, mut sampled_rgb) = (Vec::new(), Vec::new());
+        for y in (0..height as usize).step_by(2) {
+            for x in (0..width as usize).step_by(2) {
+                let pixel = y * width as usize + x;
+                sampled_map.push(segment_map[pixel]);
+                sampled_rgb.extend(&rgb[pixel * 3..pixel * 3 + 3]);
+            }
+        }
+
+        let segment_index =
+            SegmentIndex::new(&SegmentMap::new(segment_map, width, 12).subsample(2));
+        assert_eq!(
+            average_indexed_segment_colors(&rgb, &segment_index),
+            average_segment_colors(&sampled_rgb, &sampled_map, 12)
+        );
+        assert!(average_indexed_segment_colors(&sampled_rgb, &segment_index).is_err());
+    }
+
+    #[test]
+    fn it_weighs_pixels_toward_the_edge_of_the_frame() {
+        let (width, height) = (32, 32);
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                1,
+                width,
+                height,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            width,
+            1,
+        );
+        let rgb: Vec<u8> = (0..(width * height) as usize)
+            .flat_map(|pixel| {
+                let dx = 16.0 - (pixel % 32) as f64;
+                let dy = (pixel / 32) as f64 - 16.0;
+                let value = if dx.hypot(dy) > 12.0 { 0xff } else { 0x20 };
+                [value; 3]
+            })
+            .collect();
+
+        let unweighted = SegmentIndex::new(&segment_map.subsample(1));
+        let weighted =
+            SegmentIndex::new(&segment_map.subsample(1)).with_radial_weights(&segment_map, 2.0);
+        let unweighted = average_indexed_segment_colors(&rgb, &unweighted).unwrap()[0];
+        let weighted = average_indexed_segment_colors(&rgb, &weighted).unwrap()[0];
+        assert!(
+            weighted & 0xff > unweighted & 0xff,
+            "{:06x} is not brighter than {:06x}",
+            weighted,
+            unweighted
+        );
+
+        let uniform =
+            SegmentIndex::new(&segment_map.subsample(1)).with_radial_weights(&segment_map, 0.0);
+        assert_eq!(
+            average_indexed_segment_colors(&rgb, &uniform).unwrap()[0],
+            unweighted
+        );
+    }
+
+    #[test]
+    fn it_averages_segments_in_linear_light() {
+        let segment_map = SegmentMap::new(vec![Some(0), Some(0), Some(1), Some(1)], 4, 2);
+        let rgb = [
+            0xff, 0x00, 0x80, // Segment 0
+            0x00, 0x00, 0x80, // Segment 0
+            0x4b, 0x80, 0x40, // Segment 1
+            0x4b, 0x80, 0x40, // Segment 1
+        ];
+
+        let segment_index = SegmentIndex::new(&segment_map.subsample(1)).with_linear_light();
+        assert_eq!(
+            average_indexed_segment_colors(&rgb, &segment_index),
+            Ok(vec![0xdb0080, 0x4b8040])
+        );
+        assert_eq!(
+            average_indexed_segment_colors(&rgb, &SegmentIndex::new(&segment_map.subsample(1))),
+            Ok(vec![0xb40080, 0x4b8040])
+        );
+    }
+
+    #[test]
+    fn it_looks_up_segments_by_pixel() {
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                8,
+                64,
+                48,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            64,
+            8,
+        );
+
+        assert_eq!(segment_map.num_leds(), 8);
+        assert_eq!((segment_map.width(), segment_map.height()), (64, 48));
+        assert_eq!(segment_map.segment_of(0, 0), Some(3));
+        assert_eq!(segment_map.segment_of(63, 47), Some(7));
+    }
+
+    #[test]
+    fn it_leaves_the_center_of_the_segment_map_unmapped() {
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                8,
+                64,
+                64,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            64,
+            8,
+        );
+
+        for (x, y) in [(32, 32), (32, 17), (47, 32), (42, 42), (20, 40)] {
+            assert_eq!(segment_map.segment_of(x, y), None, "({}, {})", x, y);
+        }
+        assert!(segment_map.segment_of(32, 16).is_some());
+    }
+
+    #[test]
+    fn it_wraps_segments_around_theta_zero() {
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                8,
+                64,
+                64,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            64,
+            8,
+        );
+
+        assert_eq!(segment_map.segment_of(60, 31), Some(0));
+        assert_eq!(segment_map.segment_of(60, 32), Some(7));
+        assert_eq!(segment_map.segment_of(60, 33), Some(7));
+        assert_eq!(segment_map.segment_of(60, 26), Some(0));
+        assert_eq!(segment_map.segment_of(60, 38), Some(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "pixel out of bounds")]
+    fn it_panics_when_looking_up_a_pixel_outside_the_segment_map() {
+        SegmentMap::new(vec![None; 4], 2, 1).segment_of(2, 0);
+    }
+
+    #[test]
+    fn it_keeps_only_every_nth_pixel_when_subsampling() {
+        let segment_map = SegmentMap::new((0..16).map(|pixel| Some(pixel % 3)).collect(), 4, 3);
+        let subsampled = segment_map.subsample(2);
+
+        assert_eq!(subsampled.segment_of(0, 0), Some(0));
+        assert_eq!(subsampled.segment_of(2, 0), Some(2));
+        assert_eq!(subsampled.segment_of(2, 2), Some(1));
+        for (x, y) in [(1, 0), (0, 1), (3, 3), (2, 1)] {
+            assert_eq!(subsampled.segment_of(x, y), None, "({}, {})", x, y);
+        }
+        assert_eq!(
+            subsampled.segments().collect::<Vec<_>>(),
+            vec![(0, 0), (2, 2), (8, 2), (10, 1)]
+        );
+        assert_eq!(segment_map.subsample(1).segments().count(), 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "Subsample factor must be positive")]
+    fn it_rejects_a_zero_subsample_factor() {
+        SegmentMap::new(vec![None; 4], 2, 1).subsample(0);
+    }
+
+    #[test]
+    fn it_builds_the_default_segment_map() {
+        let segment_map = SegmentMapBuilder::new()
+            .num_leds(8)
+            .resolution(20, 10)
+            .build()
+            .unwrap();
+
+        assert_eq!(segment_map.num_leds(), 8);
+        assert_eq!((segment_map.width(), segment_map.height()), (20, 10));
+        assert_eq!(
+            segment_map.pixels(),
+            build_segment_map(
+                8,
+                20,
+                10,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn it_builds_a_cropped_segment_map_with_settings() {
+        let segment_map = SegmentMapBuilder::new()
+            .num_leds(4)
+            .resolution(12, 10)
+            .start_angle(90.0)
+            .inner_radius(0.2)
+            .outer_radius(Some(0.8))
+            .crop(Some((2, 1, 8, 8)))
+            .build()
+            .unwrap();
+
+        let orientation = Orientation {
+            rotation_degrees: 90.0,
+            ..Orientation::default()
+        };
+        assert_eq!(
+            segment_map.pixels(),
+            uncrop_segment_map(
+                &build_segment_map(4, 8, 8, orientation, 0.2, Some(0.8)),
+                (2, 1, 8, 8),
+                12,
+                10
+            )
+        );
+
+        let segment_map = SegmentMapBuilder::new()
+            .num_leds(1)
+            .resolution(6, 5)
+            .crop(Some((1, 2, 4, 3)))
+            .build_with(|width, height| {
+                assert_eq!((width, height), (4, 3));
+                vec![Some(0); 12]
+            })
+            .unwrap();
+        assert_eq!(segment_map.segment_of(0, 2), None);
+        assert_eq!(segment_map.segment_of(1, 2), Some(0));
+        assert_eq!(segment_map.segment_of(4, 4), Some(0));
+        assert_eq!(segment_map.segment_of(5, 4), None);
+
+        let boundaries = vec![0.0, FRAC_PI_2, PI, 3.0 * FRAC_PI_2];
+        let segment_map = SegmentMapBuilder::new()
+            .num_leds(4)
+            .resolution(8, 8)
+            .boundaries(boundaries.clone())
+            .build()
+            .unwrap();
+        assert_eq!(
+            segment_map.pixels(),
+            build_weighted_segment_map(
+                &boundaries,
+                8,
+                8,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None
+            )
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_builder_settings() {
+        let builder = SegmentMapBuilder::new().num_leds(4).resolution(8, 8);
+
+        assert_eq!(
+            SegmentMapBuilder::new().resolution(8, 8).build(),
+            Err(SegmentMapError::NoLeds)
+        );
+        assert_eq!(
+            builder.clone().resolution(0, 0).build(),
+            Err(SegmentMapError::EmptyResolution(0, 0))
+        );
+        assert_eq!(
+            builder.clone().boundaries(vec![0.0, PI]).build(),
+            Err(SegmentMapError::BoundaryCount(4, 2))
+        );
+        assert_eq!(
+            builder.clone().boundaries(vec![PI, 0.0, 1.0, 2.0]).build(),
+            Err(SegmentMapError::InvalidBoundaries)
+        );
+        assert_eq!(
+            builder.clone().crop(Some((4, 4, 8, 8))).build(),
+            Err(SegmentMapError::CropOutOfBounds((4, 4, 8, 8)))
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .zones(vec![ring_zone(1, 0.2, 0.5), ring_zone(2, 0.5, 1.0)])
+                .build(),
+            Err(SegmentMapError::ZoneCount(4, 3))
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .zones(vec![ring_zone(2, 0.2, 0.6), ring_zone(2, 0.5, 1.0)])
+                .build(),
+            Err(SegmentMapError::InvalidZones)
+        );
+        assert_eq!(
+            builder
+                .clone()
+                .zones(vec![ring_zone(2, 0.5, 0.5), ring_zone(2, 0.5, 1.0)])
+                .build(),
+            Err(SegmentMapError::InvalidZones)
+        );
+    }
+
+    fn ring_zone(num_leds: usize, inner_fraction: f64, outer_fraction: f64) -> RingZone {
+        RingZone {
+            num_leds,
+            inner_fraction,
+            outer_fraction,
+            start_angle: 0.0,
+            clockwise: true,
+        }
+    }
+
+    #[test]
+    fn it_parses_ring_zones() {
+        assert_eq!("24,0.3,0.6".parse(), Ok(ring_zone(24, 0.3, 0.6)));
+        assert_eq!(
+            "36, 0.6, 1.0, -90, ccw".parse(),
+            Ok(RingZone {
+                start_angle: -90.0,
+                clockwise: false,
+                ..ring_zone(36, 0.6, 1.0)
+            })
+        );
+        assert!("24,0.3".parse::<RingZone>().is_err());
+        assert!("24,0.3,0.6,0,up".parse::<RingZone>().is_err());
+    }
+
+    #[test]
+    fn it_maps_outer_band_pixels_past_the_inner_ring() {
+        let zones = vec![
+            ring_zone(4, 0.2, 0.5),
+            RingZone {
+                start_angle: 90.0,
+                clockwise: false,
+                ..ring_zone(8, 0.5, 1.0)
+            },
+        ];
+        assert_eq!(zone_ranges(&zones), vec![0..4, 4..12]);
+
+        let segment_map = SegmentMapBuilder::new()
+            .num_leds(12)
+            .resolution(41, 41)
+            .zones(zones)
+            .build()
+            .unwrap();
+        assert_eq!(segment_map.segment_of(20, 20), None);
+        assert!(segment_map.segment_of(27, 20).unwrap() < 4);
+        assert!(segment_map.segment_of(35, 20).unwrap() >= 4);
+
+        // The outer ring keeps its own start angle and direction
+        let outer_ring = build_segment_map(
+            8,
+            41,
+            41,
+            Orientation {
+                rotation_degrees: 90.0,
+                clockwise: false,
+                ..Orientation::default()
+            },
+            0.5,
+            Some(1.0),
+        );
+        for (pixel, segment) in segment_map.pixels().iter().enumerate() {
+            if let Some(segment @ 4..) = segment {
+                assert_eq!(outer_ring[pixel], Some(segment - 4));
+            }
+        }
+    }
+
+    #[test]
+    fn it_keeps_ring_zone_bands_from_overlapping() {
+        let segment_map = SegmentMapBuilder::new()
+            .num_leds(12)
+            .resolution(41, 41)
+            .zones(vec![ring_zone(4, 0.2, 0.5), ring_zone(8, 0.5, 1.0)])
+            .build()
+            .unwrap();
+
+        // Pixels on the shared edge of the bands belong to the outer zone
+        assert!(segment_map.segment_of(30, 20).unwrap() >= 4);
+        for y in 0..41 {
+            for x in 0..41 {
+                let radius = (20.0 - f64::from(x)).hypot(f64::from(y) - 20.0);
+                match segment_map.segment_of(x, y) {
+                    Some(segment) if segment < 4 => assert!((4.0..10.0).contains(&radius)),
+                    Some(_) => assert!((10.0..=20.0).contains(&radius)),
+                    None => assert!(!(4.0..=20.0).contains(&radius)),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_flattens_the_mapped_pixels_into_parallel_lookups() {
+        let segment_map = SegmentMap::new(vec![None, Some(1), Some(0), None, Some(1), None], 3, 2);
+
+        assert_eq!(segment_map.to_flat_lookup(), (vec![1, 2, 4], vec![1, 0, 1]));
+    }
+
+    #[test]
+    fn it_accumulates_the_same_sums_from_a_flat_lookup() {
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                8,
+                24,
+                16,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            24,
+            8,
+        );
+        let rgb: Vec<u8> = (0..24 * 16 * 3).map(|index| (index % 251) as u8).collect();
+
+        let mut sums = vec![(0, 0, 0); 8];
+        let mut counts = vec![0; 8];
+        accumulate_pixels(&rgb, segment_map.pixels(), &mut sums, &mut counts);
+
+        let (pixel_indices, segment_indices) = segment_map.to_flat_lookup();
+        let mut flat_sums = vec![(0, 0, 0); 8];
+        let mut flat_counts = vec![0; 8];
+        accumulate_flat_lookup(
+            &rgb,
+            (&pixel_indices, &segment_indices),
+            &mut flat_sums,
+            &mut flat_counts,
+        );
+
+        assert_eq!(flat_sums, sums);
+        assert_eq!(flat_counts, counts);
+    }
+
+    #[test]
+    fn it_round_trips_a_segment_map_through_bytes() {
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                8,
+                192,
+                108,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            192,
+            8,
+        );
+
+        let data = segment_map.to_bytes("8 leds at 192x108");
+        assert_eq!(
+            SegmentMap::from_bytes(&data, "8 leds at 192x108").unwrap(),
+            segment_map
+        );
+        assert!(data.len() < segment_map.pixels().len());
+    }
+
+    #[test]
+    fn it_rejects_mismatched_or_corrupt_segment_map_bytes() {
+        let segment_map = SegmentMap::new(vec![Some(0), None, Some(1), Some(1)], 2, 2);
+        let data = segment_map.to_bytes("params");
+
+        let error = |data: &[u8], params| {
+            SegmentMap::from_bytes(data, params)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(&data, "other params"),
+            "segment map was built with other parameters"
+        );
+        assert_eq!(error(b"PNG", "params"), "not a segment map file");
+
+        let mut version = data.clone();
+        version[4] += 1;
+        assert_eq!(error(&version, "params"), "unsupported segment map version");
+        assert_eq!(
+            error(&data[..data.len() - 1], "params"),
+            "segment map file is truncated"
+        );
+
+        let mut segment = data.clone();
+        let last = segment.len() - 4;
+        segment[last..].copy_from_slice(&2u32.to_le_bytes());
+        assert_eq!(
+            error(&segment, "params"),
+            "segment map references unknown LEDs"
+        );
+
+        let mut run = data.clone();
+        let last = run.len() - 8;
+        run[last..last + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(error(&run, "params"), "segment map file is corrupt");
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn it_averages_segments_in_parallel_like_the_serial_version() {
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                12,
+                64,
+                48,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            64,
+            12,
+        );
+        let rgb: Vec<u8> = (0..64 * 48 * 3).map(|index| (index % 251) as u8).collect();
+
+        let segment_index = SegmentIndex::new(&segment_map.subsample(1));
+        assert_eq!(
+            average_indexed_segment_colors_parallel(&rgb, &segment_index),
+            average_indexed_segment_colors_serial(&rgb, &segment_index)
+        );
+
+        let segment_index = segment_index
+            .with_radial_weights(&segment_map, 2.0)
+            .with_linear_light();
+        assert_eq!(
+            average_indexed_segment_colors_parallel(&rgb, &segment_index),
+            average_indexed_segment_colors_serial(&rgb, &segment_index)
+        );
+        assert_eq!(
+            average_indexed_segment_colors_parallel(&rgb[3..], &segment_index),
+            Err(FrameSizeError {
+                expected: 64 * 48 * 3,
+                actual: 64 * 48 * 3 - 3
+            })
+        );
+    }
+
+    #[test]
+    fn it_renders_each_segment_in_a_distinct_palette_color() {
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                13,
+                64,
+                48,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            64,
+            13,
+        );
+        let pixels = segment_map.render(false);
+
+        let color_of = |segment| {
+            let pixel = segment_map
+                .pixels()
+                .iter()
+                .position(|&other| other == Some(segment))
+                .unwrap();
+            pixels[pixel]
+        };
+        assert_eq!(pixels.len(), 64 * 48);
+        for (&pixel, segment) in pixels.iter().zip(segment_map.pixels()) {
+            assert_eq!(pixel, segment.map_or(0x000000, color_of));
+        }
+        for segment in 0..13 {
+            assert_ne!(
+                color_of(segment),
+                color_of((segment + 1) % 13),
+                "{}",
+                segment
+            );
+        }
+    }
+
+    #[test]
+    fn it_stamps_segment_labels_near_their_centroids() {
+        let segment_map = SegmentMap::new(vec![Some(7); 21 * 21], 21, 8);
+
+        let unlabeled = segment_map.render(false);
+        let labeled = segment_map.render(true);
+        assert!(unlabeled.iter().all(|&pixel| pixel == unlabeled[0]));
+        // The 7 glyph covers x 9..=11 and y 8..=12 around the center at (10, 10)
+        assert_eq!(labeled[8 * 21 + 9], 0xffffff);
+        assert_eq!(labeled[9 * 21 + 9], 0x000000);
+        assert_eq!(labeled[12 * 21 + 10], 0xffffff);
+        assert_eq!(labeled[0], unlabeled[0]);
+    }
+
+    #[test]
+    fn it_writes_the_rendered_segment_map_as_a_png() {
+        let path = env::temp_dir().join(format!("afterglow-segmap-{}.png", process::id()));
+        let segment_map = SegmentMap::new(
+            build_segment_map(
+                12,
+                32,
+                18,
+                Orientation::default(),
+                DEFAULT_EDGE_FRACTION,
+                None,
+            ),
+            32,
+            12,
+        );
+
+        segment_map.render_png(&path, true).unwrap();
+
+        let png = fs::read(&path).unwrap();
+        let mut reader = Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (32, 18));
+        assert_eq!(info.buffer_size(), 32 * 18 * 3);
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+use crate::output::OutputSink;
+use afterglow::led::LEDStrip;
+use afterglow::power::limit_power;
+use std::{io, thread, time::Duration};
+
+const BOOT_CHASE_DURATION: Duration = Duration::from_millis(1500);
+const BOOT_FLASH_DURATION: Duration = Duration::from_millis(300);
+const BOOT_FLASH_COLORS: [u32; 3] = [0xff0000, 0x00ff00, 0x0000ff];
+
+pub fn run_led_walk<const N: usize>(
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    delay: Duration,
+) -> io::Result<()> {
+    for index in 0..N {
+        if index > 0 {
+            led_strip.set_led(index - 1, 0x000000);
+        }
+        led_strip.set_led(index, 0xffffff);
+
+        sink.write(led_strip.get_spi_data())?;
+        thread::sleep(delay);
+    }
+
+    led_strip.set_led(N - 1, 0x000000);
+    sink.write(led_strip.get_spi_data())
+}
+
+pub fn run_boot_sequence<const N: usize>(
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    max_milliamps: Option<u32>,
+    mut sleep: impl FnMut(Duration),
+) -> io::Result<()> {
+    let colors: Vec<(u8, u8, u8)> = (0..N).map(|index| led_strip.get_led(index)).collect();
+    let mut show = |led_strip: &mut LEDStrip<N>, color: &dyn Fn(usize) -> u32| {
+        for index in 0..N {
+            led_strip.set_led(index, color(index));
+        }
+        if let Some(max_milliamps) = max_milliamps {
+            limit_power(led_strip, max_milliamps);
+        }
+        sink.write(led_strip.get_spi_data())
+    };
+
+    let chase_delay = BOOT_CHASE_DURATION / N as u32;
+    for lit in 0..N {
+        show(led_strip, &|index| {
+            if index == lit {
+                0xffffff
+            } else {
+                0x000000
+            }
+        })?;
+        sleep(chase_delay);
+    }
+    for flash_color in BOOT_FLASH_COLORS {
+        show(led_strip, &|_| flash_color)?;
+        sleep(BOOT_FLASH_DURATION);
+    }
+    show(led_strip, &|_| 0x000000)?;
+
+    for (index, &(r, g, b)) in colors.iter().enumerate() {
+        led_strip.set_led_rgb(index, r, g, b);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::VecSink;
+    use crate::self_test::{run_boot_sequence, run_led_walk};
+    use afterglow::led::{decode_spi_data, LEDStrip};
+    use std::time::Duration;
+
+    const W: (u8, u8, u8) = (255, 255, 255);
+    const K: (u8, u8, u8) = (0, 0, 0);
+    const R: (u8, u8, u8) = (255, 0, 0);
+    const G: (u8, u8, u8) = (0, 255, 0);
+    const B: (u8, u8, u8) = (0, 0, 255);
+
+    #[test]
+    fn it_lights_exactly_one_led_per_step() {
+        let mut led_strip: LEDStrip<4> = LEDStrip::new();
+        let mut sink = VecSink::default();
+
+        run_led_walk(&mut led_strip, &mut sink, Duration::ZERO).unwrap();
+
+        assert_eq!(sink.frames.len(), 5);
+        for (step, frame) in sink.frames[..4].iter().enumerate() {
+            let lit: Vec<usize> = decode_spi_data(frame)
+                .into_iter()
+                .enumerate()
+                .filter(|(_, color)| *color != (0, 0, 0))
+                .map(|(index, _)| index)
+                .collect();
+            assert_eq!(lit, vec![step]);
+            assert_eq!(decode_spi_data(frame)[step], (255, 255, 255));
+        }
+        assert_eq!(decode_spi_data(&sink.frames[4]), vec![(0, 0, 0); 4]);
+    }
+
+    #[test]
+    fn it_plays_the_boot_sequence() {
+        let mut led_strip = LEDStrip::new_with_data([0x4b8040; 3]);
+        let mut sink = VecSink::default();
+        let mut sleeps = Vec::new();
+
+        run_boot_sequence(&mut led_strip, &mut sink, None, |delay| sleeps.push(delay)).unwrap();
+
+        let frames: Vec<Vec<(u8, u8, u8)>> = sink
+            .frames
+            .iter()
+            .map(|frame| decode_spi_data(frame))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                vec![W, K, K],
+                vec![K, W, K],
+                vec![K, K, W],
+                vec![R, R, R],
+                vec![G, G, G],
+                vec![B, B, B],
+                vec![K, K, K],
+            ]
+        );
+        assert_eq!(
+            sleeps,
+            [
+                vec![Duration::from_millis(500); 3],
+                vec![Duration::from_millis(300); 3]
+            ]
+            .concat()
+        );
+        assert_eq!(led_strip.get_led(0), (0x4b, 0x80, 0x40));
+    }
+
+    #[test]
+    fn it_keeps_long_boot_sequences_short_and_within_the_power_limit() {
+        let mut led_strip: LEDStrip<300> = LEDStrip::new();
+        let mut sink = VecSink::default();
+        let mut total = Duration::ZERO;
+
+        run_boot_sequence(&mut led_strip, &mut sink, Some(1000), |delay| {
+            total += delay
+        })
+        .unwrap();
+
+        assert!(total < Duration::from_secs(3));
+        assert_eq!(sink.frames.len(), 304);
+        assert_eq!(decode_spi_data(&sink.frames[300])[0], (42, 0, 0));
+    }
+}
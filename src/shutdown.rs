@@ -0,0 +1,132 @@
+use crate::output::OutputSink;
+use afterglow::led::LEDStrip;
+use signal_hook::consts::TERM_SIGNALS;
+use signal_hook::flag;
+use std::{
+    io,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+pub const FADE_STEP: Duration = Duration::from_millis(20);
+
+pub fn install_signal_handlers() -> io::Result<Arc<AtomicBool>> {
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for &signal in TERM_SIGNALS {
+        // A second signal while still shutting down exits immediately
+        flag::register_conditional_shutdown(signal, 1, Arc::clone(&shutdown))?;
+        flag::register(signal, Arc::clone(&shutdown))?;
+    }
+
+    Ok(shutdown)
+}
+
+pub fn fade_out<const N: usize>(
+    led_strip: &mut LEDStrip<N>,
+    sink: &mut dyn OutputSink,
+    duration: Duration,
+    mut sleep: impl FnMut(Duration),
+) -> io::Result<()> {
+    let colors: Vec<(u8, u8, u8)> = (0..N).map(|index| led_strip.get_led(index)).collect();
+    let steps = (duration.as_millis() / FADE_STEP.as_millis()) as u32;
+
+    for step in (1..steps).rev() {
+        let factor = step as f32 / steps as f32;
+        let scale = |channel: u8| (f32::from(channel) * factor) as u8;
+        for (index, &(r, g, b)) in colors.iter().enumerate() {
+            led_strip.set_led_rgb(index, scale(r), scale(g), scale(b));
+        }
+
+        sink.write(led_strip.get_spi_data())?;
+        sleep(FADE_STEP);
+    }
+
+    for index in 0..N {
+        led_strip.set_led(index, 0x000000);
+    }
+    sink.write(led_strip.get_spi_data())
+}
+
+// Decays exponentially so the fade looks even, reaching 1/256 (dark on 8-bit
+// channels) by the end of the duration regardless of how often it is sampled
+pub fn idle_fade_factor(elapsed: Duration, duration: Duration) -> f32 {
+    if elapsed >= duration {
+        return 0.0;
+    }
+
+    (1.0 / 256.0_f32).powf(elapsed.as_secs_f32() / duration.as_secs_f32())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::output::VecSink;
+    use crate::shutdown::{fade_out, idle_fade_factor};
+    use afterglow::led::{decode_spi_data, LEDStrip};
+    use std::time::Duration;
+
+    #[test]
+    fn it_fades_to_black_in_steps() {
+        let mut led_strip = LEDStrip::new_with_data([0xc86432, 0xffffff]);
+        let mut sink = VecSink::default();
+        let mut sleeps = Vec::new();
+
+        fade_out(
+            &mut led_strip,
+            &mut sink,
+            Duration::from_millis(100),
+            |delay| sleeps.push(delay),
+        )
+        .unwrap();
+
+        let frames: Vec<Vec<(u8, u8, u8)>> = sink
+            .frames
+            .iter()
+            .map(|spi_data| decode_spi_data(spi_data))
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                vec![(160, 80, 40), (204, 204, 204)],
+                vec![(120, 60, 30), (153, 153, 153)],
+                vec![(80, 40, 20), (102, 102, 102)],
+                vec![(40, 20, 10), (51, 51, 51)],
+                vec![(0, 0, 0), (0, 0, 0)],
+            ]
+        );
+        assert_eq!(sleeps, vec![Duration::from_millis(20); 4]);
+    }
+
+    #[test]
+    fn it_turns_off_immediately_without_a_fade() {
+        let mut led_strip = LEDStrip::new_with_data([0xffffff; 3]);
+        let mut sink = VecSink::default();
+
+        fade_out(&mut led_strip, &mut sink, Duration::ZERO, |_| {
+            panic!("Should not sleep without a fade")
+        })
+        .unwrap();
+
+        assert_eq!(sink.frames.len(), 1);
+        assert_eq!(decode_spi_data(&sink.frames[0]), vec![(0, 0, 0); 3]);
+    }
+
+    #[test]
+    fn it_decays_to_black_within_the_idle_fade() {
+        let duration = Duration::from_millis(500);
+        let tick = Duration::from_millis(1000 / 30);
+
+        let mut elapsed = Duration::ZERO;
+        let mut previous = 255;
+        while elapsed < duration {
+            let level = (255.0 * idle_fade_factor(elapsed, duration)) as u8;
+            assert!(level <= previous);
+            previous = level;
+            elapsed += tick;
+        }
+
+        assert!(previous <= 2);
+        assert_eq!(idle_fade_factor(elapsed, duration), 0.0);
+        assert_eq!(idle_fade_factor(Duration::ZERO, duration), 1.0);
+        assert!((idle_fade_factor(duration / 2, duration) - 1.0 / 16.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,433 @@
+use afterglow::color::{clamp_u8, luminance};
+use afterglow::led::LEDStrip;
+
+const FLICKER_WINDOW: usize = 10;
+const FLICKER_VARIANCE_THRESHOLD: f32 = 64.0;
+const FLICKER_ALPHA: f32 = 0.25;
+
+pub struct HysteresisFilter<const N: usize> {
+    threshold: u8,
+    state: Option<[(u8, u8, u8); N]>,
+}
+
+impl<const N: usize> HysteresisFilter<N> {
+    pub fn new(threshold: u8) -> Self {
+        Self {
+            threshold,
+            state: None,
+        }
+    }
+
+    pub fn apply(&mut self, strip: &mut LEDStrip<N>) {
+        let Some(state) = self.state.as_mut() else {
+            self.state = Some(std::array::from_fn(|index| strip.get_led(index)));
+            return;
+        };
+
+        for (index, stored) in state.iter_mut().enumerate() {
+            let (r, g, b) = strip.get_led(index);
+            let exceeds = |old: u8, new: u8| old.abs_diff(new) > self.threshold;
+            if exceeds(stored.0, r) || exceeds(stored.1, g) || exceeds(stored.2, b) {
+                *stored = (r, g, b);
+            } else if *stored != (r, g, b) {
+                let (r, g, b) = *stored;
+                strip.set_led_rgb(index, r, g, b);
+            }
+        }
+    }
+}
+
+pub struct DeadBandFilter<const N: usize> {
+    threshold: f32,
+    state: Option<[(u8, u8, u8); N]>,
+}
+
+impl<const N: usize> DeadBandFilter<N> {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            state: None,
+        }
+    }
+
+    fn distance(old: (u8, u8, u8), new: (u8, u8, u8)) -> f32 {
+        let delta = |a: u8, b: u8| (f32::from(a) - f32::from(b)).powi(2);
+        ((delta(old.0, new.0) + delta(old.1, new.1) + delta(old.2, new.2)) / 3.0).sqrt()
+    }
+
+    pub fn apply(&mut self, strip: &mut LEDStrip<N>) {
+        let Some(state) = self.state.as_mut() else {
+            self.state = Some(std::array::from_fn(|index| strip.get_led(index)));
+            return;
+        };
+
+        for (index, stored) in state.iter_mut().enumerate() {
+            let color = strip.get_led(index);
+            if DeadBandFilter::<N>::distance(*stored, color) > self.threshold {
+                *stored = color;
+            } else if *stored != color {
+                let (r, g, b) = *stored;
+                strip.set_led_rgb(index, r, g, b);
+            }
+        }
+    }
+}
+
+pub struct FrameHistory<const N: usize> {
+    frames: Vec<[u32; N]>,
+    capacity: usize,
+    next: usize,
+}
+
+impl<const N: usize> FrameHistory<N> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "FrameHistory must hold at least one frame");
+
+        Self {
+            frames: Vec::with_capacity(capacity),
+            capacity,
+            next: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.next = 0;
+    }
+
+    pub fn update(&mut self, new_colors: [u32; N]) -> [u32; N] {
+        if self.frames.len() < self.capacity {
+            self.frames.push(new_colors);
+        } else {
+            self.frames[self.next] = new_colors;
+        }
+        self.next = (self.next + 1) % self.capacity;
+
+        let num_frames = self.frames.len() as u32;
+        std::array::from_fn(|index| {
+            let (r, g, b) = self.frames.iter().fold((0, 0, 0), |(r, g, b), frame| {
+                let [_, fr, fg, fb] = frame[index].to_be_bytes();
+                (r + u32::from(fr), g + u32::from(fg), b + u32::from(fb))
+            });
+
+            let mean = |total: u32| (total + num_frames / 2) / num_frames;
+            (mean(r) << 16) | (mean(g) << 8) | mean(b)
+        })
+    }
+}
+
+pub struct SceneChangeDetector<const N: usize> {
+    threshold: f32,
+    previous: Option<[u32; N]>,
+}
+
+impl<const N: usize> SceneChangeDetector<N> {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            previous: None,
+        }
+    }
+
+    fn mean_difference(old: &[u32; N], new: &[u32; N]) -> f32 {
+        let total: u32 = old
+            .iter()
+            .zip(new)
+            .flat_map(|(old, new)| {
+                let [_, r1, g1, b1] = old.to_be_bytes();
+                let [_, r2, g2, b2] = new.to_be_bytes();
+                [r1.abs_diff(r2), g1.abs_diff(g2), b1.abs_diff(b2)]
+            })
+            .map(u32::from)
+            .sum();
+        total as f32 / (N * 3) as f32
+    }
+
+    pub fn is_cut(&mut self, colors: [u32; N]) -> bool {
+        let is_cut = self
+            .previous
+            .is_some_and(|previous| Self::mean_difference(&previous, &colors) > self.threshold);
+        self.previous = Some(colors);
+        is_cut
+    }
+}
+
+pub struct FlickerDetector<const N: usize> {
+    window: Vec<[f32; N]>,
+    next: usize,
+    smoothed: Option<[[f32; 3]; N]>,
+    pub flickering: bool,
+}
+
+impl<const N: usize> FlickerDetector<N> {
+    pub fn new() -> Self {
+        Self {
+            window: Vec::with_capacity(FLICKER_WINDOW),
+            next: 0,
+            smoothed: None,
+            flickering: false,
+        }
+    }
+
+    fn max_variance(&self) -> f32 {
+        let num_frames = self.window.len() as f32;
+        (0..N)
+            .map(|index| {
+                let mean = self.window.iter().map(|frame| frame[index]).sum::<f32>() / num_frames;
+                self.window
+                    .iter()
+                    .map(|frame| (frame[index] - mean).powi(2))
+                    .sum::<f32>()
+                    / num_frames
+            })
+            .fold(0.0, f32::max)
+    }
+
+    pub fn apply(&mut self, colors: [u32; N]) -> [u32; N] {
+        let channels = colors.map(|color| {
+            let [_, r, g, b] = color.to_be_bytes();
+            [r, g, b]
+        });
+
+        let luminances = channels.map(|[r, g, b]| luminance(r, g, b));
+        if self.window.len() < FLICKER_WINDOW {
+            self.window.push(luminances);
+        } else {
+            self.window[self.next] = luminances;
+        }
+        self.next = (self.next + 1) % FLICKER_WINDOW;
+        self.flickering =
+            self.window.len() == FLICKER_WINDOW && self.max_variance() > FLICKER_VARIANCE_THRESHOLD;
+
+        // Track an exponential average every frame so suppression starts
+        // from the recent colors instead of jumping when flicker is detected
+        let alpha = if self.flickering { FLICKER_ALPHA } else { 1.0 };
+        let smoothed = self
+            .smoothed
+            .get_or_insert_with(|| channels.map(|channel| channel.map(f32::from)));
+        for (smoothed, channel) in smoothed.iter_mut().zip(channels) {
+            for (smoothed, value) in smoothed.iter_mut().zip(channel) {
+                *smoothed += (f32::from(value) - *smoothed) * alpha;
+            }
+        }
+
+        smoothed.map(|[r, g, b]| u32::from_be_bytes([0, clamp_u8(r), clamp_u8(g), clamp_u8(b)]))
+    }
+}
+
+pub fn blur_leds<const N: usize>(values: [u32; N], radius: usize) -> [u32; N] {
+    if radius == 0 {
+        return values;
+    }
+
+    std::array::from_fn(|index| {
+        let (mut r, mut g, mut b, mut total_weight) = (0, 0, 0, 0);
+        for offset in 0..=2 * radius {
+            let neighbor = (index + N * radius + offset - radius) % N;
+            let weight = (radius + 1 - offset.abs_diff(radius)) as u32;
+            let [_, nr, ng, nb] = values[neighbor].to_be_bytes();
+            r += u32::from(nr) * weight;
+            g += u32::from(ng) * weight;
+            b += u32::from(nb) * weight;
+            total_weight += weight;
+        }
+
+        let mean = |total: u32| (total + total_weight / 2) / total_weight;
+        (mean(r) << 16) | (mean(g) << 8) | mean(b)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::smoothing::{
+        blur_leds, DeadBandFilter, FlickerDetector, FrameHistory, HysteresisFilter,
+        SceneChangeDetector,
+    };
+    use afterglow::color::luminance;
+    use afterglow::led::LEDStrip;
+
+    #[test]
+    fn it_passes_the_first_frame_through() {
+        let mut filter = HysteresisFilter::new(8);
+        let mut strip = LEDStrip::new_with_data([0x4b8040, 0x000000]);
+
+        filter.apply(&mut strip);
+
+        assert_eq!(strip.get_led(0), (75, 128, 64));
+        assert_eq!(strip.get_led(1), (0, 0, 0));
+    }
+
+    #[test]
+    fn it_suppresses_changes_within_the_threshold() {
+        let mut filter = HysteresisFilter::new(8);
+        let mut strip = LEDStrip::new_with_data([0x4b8040]);
+        filter.apply(&mut strip);
+
+        strip.set_led(0, 0x4e8040);
+        filter.apply(&mut strip);
+
+        assert_eq!(strip.get_led(0), (75, 128, 64));
+    }
+
+    #[test]
+    fn it_passes_changes_beyond_the_threshold() {
+        let mut filter = HysteresisFilter::new(8);
+        let mut strip = LEDStrip::new_with_data([0x4b8040, 0x4b8040]);
+        filter.apply(&mut strip);
+
+        strip.set_led(0, 0x4b8a40);
+        strip.set_led(1, 0x4e8040);
+        filter.apply(&mut strip);
+
+        assert_eq!(strip.get_led(0), (75, 138, 64));
+        assert_eq!(strip.get_led(1), (75, 128, 64));
+    }
+
+    #[test]
+    fn it_compares_against_the_last_passed_color() {
+        let mut filter = HysteresisFilter::new(8);
+        let mut strip = LEDStrip::new_with_data([0x000000]);
+        filter.apply(&mut strip);
+
+        for step in [0x050505, 0x080808, 0x0a0a0a] {
+            strip.set_led(0, step);
+            filter.apply(&mut strip);
+        }
+
+        assert_eq!(strip.get_led(0), (10, 10, 10));
+    }
+
+    #[test]
+    fn it_measures_rgb_distance() {
+        assert_eq!(DeadBandFilter::<1>::distance((0, 0, 0), (0, 0, 0)), 0.0);
+        assert_eq!(DeadBandFilter::<1>::distance((0, 0, 0), (6, 6, 6)), 6.0);
+        assert_eq!(
+            DeadBandFilter::<1>::distance((10, 0, 0), (4, 0, 0)),
+            12f32.sqrt()
+        );
+    }
+
+    #[test]
+    fn it_holds_colors_inside_the_dead_band() {
+        let mut filter = DeadBandFilter::new(4.0);
+        let mut strip = LEDStrip::new_with_data([0x4b8040, 0x4b8040]);
+        filter.apply(&mut strip);
+
+        strip.set_led(0, 0x518040);
+        strip.set_led(1, 0x508545);
+        filter.apply(&mut strip);
+
+        assert_eq!(strip.get_led(0), (75, 128, 64));
+        assert_eq!(strip.get_led(1), (80, 133, 69));
+    }
+
+    #[test]
+    fn it_averages_the_stored_frames() {
+        let mut history = FrameHistory::new(3);
+
+        assert_eq!(history.update([0xff0000; 2]), [0xff0000; 2]);
+        assert_eq!(history.update([0xff0000; 2]), [0xff0000; 2]);
+        assert_eq!(history.update([0x0000ff; 2]), [0xaa0055; 2]);
+    }
+
+    #[test]
+    fn it_drops_the_oldest_frame_once_full() {
+        let mut history = FrameHistory::new(2);
+
+        history.update([0xff0000, 0x000000]);
+        history.update([0x0000ff, 0x000000]);
+        assert_eq!(history.update([0x0000ff, 0x4b8040]), [0x0000ff, 0x264020]);
+    }
+
+    #[test]
+    fn it_forgets_stored_frames_on_reset() {
+        let mut history = FrameHistory::new(3);
+
+        history.update([0xff0000; 2]);
+        history.update([0xff0000; 2]);
+        history.reset();
+        assert_eq!(history.update([0x0000ff; 2]), [0x0000ff; 2]);
+        assert_eq!(history.update([0x000000; 2]), [0x000080; 2]);
+    }
+
+    #[test]
+    fn it_detects_hard_cuts_between_frames() {
+        let mut detector = SceneChangeDetector::new(32.0);
+
+        assert!(!detector.is_cut([0x4b8040, 0x000000]));
+        assert!(!detector.is_cut([0x508545, 0x101010]));
+        assert!(detector.is_cut([0xffffff, 0xffffff]));
+        assert!(!detector.is_cut([0xf0f0f0, 0xffffff]));
+    }
+
+    #[test]
+    fn it_passes_steady_frames_through_the_flicker_detector() {
+        let mut detector = FlickerDetector::new();
+
+        for step in 0..20 {
+            let color = 0x404040 + step * 0x010101;
+            assert_eq!(detector.apply([color, 0x4b8040]), [color, 0x4b8040]);
+            assert!(!detector.flickering);
+        }
+    }
+
+    #[test]
+    fn it_dampens_flickering_segments() {
+        let mut detector = FlickerDetector::new();
+
+        let outputs: Vec<[u32; 2]> = (0..40)
+            .map(|frame| {
+                let flicker = if frame % 2 == 0 { 0x606060 } else { 0xa0a0a0 };
+                detector.apply([flicker, 0x4b8040])
+            })
+            .collect();
+        assert!(detector.flickering);
+
+        let luminances: Vec<f32> = outputs[20..]
+            .iter()
+            .map(|&[color, _]| {
+                let [_, r, g, b] = color.to_be_bytes();
+                luminance(r, g, b)
+            })
+            .collect();
+        let min = luminances.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = luminances.iter().copied().fold(0.0, f32::max);
+        assert!(max - min < 16.0, "{}..{}", min, max);
+        assert!((min + max) / 2.0 > 112.0 && (min + max) / 2.0 < 144.0);
+        assert_eq!(outputs[39][1], 0x4b8040);
+    }
+
+    #[test]
+    #[should_panic(expected = "FrameHistory must hold at least one frame")]
+    fn it_throws_when_holding_no_frames() {
+        FrameHistory::<1>::new(0);
+    }
+
+    #[test]
+    fn it_spreads_a_bright_led_into_its_neighbors_with_wraparound() {
+        let blurred = blur_leds(
+            [0xff0000, 0x000000, 0x000000, 0x000000, 0x000000, 0x000000],
+            1,
+        );
+        assert_eq!(
+            blurred,
+            [0x800000, 0x400000, 0x000000, 0x000000, 0x000000, 0x400000]
+        );
+
+        let blurred = blur_leds(
+            [0x000000, 0x000000, 0x00ff00, 0x000000, 0x000000, 0x000000],
+            2,
+        );
+        assert_eq!(
+            blurred,
+            [0x001c00, 0x003900, 0x005500, 0x003900, 0x001c00, 0x000000]
+        );
+    }
+
+    #[test]
+    fn it_leaves_colors_unchanged_without_a_blur_radius() {
+        let colors = [0xff0000, 0x4b8040, 0x0000ff];
+        assert_eq!(blur_leds(colors, 0), colors);
+        assert_eq!(blur_leds([0x4b8040; 4], 3), [0x4b8040; 4]);
+    }
+}
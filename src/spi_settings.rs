@@ -0,0 +1,240 @@
+use clap::ValueEnum;
+#[cfg(feature = "rpi")]
+use rppal::spi::{Bus, Mode, SlaveSelect};
+use std::fmt;
+
+pub const MIN_CLOCK_SPEED: u32 = 100_000;
+pub const MAX_CLOCK_SPEED: u32 = 32_000_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SpiBus {
+    #[default]
+    Spi0,
+    Spi1,
+    Spi2,
+    Spi3,
+    Spi4,
+    Spi5,
+    Spi6,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SpiSlaveSelect {
+    #[default]
+    Ss0,
+    Ss1,
+    Ss2,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum SpiMode {
+    #[default]
+    Mode0,
+    Mode1,
+    Mode2,
+    Mode3,
+}
+
+pub fn value_name(value: impl ValueEnum) -> String {
+    value.to_possible_value().unwrap().get_name().to_string()
+}
+
+pub fn parse_bus(s: &str) -> Result<SpiBus, String> {
+    SpiBus::from_str(s, true)
+        .or_else(|_| SpiBus::from_str(&format!("spi{}", s), true))
+        .map_err(|_| format!("unknown SPI bus: {} (expected spi0 - spi6)", s))
+}
+
+pub fn parse_slave_select(s: &str) -> Result<SpiSlaveSelect, String> {
+    SpiSlaveSelect::from_str(s, true)
+        .or_else(|_| SpiSlaveSelect::from_str(&format!("ss{}", s), true))
+        .map_err(|_| format!("unknown SPI slave select: {} (expected ss0 - ss2)", s))
+}
+
+pub fn parse_clock_speed(s: &str) -> Result<u32, String> {
+    let clock_speed: u32 = s
+        .parse()
+        .map_err(|_| format!("expected a clock speed in Hz, got: {}", s))?;
+    if !(MIN_CLOCK_SPEED..=MAX_CLOCK_SPEED).contains(&clock_speed) {
+        return Err(format!(
+            "clock speed {} Hz is outside the supported {} Hz - {} Hz range",
+            clock_speed, MIN_CLOCK_SPEED, MAX_CLOCK_SPEED
+        ));
+    }
+
+    Ok(clock_speed)
+}
+
+pub struct ClockFallback {
+    next_clock_speed: Option<u32>,
+    min_clock_speed: u32,
+}
+
+impl ClockFallback {
+    pub fn new(clock_speed: u32, min_clock_speed: u32) -> Self {
+        Self {
+            next_clock_speed: Some(clock_speed),
+            min_clock_speed: min_clock_speed.min(clock_speed),
+        }
+    }
+}
+
+impl Iterator for ClockFallback {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let clock_speed = self.next_clock_speed?;
+        self.next_clock_speed = (clock_speed > self.min_clock_speed)
+            .then(|| (clock_speed / 2).max(self.min_clock_speed));
+
+        Some(clock_speed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpiSettings {
+    pub bus: SpiBus,
+    pub slave_select: SpiSlaveSelect,
+    pub clock_speed: u32,
+    pub mode: SpiMode,
+}
+
+impl SpiSettings {
+    pub fn device_path(&self) -> String {
+        format!("/dev/spidev{}.{}", self.bus as u8, self.slave_select as u8)
+    }
+}
+
+impl fmt::Display for SpiSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} at {} Hz in {}",
+            value_name(self.bus),
+            value_name(self.slave_select),
+            self.clock_speed,
+            value_name(self.mode)
+        )
+    }
+}
+
+#[cfg(feature = "rpi")]
+impl From<SpiBus> for Bus {
+    fn from(bus: SpiBus) -> Self {
+        match bus {
+            SpiBus::Spi0 => Bus::Spi0,
+            SpiBus::Spi1 => Bus::Spi1,
+            SpiBus::Spi2 => Bus::Spi2,
+            SpiBus::Spi3 => Bus::Spi3,
+            SpiBus::Spi4 => Bus::Spi4,
+            SpiBus::Spi5 => Bus::Spi5,
+            SpiBus::Spi6 => Bus::Spi6,
+        }
+    }
+}
+
+#[cfg(feature = "rpi")]
+impl From<SpiSlaveSelect> for SlaveSelect {
+    fn from(slave_select: SpiSlaveSelect) -> Self {
+        match slave_select {
+            SpiSlaveSelect::Ss0 => SlaveSelect::Ss0,
+            SpiSlaveSelect::Ss1 => SlaveSelect::Ss1,
+            SpiSlaveSelect::Ss2 => SlaveSelect::Ss2,
+        }
+    }
+}
+
+#[cfg(feature = "rpi")]
+impl From<SpiMode> for Mode {
+    fn from(mode: SpiMode) -> Self {
+        match mode {
+            SpiMode::Mode0 => Mode::Mode0,
+            SpiMode::Mode1 => Mode::Mode1,
+            SpiMode::Mode2 => Mode::Mode2,
+            SpiMode::Mode3 => Mode::Mode3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spi_settings::{
+        parse_bus, parse_clock_speed, parse_slave_select, ClockFallback, SpiBus, SpiMode,
+        SpiSettings, SpiSlaveSelect,
+    };
+    #[cfg(feature = "rpi")]
+    use rppal::spi::{Bus, Mode, SlaveSelect};
+
+    #[test]
+    fn it_parses_bus_and_slave_select_names() {
+        assert_eq!(parse_bus("spi1"), Ok(SpiBus::Spi1));
+        assert_eq!(parse_bus("SPI6"), Ok(SpiBus::Spi6));
+        assert_eq!(parse_bus("0"), Ok(SpiBus::Spi0));
+        assert!(parse_bus("spi7").is_err());
+        assert!(parse_bus("i2c1").is_err());
+
+        assert_eq!(parse_slave_select("ss2"), Ok(SpiSlaveSelect::Ss2));
+        assert_eq!(parse_slave_select("1"), Ok(SpiSlaveSelect::Ss1));
+        assert!(parse_slave_select("ss3").is_err());
+    }
+
+    #[test]
+    fn it_rejects_clock_speeds_out_of_range() {
+        assert_eq!(parse_clock_speed("100000"), Ok(100_000));
+        assert_eq!(parse_clock_speed("8000000"), Ok(8_000_000));
+        assert_eq!(parse_clock_speed("32000000"), Ok(32_000_000));
+        assert_eq!(
+            parse_clock_speed("99999"),
+            Err(
+                "clock speed 99999 Hz is outside the supported 100000 Hz - 32000000 Hz range"
+                    .to_string()
+            )
+        );
+        assert!(parse_clock_speed("32000001").is_err());
+        assert!(parse_clock_speed("8MHz").is_err());
+    }
+
+    #[test]
+    fn it_halves_the_clock_speed_down_to_the_minimum() {
+        assert_eq!(
+            ClockFallback::new(16_000_000, 4_000_000).collect::<Vec<_>>(),
+            vec![16_000_000, 8_000_000, 4_000_000]
+        );
+        assert_eq!(
+            ClockFallback::new(16_000_000, 5_000_000).collect::<Vec<_>>(),
+            vec![16_000_000, 8_000_000, 5_000_000]
+        );
+        assert_eq!(
+            ClockFallback::new(16_000_000, 16_000_000).collect::<Vec<_>>(),
+            vec![16_000_000]
+        );
+        assert_eq!(
+            ClockFallback::new(1_000_000, 4_000_000).collect::<Vec<_>>(),
+            vec![1_000_000]
+        );
+    }
+
+    #[test]
+    fn it_describes_the_effective_settings() {
+        let settings = SpiSettings {
+            bus: SpiBus::Spi1,
+            slave_select: SpiSlaveSelect::Ss2,
+            clock_speed: 8_000_000,
+            mode: SpiMode::Mode0,
+        };
+        assert_eq!(settings.to_string(), "spi1 ss2 at 8000000 Hz in mode0");
+        assert_eq!(settings.device_path(), "/dev/spidev1.2");
+    }
+
+    #[cfg(feature = "rpi")]
+    #[test]
+    fn it_maps_settings_to_rppal_enums() {
+        assert_eq!(Bus::from(SpiBus::Spi0), Bus::Spi0);
+        assert_eq!(Bus::from(SpiBus::Spi1), Bus::Spi1);
+        assert_eq!(Bus::from(SpiBus::Spi6), Bus::Spi6);
+        assert_eq!(SlaveSelect::from(SpiSlaveSelect::Ss0), SlaveSelect::Ss0);
+        assert_eq!(SlaveSelect::from(SpiSlaveSelect::Ss2), SlaveSelect::Ss2);
+        assert_eq!(Mode::from(SpiMode::Mode0), Mode::Mode0);
+        assert_eq!(Mode::from(SpiMode::Mode3), Mode::Mode3);
+    }
+}
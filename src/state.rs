@@ -0,0 +1,71 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, SyncSender},
+    thread,
+};
+use tracing::warn;
+
+pub fn write_state_atomically(path: &Path, state: &[u8]) -> io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(state)?;
+    file.sync_all()?;
+
+    fs::rename(temp_path, path)
+}
+
+pub struct StatePersister {
+    sender: SyncSender<Vec<u8>>,
+}
+
+impl StatePersister {
+    pub fn spawn(path: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(1);
+
+        thread::spawn(move || {
+            let mut last_state = None;
+            for state in receiver {
+                if last_state.as_ref() == Some(&state) {
+                    continue;
+                }
+
+                match write_state_atomically(&path, &state) {
+                    Ok(()) => last_state = Some(state),
+                    Err(err) => {
+                        warn!("Failed to save LED state to {}: {}", path.display(), err)
+                    }
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub fn save(&self, state: Vec<u8>) {
+        let _ = self.sender.try_send(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::state::write_state_atomically;
+    use std::{env, fs, process};
+
+    #[test]
+    fn it_replaces_the_state_file_without_leaving_a_temp_file() {
+        let dir = env::temp_dir().join(format!("afterglow-state-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("leds.state");
+
+        write_state_atomically(&path, &[0xff, 0x00, 0x00]).unwrap();
+        write_state_atomically(&path, &[0x4b, 0x80, 0x40]).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), vec![0x4b, 0x80, 0x40]);
+        assert!(!path.with_extension("tmp").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
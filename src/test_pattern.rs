@@ -0,0 +1,137 @@
+use crate::color::{hsv_to_rgb, parse_hex_color};
+use std::{str::FromStr, time::Duration};
+
+const DEFAULT_CHASE_SPEED_MS: u64 = 100;
+const STATIC_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestPattern {
+    AllOff,
+    AllWhite,
+    Rainbow,
+    Chase { speed_ms: u64 },
+    Solid { color: u32 },
+    Indexed,
+}
+
+impl TestPattern {
+    pub fn color(&self, index: usize, num_leds: usize, tick: usize) -> u32 {
+        match self {
+            TestPattern::AllOff => 0x000000,
+            TestPattern::AllWhite => 0xffffff,
+            TestPattern::Rainbow => hsv_to_rgb((index * 360 / num_leds) as f32, 1.0, 1.0),
+            TestPattern::Chase { .. } => {
+                if index == tick % num_leds {
+                    0xffffff
+                } else {
+                    0x000000
+                }
+            }
+            TestPattern::Solid { color } => *color,
+            TestPattern::Indexed => (index as u32) & 0xffffff,
+        }
+    }
+
+    pub fn frame_delay(&self) -> Duration {
+        match self {
+            TestPattern::Chase { speed_ms } => Duration::from_millis(*speed_ms),
+            _ => STATIC_FRAME_DELAY,
+        }
+    }
+}
+
+impl FromStr for TestPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+
+        match (name, arg) {
+            ("off", None) => Ok(TestPattern::AllOff),
+            ("white", None) => Ok(TestPattern::AllWhite),
+            ("rainbow", None) => Ok(TestPattern::Rainbow),
+            ("indexed", None) => Ok(TestPattern::Indexed),
+            ("chase", None) => Ok(TestPattern::Chase {
+                speed_ms: DEFAULT_CHASE_SPEED_MS,
+            }),
+            ("chase", Some(speed_ms)) => match speed_ms.parse() {
+                Ok(speed_ms) if speed_ms > 0 => Ok(TestPattern::Chase { speed_ms }),
+                _ => Err(format!("expected a chase speed in ms, got: {}", speed_ms)),
+            },
+            ("solid", Some(hex)) => parse_hex_color(hex).map(|color| TestPattern::Solid { color }),
+            _ => Err(format!("unknown test pattern: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_pattern::TestPattern;
+    use std::time::Duration;
+
+    #[test]
+    fn it_parses_test_patterns() {
+        assert_eq!("off".parse(), Ok(TestPattern::AllOff));
+        assert_eq!("white".parse(), Ok(TestPattern::AllWhite));
+        assert_eq!("rainbow".parse(), Ok(TestPattern::Rainbow));
+        assert_eq!("indexed".parse(), Ok(TestPattern::Indexed));
+        assert_eq!("chase".parse(), Ok(TestPattern::Chase { speed_ms: 100 }));
+        assert_eq!("chase:25".parse(), Ok(TestPattern::Chase { speed_ms: 25 }));
+        assert_eq!(
+            "solid:4b8040".parse(),
+            Ok(TestPattern::Solid { color: 0x4b8040 })
+        );
+        assert!("chase:0".parse::<TestPattern>().is_err());
+        assert!("chase:fast".parse::<TestPattern>().is_err());
+        assert!("solid".parse::<TestPattern>().is_err());
+        assert!("solid:4b80".parse::<TestPattern>().is_err());
+        assert!("solid:zzzzzz".parse::<TestPattern>().is_err());
+        assert!("rainbow:2".parse::<TestPattern>().is_err());
+        assert!("sparkle".parse::<TestPattern>().is_err());
+    }
+
+    #[test]
+    fn it_fills_a_solid_color() {
+        let pattern = TestPattern::Solid { color: 0x4b8040 };
+        for index in 0..4 {
+            assert_eq!(pattern.color(index, 4, 0), 0x4b8040);
+            assert_eq!(pattern.color(index, 4, 7), 0x4b8040);
+            assert_eq!(TestPattern::AllOff.color(index, 4, 0), 0x000000);
+            assert_eq!(TestPattern::AllWhite.color(index, 4, 0), 0xffffff);
+        }
+    }
+
+    #[test]
+    fn it_spreads_the_hue_wheel_across_leds() {
+        let colors: Vec<u32> = (0..6)
+            .map(|index| TestPattern::Rainbow.color(index, 6, 0))
+            .collect();
+        assert_eq!(
+            colors,
+            vec![0xff0000, 0xffff00, 0x00ff00, 0x00ffff, 0x0000ff, 0xff00ff]
+        );
+    }
+
+    #[test]
+    fn it_advances_the_chase_each_tick() {
+        let pattern = TestPattern::Chase { speed_ms: 50 };
+        assert_eq!(pattern.frame_delay(), Duration::from_millis(50));
+        for tick in 0..8 {
+            let lit: Vec<usize> = (0..4)
+                .filter(|&index| pattern.color(index, 4, tick) != 0)
+                .collect();
+            assert_eq!(lit, vec![tick % 4]);
+        }
+    }
+
+    #[test]
+    fn it_shows_each_led_index_as_its_color() {
+        let colors: Vec<u32> = (0..4)
+            .map(|index| TestPattern::Indexed.color(index, 4, 3))
+            .collect();
+        assert_eq!(colors, vec![0x000000, 0x000001, 0x000002, 0x000003]);
+    }
+}